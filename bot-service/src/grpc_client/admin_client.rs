@@ -0,0 +1,94 @@
+use shared::errors::{Result, ServiceError};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::Channel;
+use std::time::Duration;
+
+pub mod game {
+    tonic::include_proto!("game");
+}
+
+use game::admin_service_client::AdminServiceClient as GrpcAdminServiceClient;
+pub use game::*;
+
+/// Calls game-service's AdminService, attaching the shared admin token to every
+/// request.
+#[derive(Clone)]
+pub struct AdminServiceClient {
+    client: GrpcAdminServiceClient<Channel>,
+    authorization: MetadataValue<Ascii>,
+}
+
+impl AdminServiceClient {
+    pub async fn connect(url: String, admin_token: &str) -> Result<Self> {
+        let authorization = format!("Bearer {}", admin_token)
+            .parse()
+            .map_err(|_| ServiceError::Internal("ADMIN_TOKEN must be printable ASCII".to_string()))?;
+
+        let endpoint = Channel::from_shared(url.clone())
+            .map_err(|e| ServiceError::Grpc(format!("Invalid URL {}: {}", url, e)))?
+            .connect_timeout(Duration::from_millis(1000))
+            .timeout(Duration::from_secs(5))
+            .tcp_nodelay(true);
+
+        let client = GrpcAdminServiceClient::connect(endpoint)
+            .await
+            .map_err(|e| {
+                ServiceError::Grpc(format!(
+                    "Failed to connect to Admin Service at {}: {}",
+                    url, e
+                ))
+            })?;
+
+        tracing::info!("Connected to Admin Service at {}", url);
+
+        Ok(Self { client, authorization })
+    }
+
+    fn request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request
+            .metadata_mut()
+            .insert("authorization", self.authorization.clone());
+        request
+    }
+
+    pub async fn ban_user(&mut self, user_id: String, reason: String, actor: String) -> Result<AdminActionResponse> {
+        let request = self.request(BanUserRequest { user_id, reason, actor });
+
+        Ok(self.client.ban_user(request).await?.into_inner())
+    }
+
+    pub async fn unban_user(&mut self, user_id: String, reason: String, actor: String) -> Result<AdminActionResponse> {
+        let request = self.request(UnbanUserRequest { user_id, reason, actor });
+
+        Ok(self.client.unban_user(request).await?.into_inner())
+    }
+
+    pub async fn reset_score(&mut self, user_id: String, reason: String, actor: String) -> Result<AdjustScoreResponse> {
+        let request = self.request(AdjustScoreRequest {
+            user_id,
+            change: Some(adjust_score_request::Change::SetTo(0)),
+            reason,
+            actor,
+        });
+
+        Ok(self.client.adjust_score(request).await?.into_inner())
+    }
+
+    /// Number of users the anti-cheat has given `action`; empty for any action.
+    pub async fn count_flagged_users(&mut self, action: &str) -> Result<i64> {
+        let request = self.request(ListFlaggedUsersRequest {
+            limit: 1,
+            offset: 0,
+            action: action.to_string(),
+        });
+
+        Ok(self.client.list_flagged_users(request).await?.into_inner().total_count)
+    }
+
+    pub async fn list_telegram_ids(&mut self, after_telegram_id: i64, limit: i32) -> Result<Vec<i64>> {
+        let request = self.request(ListTelegramIdsRequest { after_telegram_id, limit });
+
+        Ok(self.client.list_telegram_ids(request).await?.into_inner().telegram_ids)
+    }
+}
@@ -0,0 +1,286 @@
+use crate::grpc_client::game_client::{ClickStreamRequest, ClickStreamResponse, ProcessClickRequest, ProcessClickResponse};
+use crate::grpc_client::{GameServiceClient, GrpcClientPool};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use shared::errors::{Result, ServiceError};
+use shared::sharding::stable_hash;
+use shared::ShardMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+const OUTBOUND_BUFFER: usize = 1024;
+const ACK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The client's own clock, in ms since the epoch, at a batch's first and last
+/// click. Zero when the client didn't send them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchTimes {
+    pub started_at_ms: i64,
+    pub ended_at_ms: i64,
+}
+
+type PendingAcks = Arc<Mutex<HashMap<u64, oneshot::Sender<ClickStreamResponse>>>>;
+
+/// One long-lived `ClickStream` RPC shared by many WebSocket connections.
+/// Each batch gets a request id and waits for the matching ack.
+#[derive(Clone)]
+pub struct ClickStreamHandle {
+    outbound: mpsc::Sender<ClickStreamRequest>,
+    pending: PendingAcks,
+    next_request_id: Arc<AtomicU64>,
+    closed: Arc<AtomicBool>,
+}
+
+impl ClickStreamHandle {
+    pub async fn open(client: &mut GameServiceClient) -> Result<Self> {
+        let (outbound, outbound_rx) = mpsc::channel(OUTBOUND_BUFFER);
+        let mut inbound = client.click_stream(outbound_rx).await?;
+
+        let pending: PendingAcks = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let reader_pending = pending.clone();
+        let reader_closed = closed.clone();
+        tokio::spawn(async move {
+            while let Some(message) = inbound.next().await {
+                match message {
+                    Ok(ack) => dispatch_ack(&reader_pending, ack),
+                    Err(status) => {
+                        tracing::warn!(error = %status, "Click stream failed");
+                        break;
+                    }
+                }
+            }
+
+            reader_closed.store(true, Ordering::Release);
+            // Dropping the senders fails every batch still waiting on this stream
+            reader_pending.lock().unwrap().clear();
+            shared::record_counter("click_stream.closed", 1);
+            tracing::info!("Click stream closed");
+        });
+
+        shared::record_counter("click_stream.opened", 1);
+
+        Ok(Self {
+            outbound,
+            pending,
+            next_request_id: Arc::new(AtomicU64::new(1)),
+            closed,
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) || self.outbound.is_closed()
+    }
+
+    pub async fn process_click(
+        &self,
+        user_id: String,
+        telegram_id: i64,
+        session_id: String,
+        click_count: u32,
+        times: BatchTimes,
+    ) -> Result<ProcessClickResponse> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (ack_tx, ack_rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, ack_tx);
+
+        let request = ClickStreamRequest {
+            request_id,
+            click: Some(ProcessClickRequest {
+                user_id,
+                telegram_id,
+                session_id,
+                click_count,
+                batch_started_at_ms: times.started_at_ms,
+                batch_ended_at_ms: times.ended_at_ms,
+            }),
+        };
+
+        if self.outbound.clone().send(request).await.is_err() {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(ServiceError::Grpc("Click stream is closed".to_string()));
+        }
+
+        match tokio::time::timeout(ACK_TIMEOUT, ack_rx).await {
+            Ok(Ok(ack)) => ack
+                .result
+                .ok_or_else(|| ServiceError::Grpc("Click ack without result".to_string())),
+            Ok(Err(_)) => Err(ServiceError::Grpc("Click stream closed before ack".to_string())),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&request_id);
+                Err(ServiceError::Grpc("Timed out waiting for click ack".to_string()))
+            }
+        }
+    }
+}
+
+fn dispatch_ack(pending: &PendingAcks, ack: ClickStreamResponse) {
+    match pending.lock().unwrap().remove(&ack.request_id) {
+        Some(waiter) => {
+            let _ = waiter.send(ack);
+        }
+        None => {
+            tracing::debug!(request_id = ack.request_id, "Dropping ack for abandoned click batch");
+        }
+    }
+}
+
+/// Keeps a small, fixed number of click streams open to each game-service shard
+/// and reopens any that close. Users go to the shard that owns them in the shard
+/// map, and one user's batches always share a stream.
+pub struct ClickStreamPool {
+    shard_map: Arc<ShardMap>,
+    /// One client per shard, in shard map order.
+    clients: Arc<GrpcClientPool<GameServiceClient>>,
+    streams_per_shard: usize,
+    streams: Vec<tokio::sync::Mutex<Option<ClickStreamHandle>>>,
+}
+
+impl ClickStreamPool {
+    pub fn new(
+        shard_map: Arc<ShardMap>,
+        clients: Arc<GrpcClientPool<GameServiceClient>>,
+        streams_per_shard: usize,
+    ) -> Self {
+        let streams_per_shard = streams_per_shard.max(1);
+        let streams = (0..shard_map.len() * streams_per_shard)
+            .map(|_| tokio::sync::Mutex::new(None))
+            .collect();
+
+        Self {
+            shard_map,
+            clients,
+            streams_per_shard,
+            streams,
+        }
+    }
+
+    pub fn shard_for(&self, user_id: &str) -> usize {
+        self.shard_map.shard_index_for(user_id)
+    }
+
+    /// Sends a click batch to `shard`. If that shard no longer owns the user, as
+    /// while shards are being added, the batch is retried once on the owner it names.
+    pub async fn process_click(
+        &self,
+        shard: usize,
+        user_id: String,
+        telegram_id: i64,
+        session_id: String,
+        click_count: u32,
+        times: BatchTimes,
+    ) -> Result<ProcessClickResponse> {
+        let stream = self.stream_for(shard, &user_id).await?;
+        let response = stream
+            .process_click(user_id.clone(), telegram_id, session_id.clone(), click_count, times)
+            .await?;
+
+        if response.success || response.owner_shard.is_empty() {
+            return Ok(response);
+        }
+
+        match self.shard_map.index_of(&response.owner_shard) {
+            Some(owner) if owner != shard => {
+                tracing::warn!(
+                    user_id = %user_id,
+                    from_shard = shard,
+                    owner = %response.owner_shard,
+                    "Click sent to wrong shard, redirecting"
+                );
+                shared::record_counter("click_stream.redirected", 1);
+
+                let stream = self.stream_for(owner, &user_id).await?;
+                stream
+                    .process_click(user_id, telegram_id, session_id, click_count, times)
+                    .await
+            }
+            _ => Ok(response),
+        }
+    }
+
+    fn slot_for(&self, shard: usize, user_id: &str) -> usize {
+        let offset = (stable_hash(user_id) % self.streams_per_shard as u64) as usize;
+        (shard % self.shard_map.len()) * self.streams_per_shard + offset
+    }
+
+    async fn stream_for(&self, shard: usize, user_id: &str) -> Result<ClickStreamHandle> {
+        let mut slot = self.streams[self.slot_for(shard, user_id)].lock().await;
+
+        if let Some(stream) = slot.as_ref().filter(|stream| !stream.is_closed()) {
+            return Ok(stream.clone());
+        }
+
+        let client_mutex = self.clients.get_client_by_shard(shard);
+        let mut client = client_mutex.lock().await;
+        let stream = ClickStreamHandle::open(&mut client).await?;
+
+        tracing::info!(shard = shard, "Opened click stream");
+        *slot = Some(stream.clone());
+
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispatch_ack_routes_by_request_id() {
+        let pending: PendingAcks = Arc::new(Mutex::new(HashMap::new()));
+        let (first_tx, mut first_rx) = oneshot::channel();
+        let (second_tx, mut second_rx) = oneshot::channel();
+        pending.lock().unwrap().insert(1, first_tx);
+        pending.lock().unwrap().insert(2, second_tx);
+
+        dispatch_ack(
+            &pending,
+            ClickStreamResponse {
+                request_id: 2,
+                user_id: "user-b".to_string(),
+                result: None,
+            },
+        );
+
+        assert_eq!(second_rx.try_recv().unwrap().user_id, "user-b");
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(pending.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_slot_stays_within_owning_shard() {
+        let shard_map = ShardMap::parse("game-1=http://a:50051,game-2=http://b:50051").unwrap();
+        let channel = tonic::transport::Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        let clients = GrpcClientPool::new(vec![GameServiceClient::new(channel); 2]);
+        let pool = ClickStreamPool::new(Arc::new(shard_map), Arc::new(clients), 4);
+
+        for n in 0..100 {
+            let user_id = format!("user-{}", n);
+            let shard = pool.shard_for(&user_id);
+            let slot = pool.slot_for(shard, &user_id);
+
+            assert_eq!(slot / 4, shard);
+            assert_eq!(slot, pool.slot_for(shard, &user_id));
+        }
+    }
+
+    #[test]
+    fn test_dispatch_ack_ignores_unknown_request() {
+        let pending: PendingAcks = Arc::new(Mutex::new(HashMap::new()));
+
+        dispatch_ack(
+            &pending,
+            ClickStreamResponse {
+                request_id: 99,
+                user_id: String::new(),
+                result: None,
+            },
+        );
+
+        assert!(pending.lock().unwrap().is_empty());
+    }
+}
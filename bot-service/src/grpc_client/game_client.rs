@@ -52,10 +52,12 @@ impl GameServiceClient {
         &mut self,
         telegram_id: i64,
         username: String,
+        referral_code: Option<String>,
     ) -> Result<CreateUserResponse> {
         let request = tonic::Request::new(CreateUserRequest {
             telegram_id,
             username,
+            referral_code: referral_code.unwrap_or_default(),
         });
 
         let response = self.client.create_user(request).await?.into_inner();
@@ -86,6 +88,7 @@ impl GameServiceClient {
         Ok(response)
     }
 
+    #[allow(dead_code)]
     pub async fn process_click(
         &mut self,
         user_id: String,
@@ -97,8 +100,9 @@ impl GameServiceClient {
             user_id,
             telegram_id,
             session_id,
-            timestamp: chrono::Utc::now().timestamp(),
             click_count,
+            batch_started_at_ms: 0,
+            batch_ended_at_ms: 0,
         });
 
         let response = self.client.process_click(request).await?.into_inner();
@@ -106,6 +110,16 @@ impl GameServiceClient {
         Ok(response)
     }
 
+    pub async fn click_stream<S>(&mut self, batches: S) -> Result<tonic::Streaming<ClickStreamResponse>>
+    where
+        S: futures::Stream<Item = ClickStreamRequest> + Send + 'static,
+    {
+        let response = self.client.click_stream(batches).await?.into_inner();
+
+        Ok(response)
+    }
+
+    #[allow(dead_code)]
     pub async fn start_session(
         &mut self,
         user_id: String,
@@ -123,6 +137,7 @@ impl GameServiceClient {
         Ok(response)
     }
 
+    #[allow(dead_code)]
     pub async fn heartbeat(&mut self, session_id: String) -> Result<HeartbeatResponse> {
         let request = tonic::Request::new(HeartbeatRequest { session_id });
 
@@ -131,6 +146,7 @@ impl GameServiceClient {
         Ok(response)
     }
 
+    #[allow(dead_code)]
     pub async fn end_session(&mut self, session_id: String) -> Result<EndSessionResponse> {
         let request = tonic::Request::new(EndSessionRequest { session_id });
 
@@ -52,10 +52,20 @@ impl LeaderboardServiceClient {
         &mut self,
         limit: Option<i32>,
         offset: Option<i32>,
+    ) -> Result<GetLeaderboardResponse> {
+        self.get_period_leaderboard(LeaderboardPeriod::AllTime, limit, offset).await
+    }
+
+    pub async fn get_period_leaderboard(
+        &mut self,
+        period: LeaderboardPeriod,
+        limit: Option<i32>,
+        offset: Option<i32>,
     ) -> Result<GetLeaderboardResponse> {
         let request = tonic::Request::new(GetLeaderboardRequest {
             limit: limit.unwrap_or(20),
             offset: offset.unwrap_or(0),
+            period: period as i32,
         });
 
         let response = self
@@ -68,13 +78,58 @@ impl LeaderboardServiceClient {
     }
 
     pub async fn get_user_rank(&mut self, user_id: String) -> Result<GetUserRankResponse> {
-        let request = tonic::Request::new(GetUserRankRequest { user_id });
+        let request = tonic::Request::new(GetUserRankRequest {
+            user_id,
+            period: LeaderboardPeriod::AllTime as i32,
+        });
 
         let response = self.client.get_user_rank(request).await?.into_inner();
 
         Ok(response)
     }
 
+    pub async fn get_leaderboard_around_user(
+        &mut self,
+        user_id: String,
+        radius: i32,
+    ) -> Result<GetLeaderboardAroundUserResponse> {
+        let request = tonic::Request::new(GetLeaderboardAroundUserRequest {
+            user_id,
+            radius,
+            period: LeaderboardPeriod::AllTime as i32,
+        });
+
+        let response = self.client.get_leaderboard_around_user(request).await?.into_inner();
+
+        Ok(response)
+    }
+
+    pub async fn get_friends_leaderboard(
+        &mut self,
+        user_id: String,
+        limit: Option<i32>,
+    ) -> Result<GetFriendsLeaderboardResponse> {
+        let request = tonic::Request::new(GetFriendsLeaderboardRequest {
+            user_id,
+            limit: limit.unwrap_or(20),
+        });
+
+        let response = self.client.get_friends_leaderboard(request).await?.into_inner();
+
+        Ok(response)
+    }
+
+    pub async fn subscribe_user_rank(
+        &mut self,
+        user_ids: Vec<String>,
+    ) -> Result<tonic::Streaming<UserRankUpdate>> {
+        let request = tonic::Request::new(SubscribeUserRankRequest { user_ids });
+
+        let response = self.client.subscribe_user_rank(request).await?.into_inner();
+
+        Ok(response)
+    }
+
     pub async fn get_global_stats(&mut self) -> Result<GetGlobalStatsResponse> {
         let request = tonic::Request::new(GetGlobalStatsRequest {});
 
@@ -1,7 +1,11 @@
+pub mod admin_client;
+pub mod click_stream;
 pub mod game_client;
 pub mod leaderboard_client;
 pub mod pool;
 
+pub use admin_client::AdminServiceClient;
+pub use click_stream::ClickStreamPool;
 pub use game_client::GameServiceClient;
 pub use leaderboard_client::LeaderboardServiceClient;
-pub use pool::{GrpcClientPool, get_shard_for_user};
+pub use pool::GrpcClientPool;
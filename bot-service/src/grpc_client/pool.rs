@@ -1,7 +1,5 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use tokio::sync::Mutex;
 
 pub struct GrpcClientPool<T> {
@@ -36,12 +34,6 @@ impl<T> GrpcClientPool<T> {
     }
 }
 
-pub fn get_shard_for_user(user_id: &str, pool_size: usize) -> usize {
-    let mut hasher = DefaultHasher::new();
-    user_id.hash(&mut hasher);
-    (hasher.finish() as usize) % pool_size
-}
-
 impl<T> Clone for GrpcClientPool<T> {
     fn clone(&self) -> Self {
         Self {
@@ -5,8 +5,9 @@ mod telegram;
 mod websocket;
 
 use axum::{routing::get, Router};
-use grpc_client::{GameServiceClient, LeaderboardServiceClient, GrpcClientPool};
+use grpc_client::{AdminServiceClient, ClickStreamPool, GameServiceClient, LeaderboardServiceClient, GrpcClientPool};
 use state::State;
+use telegram::AdminTools;
 use std::env;
 use std::sync::Arc;
 use std::time::Duration;
@@ -14,9 +15,9 @@ use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::prelude::*;
 use tonic::transport::Channel;
 use tower_http::services::ServeDir;
-use tracing_subscriber;
-use websocket::{AppState, LeaderboardBroadcaster};
+use websocket::{AppState, InitDataValidator, LeaderboardBroadcaster, RankUpdateRouter};
 use shared::config::BatchConfig;
+use shared::{AdminAuth, Shard, ShardMap};
 
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
 
@@ -50,18 +51,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("WEBSOCKET_PORT must be a valid port number");
 
 
+    let init_data_max_age_secs: i64 = env::var("INIT_DATA_MAX_AGE_SECS")
+        .unwrap_or_else(|_| "86400".to_string())
+        .parse()
+        .expect("INIT_DATA_MAX_AGE_SECS must be a number of seconds");
+
     let enable_telegram_polling = env::var("ENABLE_TELEGRAM_POLLING")
         .unwrap_or_else(|_| "true".to_string())
         .to_lowercase() == "true";
 
     let batch_config = BatchConfig::from_env()?;
 
+    let admin_telegram_ids = telegram::admin::parse_admin_ids(
+        &env::var("ADMIN_TELEGRAM_IDS").unwrap_or_default(),
+    )?;
+    let admin_token = AdminAuth::token_from_env();
+
     tracing::info!("Configuration:");
     tracing::info!("  Game Service URL: {}", game_service_url);
     tracing::info!("  Leaderboard Service URL: {}", leaderboard_service_url);
     tracing::info!("  Mini App URL: {}", mini_app_url);
     tracing::info!("  WebSocket Port: {}", websocket_port);
     tracing::info!("  Telegram Polling Enabled: {}", enable_telegram_polling);
+    tracing::info!("  initData Max Age: {}s", init_data_max_age_secs);
+    tracing::info!("  Telegram Admins: {}", admin_telegram_ids.len());
     tracing::info!("  Leaderboard Broadcast Interval: {}ms", batch_config.leaderboard_broadcast_interval_ms);
 
 
@@ -70,6 +83,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .unwrap_or(20);
 
+    let click_stream_count: usize = env::var("GRPC_CLICK_STREAMS")
+        .unwrap_or_else(|_| "4".to_string())
+        .parse()
+        .unwrap_or(4);
+
     tracing::info!("Creating gRPC connection pool (size: {})...", grpc_pool_size);
 
     tracing::info!("Connecting to Game Service pool...");
@@ -92,6 +110,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let game_client_pool = Arc::new(GrpcClientPool::new(game_clients));
     tracing::info!("Game Service pool ready ({} connections)", grpc_pool_size);
 
+    let shard_map = Arc::new(ShardMap::from_env(Shard {
+        id: "game-1".to_string(),
+        url: game_service_url.clone(),
+    })?);
+
+    tracing::info!("Connecting to {} game-service shard(s) for click ingestion...", shard_map.len());
+    let mut shard_clients = Vec::new();
+    for shard in shard_map.shards() {
+        let channel = Channel::from_shared(shard.url.clone())?
+            .concurrency_limit(256)
+            .initial_stream_window_size(1024 * 1024)
+            .initial_connection_window_size(10 * 1024 * 1024)
+            .tcp_nodelay(true)
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .connect()
+            .await
+            .map_err(|e| format!("Failed to connect to game-service shard {}: {}", shard.id, e))?;
+
+        shard_clients.push(GameServiceClient::new(channel));
+        tracing::debug!("Connected game-service shard {} at {}", shard.id, shard.url);
+    }
+    let shard_client_pool = Arc::new(GrpcClientPool::new(shard_clients));
+    tracing::info!("Game Service shard clients ready ({} shards)", shard_client_pool.size());
+
+    let click_streams = Arc::new(ClickStreamPool::new(
+        shard_map.clone(),
+        shard_client_pool,
+        click_stream_count,
+    ));
+    tracing::info!("Click ingestion will use {} gRPC streams per shard", click_stream_count);
+
     tracing::info!("Connecting to Leaderboard Service pool...");
     let mut leaderboard_clients = Vec::new();
     for i in 0..grpc_pool_size {
@@ -108,9 +157,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let leaderboard_client_pool = Arc::new(GrpcClientPool::new(leaderboard_clients));
     tracing::info!("✅ Leaderboard Service pool ready ({} connections)", grpc_pool_size);
 
+    let init_data_validator = Arc::new(InitDataValidator::new(&bot_token, init_data_max_age_secs));
+
     let websocket_handle = tokio::spawn(run_websocket_server(
         game_client_pool,
+        click_streams,
         leaderboard_client_pool,
+        init_data_validator,
         websocket_port,
         batch_config.leaderboard_broadcast_interval_ms,
     ));
@@ -120,6 +173,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Connecting single clients for Telegram bot...");
         let game_client_telegram = GameServiceClient::connect(game_service_url.clone()).await?;
         let leaderboard_client_telegram = LeaderboardServiceClient::connect(leaderboard_service_url.clone()).await?;
+
+        let admin_tools = match admin_token {
+            Some(token) if !admin_telegram_ids.is_empty() => {
+                let admin_client = AdminServiceClient::connect(game_service_url.clone(), &token).await?;
+                Some(AdminTools::new(
+                    admin_telegram_ids,
+                    admin_client,
+                    game_client_telegram.clone(),
+                    leaderboard_client_telegram.clone(),
+                ))
+            }
+            _ => {
+                tracing::info!("Admin commands disabled (set ADMIN_TELEGRAM_IDS and ADMIN_TOKEN to enable)");
+                None
+            }
+        };
         tracing::info!("Telegram bot clients ready");
 
         let bot = Bot::new(bot_token);
@@ -127,7 +196,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             bot,
             game_client_telegram,
             leaderboard_client_telegram,
-            mini_app_url.clone()
+            mini_app_url.clone(),
+            admin_tools,
         ));
 
         tracing::info!("Bot Service is running");
@@ -160,7 +230,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn run_telegram_bot(bot: Bot, game_client: GameServiceClient, leaderboard_client: LeaderboardServiceClient, mini_app_url: String) {
+async fn run_telegram_bot(
+    bot: Bot,
+    game_client: GameServiceClient,
+    leaderboard_client: LeaderboardServiceClient,
+    mini_app_url: String,
+    admin_tools: Option<AdminTools>,
+) {
     tracing::info!("Starting Telegram bot...");
 
     let me = loop {
@@ -183,13 +259,46 @@ async fn run_telegram_bot(bot: Bot, game_client: GameServiceClient, leaderboard_
     let mini_app_url_idle = mini_app_url.clone();
     let me_idle = me.clone();
 
+    let admin_tools_filter = admin_tools.clone();
+    let me_filter = me.clone();
+    let me_admin = me;
+
     let game_client_name_change = game_client.clone();
 
+    let game_client_username = game_client.clone();
+    let leaderboard_client_username = leaderboard_client.clone();
+    let mini_app_url_username = mini_app_url.clone();
+
     let game_client_cb = game_client;
     let leaderboard_client_cb = leaderboard_client;
     let mini_app_url_cb = mini_app_url;
 
+    // Admin commands come first, so they work whatever dialogue the admin is in
     let handler = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter(move |msg: Message| {
+                    admin_tools_filter
+                        .as_ref()
+                        .is_some_and(|admin| admin.is_admin_command(&msg, me_filter.username()))
+                })
+                .endpoint(move |bot: Bot, msg: Message| {
+                    let admin_tools = admin_tools.clone();
+                    let me = me_admin.clone();
+                    async move {
+                        let Some(admin) = &admin_tools else {
+                            return Ok(());
+                        };
+                        telegram::handle_admin_message(&bot, &msg, me.username(), admin)
+                            .await
+                            .map(|_| ())
+                            .map_err(|e| {
+                                tracing::error!("Admin command handler error: {}", e);
+                                e
+                            })
+                    }
+                }),
+        )
         .branch(
             Update::filter_message()
                 .enter_dialogue::<Update, InMemStorage<State>, State>()
@@ -237,6 +346,31 @@ async fn run_telegram_bot(bot: Bot, game_client: GameServiceClient, leaderboard_
                             }
                         },
                     ),
+                )
+                .branch(
+                    dptree::case![State::WaitingForUsername { referral_code }].endpoint(
+                        move |bot: Bot, msg: Message, dialogue: MyDialogue, referral_code: Option<String>| {
+                            let game_client = game_client_username.clone();
+                            let leaderboard_client = leaderboard_client_username.clone();
+                            let mini_app_url = mini_app_url_username.clone();
+                            async move {
+                                telegram::handlers::handle_username_input(
+                                    bot,
+                                    msg,
+                                    dialogue,
+                                    referral_code,
+                                    game_client,
+                                    leaderboard_client,
+                                    mini_app_url,
+                                )
+                                .await
+                                .map_err(|e| {
+                                    tracing::error!("Username input handler error: {}", e);
+                                    e
+                                })
+                            }
+                        },
+                    ),
                 ),
         )
         .branch(
@@ -274,7 +408,9 @@ async fn run_telegram_bot(bot: Bot, game_client: GameServiceClient, leaderboard_
 
 async fn run_websocket_server(
     game_client_pool: Arc<GrpcClientPool<GameServiceClient>>,
+    click_streams: Arc<ClickStreamPool>,
     leaderboard_client_pool: Arc<GrpcClientPool<LeaderboardServiceClient>>,
+    init_data_validator: Arc<InitDataValidator>,
     port: u16,
     broadcast_interval_ms: u64,
 ) {
@@ -294,10 +430,16 @@ async fn run_websocket_server(
     );
     leaderboard_broadcaster.clone().start_periodic_broadcaster();
 
+    let rank_updates = Arc::new(RankUpdateRouter::new(leaderboard_client_pool.clone()));
+    rank_updates.clone().start();
+
     let app_state = AppState {
         game_client_pool,
+        click_streams,
         leaderboard_client_pool,
         broadcast_tx,
+        init_data_validator,
+        rank_updates,
     };
 
     let app = Router::new()
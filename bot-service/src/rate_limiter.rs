@@ -1,10 +1,12 @@
 use std::time::Duration;
 
+#[allow(dead_code)]
 pub struct AdaptiveRateLimiter {
     min_interval: Duration,
     max_interval: Duration,
 }
 
+#[allow(dead_code)]
 impl AdaptiveRateLimiter {
     pub fn new() -> Self {
         Self {
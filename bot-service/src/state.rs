@@ -3,4 +3,7 @@ pub enum State {
     #[default]
     Idle,
     WaitingForNameChange { user_id: String },
+    /// A new player picking their own username, with the referral code from
+    /// their invite link if they came through one.
+    WaitingForUsername { referral_code: Option<String> },
 }
@@ -0,0 +1,317 @@
+use crate::grpc_client::{AdminServiceClient, GameServiceClient, LeaderboardServiceClient};
+use crate::telegram::format_admin_stats_message;
+use shared::errors::{Result, ServiceError};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::{prelude::*, types::Message, utils::command::BotCommands, RequestError};
+
+/// Telegram allows about 30 messages a second per bot; stay under it.
+const BROADCAST_SEND_INTERVAL: Duration = Duration::from_millis(50);
+const BROADCAST_PAGE_SIZE: i32 = 500;
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Admin commands:")]
+pub enum AdminCommand {
+    #[command(description = "Ban a player: /ban <telegram_id|user_id> [reason]")]
+    Ban(String),
+    #[command(description = "Lift a ban or anti-cheat action: /unban <telegram_id|user_id> [reason]")]
+    Unban(String),
+    #[command(description = "Reset a player's clicks to 0: /resetscore <telegram_id|user_id> [reason]")]
+    Resetscore(String),
+    #[command(description = "Show game and moderation stats")]
+    Stats,
+    #[command(description = "Message every player: /broadcast <text>")]
+    Broadcast(String),
+    #[command(description = "List admin commands")]
+    Adminhelp,
+}
+
+/// Admin commands are only answered for these Telegram ids; anyone else gets the
+/// same silence as for an unknown command.
+#[derive(Clone)]
+pub struct AdminTools {
+    admin_ids: Arc<HashSet<i64>>,
+    client: AdminServiceClient,
+    game_client: GameServiceClient,
+    leaderboard_client: LeaderboardServiceClient,
+}
+
+impl AdminTools {
+    pub fn new(
+        admin_ids: HashSet<i64>,
+        client: AdminServiceClient,
+        game_client: GameServiceClient,
+        leaderboard_client: LeaderboardServiceClient,
+    ) -> Self {
+        Self {
+            admin_ids: Arc::new(admin_ids),
+            client,
+            game_client,
+            leaderboard_client,
+        }
+    }
+
+    pub fn is_admin(&self, telegram_id: i64) -> bool {
+        self.admin_ids.contains(&telegram_id)
+    }
+
+    /// Whether the message is an admin command sent by an admin, whatever
+    /// dialogue the admin is in.
+    pub fn is_admin_command(&self, msg: &Message, bot_username: &str) -> bool {
+        let telegram_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+        self.is_admin(telegram_id)
+            && msg
+                .text()
+                .is_some_and(|text| AdminCommand::parse(text, bot_username).is_ok())
+    }
+}
+
+/// Parses `ADMIN_TELEGRAM_IDS`, a comma-separated list of Telegram user ids.
+pub fn parse_admin_ids(spec: &str) -> Result<HashSet<i64>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse::<i64>()
+                .map_err(|_| ServiceError::Internal(format!("Invalid admin Telegram id: {}", id)))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Target {
+    TelegramId(i64),
+    UserId(String),
+}
+
+/// Splits `<telegram_id|user_id> [reason]`.
+fn parse_target(args: &str) -> Option<(Target, String)> {
+    let args = args.trim();
+    let (target, reason) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+
+    let target = if let Ok(telegram_id) = target.parse::<i64>() {
+        Target::TelegramId(telegram_id)
+    } else if uuid::Uuid::parse_str(target).is_ok() {
+        Target::UserId(target.to_string())
+    } else {
+        return None;
+    };
+
+    Some((target, reason.trim().to_string()))
+}
+
+async fn resolve_user_id(game_client: &mut GameServiceClient, target: Target) -> Result<Option<String>> {
+    match target {
+        Target::UserId(user_id) => Ok(Some(user_id)),
+        Target::TelegramId(telegram_id) => {
+            let user = game_client.get_user(telegram_id).await?;
+            Ok(user.exists.then_some(user.user_id))
+        }
+    }
+}
+
+/// Runs the message if it is an admin command sent by an admin. Returns false
+/// for anything else, so it goes on to the regular handlers.
+pub async fn handle_admin_message(bot: &Bot, msg: &Message, bot_username: &str, admin: &AdminTools) -> Result<bool> {
+    let telegram_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+    if !admin.is_admin(telegram_id) {
+        return Ok(false);
+    }
+
+    let Some(Ok(command)) = msg.text().map(|text| AdminCommand::parse(text, bot_username)) else {
+        return Ok(false);
+    };
+
+    tracing::info!(admin = telegram_id, command = msg.text(), "Admin command");
+    shared::record_counter("bot.admin.commands", 1);
+
+    let reply = match run_admin_command(bot, msg, command, admin.clone()).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            tracing::warn!(admin = telegram_id, error = %e, "Admin command failed");
+            format!("❌ {}", e)
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply)
+        .await
+        .map_err(|e| ServiceError::Telegram(e.to_string()))?;
+
+    Ok(true)
+}
+
+async fn run_admin_command(
+    bot: &Bot,
+    msg: &Message,
+    command: AdminCommand,
+    admin: AdminTools,
+) -> Result<String> {
+    let actor = format!("telegram:{}", msg.from.as_ref().map(|u| u.id.0).unwrap_or(0));
+    let AdminTools {
+        mut client,
+        mut game_client,
+        mut leaderboard_client,
+        ..
+    } = admin;
+
+    let targeted = |args: &str, usage: &str| {
+        parse_target(args).ok_or_else(|| ServiceError::Validation(format!("Usage: {}", usage)))
+    };
+
+    match command {
+        AdminCommand::Ban(args) => {
+            let (target, reason) = targeted(&args, "/ban <telegram_id|user_id> [reason]")?;
+            let Some(user_id) = resolve_user_id(&mut game_client, target).await? else {
+                return Ok("❌ User not found".to_string());
+            };
+
+            client.ban_user(user_id.clone(), reason, actor).await?;
+            Ok(format!("🔨 Banned {}", user_id))
+        }
+        AdminCommand::Unban(args) => {
+            let (target, reason) = targeted(&args, "/unban <telegram_id|user_id> [reason]")?;
+            let Some(user_id) = resolve_user_id(&mut game_client, target).await? else {
+                return Ok("❌ User not found".to_string());
+            };
+
+            client.unban_user(user_id.clone(), reason, actor).await?;
+            Ok(format!("✅ Unbanned {}", user_id))
+        }
+        AdminCommand::Resetscore(args) => {
+            let (target, reason) = targeted(&args, "/resetscore <telegram_id|user_id> [reason]")?;
+            let Some(user_id) = resolve_user_id(&mut game_client, target).await? else {
+                return Ok("❌ User not found".to_string());
+            };
+            let reason = if reason.is_empty() { "Reset by admin".to_string() } else { reason };
+
+            let response = client.reset_score(user_id.clone(), reason, actor).await?;
+            Ok(format!("♻️ Reset {} to {} clicks", user_id, response.new_total))
+        }
+        AdminCommand::Stats => {
+            let stats = leaderboard_client.get_global_stats().await?;
+            let flagged = client.count_flagged_users("flag").await?;
+            let shadow_banned = client.count_flagged_users("shadow_ban").await?;
+            let frozen = client.count_flagged_users("freeze").await?;
+
+            Ok(format_admin_stats_message(
+                stats.total_users,
+                stats.total_clicks,
+                stats.active_sessions,
+                flagged,
+                shadow_banned,
+                frozen,
+            ))
+        }
+        AdminCommand::Broadcast(text) => {
+            let text = text.trim().to_string();
+            if text.is_empty() {
+                return Ok("Usage: /broadcast <text>".to_string());
+            }
+
+            tokio::spawn(run_broadcast(bot.clone(), msg.chat.id, client, text));
+            Ok("📣 Broadcast started, I'll report back when it's done".to_string())
+        }
+        AdminCommand::Adminhelp => Ok(AdminCommand::descriptions().to_string()),
+    }
+}
+
+/// Sends `text` to every player, paging through their ids, then reports the
+/// tally to the admin who started it.
+async fn run_broadcast(bot: Bot, admin_chat: ChatId, mut client: AdminServiceClient, text: String) {
+    let mut sent = 0u64;
+    let mut failed = 0u64;
+    let mut after = 0;
+
+    let outcome = loop {
+        let telegram_ids = match client.list_telegram_ids(after, BROADCAST_PAGE_SIZE).await {
+            Ok(ids) if ids.is_empty() => break Ok(()),
+            Ok(ids) => ids,
+            Err(e) => break Err(e),
+        };
+
+        for telegram_id in &telegram_ids {
+            if send_with_retry(&bot, ChatId(*telegram_id), &text).await {
+                sent += 1;
+            } else {
+                failed += 1;
+            }
+            tokio::time::sleep(BROADCAST_SEND_INTERVAL).await;
+        }
+
+        after = *telegram_ids.last().expect("page is not empty");
+    };
+
+    shared::record_counter("bot.admin.broadcast.sent", sent);
+    shared::record_counter("bot.admin.broadcast.failed", failed);
+
+    let report = match outcome {
+        Ok(()) => format!("📣 Broadcast finished: {} sent, {} failed", sent, failed),
+        Err(e) => {
+            tracing::error!(error = %e, "Broadcast stopped early");
+            format!("⚠️ Broadcast stopped after {} sent, {} failed: {}", sent, failed, e)
+        }
+    };
+
+    if let Err(e) = bot.send_message(admin_chat, report).await {
+        tracing::warn!(error = %e, "Failed to report broadcast result");
+    }
+}
+
+/// Players who blocked the bot fail for good; a flood wait is retried once.
+async fn send_with_retry(bot: &Bot, chat_id: ChatId, text: &str) -> bool {
+    match bot.send_message(chat_id, text).await {
+        Ok(_) => true,
+        Err(RequestError::RetryAfter(wait)) => {
+            tokio::time::sleep(wait.duration()).await;
+            bot.send_message(chat_id, text).await.is_ok()
+        }
+        Err(e) => {
+            tracing::debug!(chat_id = chat_id.0, error = %e, "Broadcast message not delivered");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_admin_ids() {
+        let ids = parse_admin_ids("123, 456,,789").unwrap();
+        assert_eq!(ids, HashSet::from([123, 456, 789]));
+
+        assert!(parse_admin_ids("").unwrap().is_empty());
+        assert!(parse_admin_ids("123,abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("12345 spamming the board"),
+            Some((Target::TelegramId(12345), "spamming the board".to_string()))
+        );
+
+        let user_id = "6f1c2b1e-8a57-4f3e-9b0d-2f4f8c1d9e21";
+        assert_eq!(
+            parse_target(&format!(" {} ", user_id)),
+            Some((Target::UserId(user_id.to_string()), String::new()))
+        );
+
+        assert_eq!(parse_target("alice cheating"), None);
+        assert_eq!(parse_target(""), None);
+    }
+
+    #[test]
+    fn test_parse_admin_commands() {
+        let command = AdminCommand::parse("/ban 12345 autoclicker", "clicker_bot").unwrap();
+        assert!(matches!(command, AdminCommand::Ban(ref args) if args == "12345 autoclicker"));
+
+        let command = AdminCommand::parse("/broadcast Double clicks this weekend!", "clicker_bot").unwrap();
+        assert!(matches!(command, AdminCommand::Broadcast(ref text) if text == "Double clicks this weekend!"));
+
+        assert!(matches!(AdminCommand::parse("/stats", "clicker_bot").unwrap(), AdminCommand::Stats));
+        assert!(AdminCommand::parse("/top", "clicker_bot").is_err());
+    }
+}
@@ -1,6 +1,10 @@
+use crate::grpc_client::leaderboard_client::LeaderboardPeriod;
 use crate::grpc_client::GameServiceClient;
 use crate::state::State;
-use crate::telegram::{format_welcome_message, make_game_keyboard, make_username_keyboard};
+use crate::telegram::{
+    format_friends_message, format_top_message, format_welcome_message, make_game_keyboard,
+    make_username_keyboard,
+};
 use shared::errors::{Result, ServiceError};
 use teloxide::{
     dispatching::dialogue::InMemStorage,
@@ -19,11 +23,15 @@ fn map_teloxide_err<E: std::fmt::Display>(e: E) -> ServiceError {
 #[command(rename_rule = "lowercase", description = "Available commands:")]
 pub enum Command {
     #[command(description = "Start the bot and register")]
-    Start,
+    Start(String),
     #[command(description = "Change your username")]
     Changename,
     #[command(description = "Refresh your score and rank")]
     Refresh,
+    #[command(description = "Show top clickers: /top [daily|weekly|monthly]")]
+    Top(String),
+    #[command(description = "See how you rank against your friends and get your invite link")]
+    Friends,
 }
 
 pub async fn handle_idle_state(
@@ -37,8 +45,9 @@ pub async fn handle_idle_state(
 ) -> Result<()> {
     if let Some(text) = msg.text() {
         match BotCommands::parse(text, me.username()) {
-            Ok(Command::Start) => {
-                handle_start(bot, msg, game_client, leaderboard_client, mini_app_url).await?;
+            Ok(Command::Start(payload)) => {
+                let referral_code = parse_referral_code(&payload);
+                handle_start(bot, msg, game_client, leaderboard_client, mini_app_url, referral_code).await?;
             }
             Ok(Command::Changename) => {
                 handle_changename_command(bot, msg, dialogue, game_client).await?;
@@ -46,6 +55,12 @@ pub async fn handle_idle_state(
             Ok(Command::Refresh) => {
                 handle_refresh(bot, msg, game_client, leaderboard_client).await?;
             }
+            Ok(Command::Top(period)) => {
+                handle_top(bot, msg, leaderboard_client, &period).await?;
+            }
+            Ok(Command::Friends) => {
+                handle_friends(bot, msg, me.username(), game_client, leaderboard_client).await?;
+            }
             Err(_) => {
             }
         }
@@ -63,7 +78,12 @@ pub async fn handle_callback_query(
     mini_app_url: String,
 ) -> Result<()> {
     if let Some(data) = &q.data {
-        match data.as_str() {
+        let (action, referral_code) = match data.split_once(':') {
+            Some((action, code)) => (action, Some(code.to_string())),
+            None => (data.as_str(), None),
+        };
+
+        match action {
             "change_name" => {
                 if let Some(msg) = &q.message {
                     let chat = msg.chat();
@@ -111,11 +131,15 @@ pub async fn handle_callback_query(
                 let random_username = generate_random_username();
                 if let Some(msg) = &q.message {
                     let chat = msg.chat();
+                    let player = NewPlayer {
+                        telegram_id: q.from.id.0 as i64,
+                        username: random_username,
+                        referral_code,
+                    };
                     create_user_and_show_welcome(
                         bot.clone(),
                         chat.id,
-                        q.from.id.0 as i64,
-                        random_username,
+                        player,
                         game_client,
                         leaderboard_client,
                         mini_app_url,
@@ -126,9 +150,21 @@ pub async fn handle_callback_query(
             "username_custom" => {
                 if let Some(msg) = &q.message {
                     let chat = msg.chat();
+
+                    dialogue
+                        .update(State::WaitingForUsername { referral_code })
+                        .await
+                        .map_err(|e| {
+                            ServiceError::Internal(format!("Failed to update dialogue: {}", e))
+                        })?;
+
                     bot.send_message(
                         chat.id,
-                        "Please send me your desired username (3-20 characters, alphanumeric only):",
+                        "Please send me your desired username:\n\n\
+                        📝 Requirements:\n\
+                        • 3-20 characters\n\
+                        • Letters, numbers, underscore, hyphen only\n\n\
+                        Send /cancel to abort.",
                     )
                     .await
                     .map_err(map_teloxide_err)?;
@@ -151,6 +187,7 @@ async fn handle_start(
     mut game_client: GameServiceClient,
     leaderboard_client: crate::grpc_client::LeaderboardServiceClient,
     mini_app_url: String,
+    referral_code: Option<&str>,
 ) -> Result<()> {
     let start_time = std::time::Instant::now();
     let telegram_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
@@ -161,6 +198,7 @@ async fn handle_start(
     let user_response = game_client.get_user(telegram_id).await?;
     tracing::info!("⏱️ get_user took: {:?}", user_fetch_start.elapsed());
 
+    // Invites only count for new players, so existing ones just get their dashboard
     if user_response.exists {
         let welcome_start = std::time::Instant::now();
         send_welcome_message(bot, msg, user_response, leaderboard_client, mini_app_url).await?;
@@ -170,7 +208,7 @@ async fn handle_start(
             msg.chat.id,
             "👋 Welcome to Bitcoin Clicker!\n\nChoose how to set your username:",
         )
-        .reply_markup(make_username_keyboard())
+        .reply_markup(make_username_keyboard(referral_code))
         .await
         .map_err(map_teloxide_err)?;
     }
@@ -179,6 +217,13 @@ async fn handle_start(
     Ok(())
 }
 
+/// The code in a `ref_<code>` /start payload, if the payload is one.
+fn parse_referral_code(payload: &str) -> Option<&str> {
+    let code = payload.trim().strip_prefix("ref_")?;
+    let valid = (1..=32).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then_some(code)
+}
+
 async fn handle_changename_command(
     bot: Bot,
     msg: Message,
@@ -271,6 +316,87 @@ async fn handle_refresh(
     Ok(())
 }
 
+fn parse_period(arg: &str) -> Option<(LeaderboardPeriod, &'static str)> {
+    match arg.trim().to_lowercase().as_str() {
+        "" | "all" | "alltime" => Some((LeaderboardPeriod::AllTime, "All Time")),
+        "daily" | "day" | "today" => Some((LeaderboardPeriod::Daily, "Today")),
+        "weekly" | "week" => Some((LeaderboardPeriod::Weekly, "This Week")),
+        "monthly" | "month" => Some((LeaderboardPeriod::Monthly, "This Month")),
+        _ => None,
+    }
+}
+
+async fn handle_top(
+    bot: Bot,
+    msg: Message,
+    mut leaderboard_client: crate::grpc_client::LeaderboardServiceClient,
+    period_arg: &str,
+) -> Result<()> {
+    let Some((period, label)) = parse_period(period_arg) else {
+        bot.send_message(msg.chat.id, "Usage: /top [daily|weekly|monthly]")
+            .await
+            .map_err(map_teloxide_err)?;
+        return Ok(());
+    };
+
+    let response = leaderboard_client
+        .get_period_leaderboard(period, Some(10), Some(0))
+        .await?;
+
+    let leaderboard: Vec<(i32, String, i64)> = response
+        .entries
+        .into_iter()
+        .map(|entry| (entry.rank, entry.username, entry.total_clicks))
+        .collect();
+
+    bot.send_message(msg.chat.id, format_top_message(label, &leaderboard))
+        .await
+        .map_err(map_teloxide_err)?;
+
+    Ok(())
+}
+
+/// Friends shown by /friends. The user's own rank is reported even when lower.
+const FRIENDS_LIMIT: i32 = 10;
+
+async fn handle_friends(
+    bot: Bot,
+    msg: Message,
+    bot_username: &str,
+    mut game_client: GameServiceClient,
+    mut leaderboard_client: crate::grpc_client::LeaderboardServiceClient,
+) -> Result<()> {
+    let telegram_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+
+    let user_response = game_client.get_user(telegram_id).await?;
+    if !user_response.exists {
+        bot.send_message(msg.chat.id, "❌ Please /start first to register!")
+            .await
+            .map_err(map_teloxide_err)?;
+        return Ok(());
+    }
+
+    let response = leaderboard_client
+        .get_friends_leaderboard(user_response.user_id, Some(FRIENDS_LIMIT))
+        .await?;
+
+    let friends: Vec<(i32, String, i64)> = response
+        .entries
+        .into_iter()
+        .map(|entry| (entry.rank, entry.username, entry.total_clicks))
+        .collect();
+    let invite_link = format!("https://t.me/{}?start=ref_{}", bot_username, response.referral_code);
+
+    bot.send_message(
+        msg.chat.id,
+        format_friends_message(&friends, response.user_rank, response.total_count, &invite_link),
+    )
+    .await
+    .map_err(map_teloxide_err)?;
+
+    Ok(())
+}
+
 pub async fn handle_name_change_input(
     bot: Bot,
     msg: Message,
@@ -337,9 +463,70 @@ pub async fn handle_name_change_input(
     Ok(())
 }
 
+/// Registers a new player under the username they sent, crediting whoever
+/// invited them. Stays in the dialogue until a name is accepted.
+pub async fn handle_username_input(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    referral_code: Option<String>,
+    game_client: GameServiceClient,
+    leaderboard_client: crate::grpc_client::LeaderboardServiceClient,
+    mini_app_url: String,
+) -> Result<()> {
+    if msg.text() == Some("/cancel") {
+        dialogue.update(State::Idle).await.ok();
+        bot.send_message(msg.chat.id, "❌ Registration cancelled. Send /start to try again.")
+            .await
+            .map_err(map_teloxide_err)?;
+        return Ok(());
+    }
+
+    let Some(username) = msg.text().map(|text| text.trim().to_string()) else {
+        bot.send_message(msg.chat.id, "❌ Please send text, not other content.")
+            .await
+            .map_err(map_teloxide_err)?;
+        return Ok(());
+    };
+
+    if !is_valid_username(&username) {
+        bot.send_message(
+            msg.chat.id,
+            "❌ Invalid username!\n\n\
+            Requirements:\n\
+            • 3-20 characters\n\
+            • Letters, numbers, underscore (_), hyphen (-) only\n\n\
+            Please try again or send /cancel:",
+        )
+        .await
+        .map_err(map_teloxide_err)?;
+        return Ok(());
+    }
+
+    let player = NewPlayer {
+        telegram_id: msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0),
+        username,
+        referral_code,
+    };
+    let created = create_user_and_show_welcome(
+        bot,
+        msg.chat.id,
+        player,
+        game_client,
+        leaderboard_client,
+        mini_app_url,
+    )
+    .await?;
+    if created {
+        dialogue.update(State::Idle).await.ok();
+    }
+
+    Ok(())
+}
+
 fn is_valid_username(username: &str) -> bool {
     let len = username.len();
-    if len < 3 || len > 20 {
+    if !(3..=20).contains(&len) {
         return false;
     }
 
@@ -356,21 +543,22 @@ async fn send_welcome_message(
     mini_app_url: String,
 ) -> Result<()> {
     let mut leaderboard_client_mut = leaderboard_client.clone();
-    let (leaderboard, user_rank, global_clicks) =
-        match fetch_leaderboard_data(&mut leaderboard_client_mut, &user_data.user_id).await {
-            Ok(data) => data,
-            Err(e) => {
-                tracing::warn!("Failed to fetch leaderboard data: {}, using placeholder", e);
-                (vec![], 0, user_data.total_clicks)
-            }
-        };
+    let dashboard = match fetch_leaderboard_data(&mut leaderboard_client_mut, &user_data.user_id).await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::warn!("Failed to fetch leaderboard data: {}, using placeholder", e);
+            DashboardData::placeholder(user_data.total_clicks)
+        }
+    };
 
     let text = format_welcome_message(
         &user_data.username,
         user_data.total_clicks,
-        global_clicks,
-        user_rank,
-        &leaderboard,
+        dashboard.global_clicks,
+        dashboard.user_rank,
+        dashboard.user_percentile,
+        &dashboard.leaderboard,
+        &dashboard.nearby,
     );
 
     let keyboard = make_game_keyboard(&mini_app_url);
@@ -383,21 +571,48 @@ async fn send_welcome_message(
     Ok(())
 }
 
+/// How many players above and below the user the dashboard shows.
+const NEARBY_RADIUS: i32 = 2;
+
+struct DashboardData {
+    leaderboard: Vec<(i32, String, i64)>,
+    user_rank: i32,
+    user_percentile: f64,
+    global_clicks: i64,
+    nearby: Vec<(i32, String, i64)>,
+}
+
+impl DashboardData {
+    fn placeholder(user_clicks: i64) -> Self {
+        Self {
+            leaderboard: vec![],
+            user_rank: 0,
+            user_percentile: 0.0,
+            global_clicks: user_clicks,
+            nearby: vec![],
+        }
+    }
+}
+
 async fn fetch_leaderboard_data(
     leaderboard_client: &mut crate::grpc_client::LeaderboardServiceClient,
     user_id: &str,
-) -> Result<(Vec<(i32, String, i64)>, i32, i64)> {
+) -> Result<DashboardData> {
     let fetch_start = std::time::Instant::now();
 
-    let mut leaderboard_client_clone = leaderboard_client.clone();
-    let user_id_clone = user_id.to_string();
+    let mut rank_client = leaderboard_client.clone();
+    let mut nearby_client = leaderboard_client.clone();
 
     let concurrent_start = std::time::Instant::now();
-    let (leaderboard_result, rank_result) = tokio::join!(
+    let (leaderboard_result, rank_result, nearby_result) = tokio::join!(
         leaderboard_client.get_leaderboard(Some(20), Some(0)),
-        leaderboard_client_clone.get_user_rank(user_id_clone)
+        rank_client.get_user_rank(user_id.to_string()),
+        nearby_client.get_leaderboard_around_user(user_id.to_string(), NEARBY_RADIUS)
+    );
+    tracing::info!(
+        "⏱️ Concurrent calls (leaderboard + rank + nearby) took: {:?}",
+        concurrent_start.elapsed()
     );
-    tracing::info!("⏱️ Concurrent calls (leaderboard + rank) took: {:?}", concurrent_start.elapsed());
 
     let leaderboard_response = leaderboard_result?;
     let leaderboard: Vec<(i32, String, i64)> = leaderboard_response
@@ -407,10 +622,23 @@ async fn fetch_leaderboard_data(
         .collect();
 
     let rank_response = rank_result?;
-    let user_rank = if rank_response.found {
-        rank_response.rank
+    let (user_rank, user_percentile) = if rank_response.found {
+        (rank_response.rank, rank_response.percentile)
     } else {
-        0
+        (0, 0.0)
+    };
+
+    // The dashboard is still useful without this section
+    let nearby = match nearby_result {
+        Ok(response) => response
+            .entries
+            .into_iter()
+            .map(|entry| (entry.rank, entry.username, entry.total_clicks))
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to fetch leaderboard around user: {}", e);
+            vec![]
+        }
     };
 
     let stats_start = std::time::Instant::now();
@@ -419,45 +647,73 @@ async fn fetch_leaderboard_data(
     let global_clicks = stats_response.total_clicks;
 
     tracing::info!("⏱️ fetch_leaderboard_data TOTAL: {:?}", fetch_start.elapsed());
-    Ok((leaderboard, user_rank, global_clicks))
+    Ok(DashboardData {
+        leaderboard,
+        user_rank,
+        user_percentile,
+        global_clicks,
+        nearby,
+    })
+}
+
+/// A player about to be registered, with the referral code from their invite link.
+struct NewPlayer {
+    telegram_id: i64,
+    username: String,
+    referral_code: Option<String>,
 }
 
+/// Returns whether the player was registered; if not, they have been told why.
 async fn create_user_and_show_welcome(
     bot: Bot,
     chat_id: ChatId,
-    telegram_id: i64,
-    username: String,
+    player: NewPlayer,
     mut game_client: GameServiceClient,
     leaderboard_client: crate::grpc_client::LeaderboardServiceClient,
     mini_app_url: String,
-) -> Result<()> {
-    let create_response = game_client.create_user(telegram_id, username).await?;
+) -> Result<bool> {
+    let create_response = game_client
+        .create_user(player.telegram_id, player.username, player.referral_code)
+        .await?;
 
     if !create_response.success {
         bot.send_message(chat_id, format!("Error: {}", create_response.message))
             .await
             .map_err(map_teloxide_err)?;
-        return Ok(());
+        return Ok(false);
     }
 
-    let user_response = game_client.get_user(telegram_id).await?;
+    if !create_response.referred_by.is_empty() {
+        bot.send_message(
+            chat_id,
+            format!(
+                "🤝 You joined through {}'s invite. Check /friends to see how you compare!",
+                create_response.referred_by
+            ),
+        )
+        .await
+        .map_err(map_teloxide_err)?;
+    }
+
+    let user_response = game_client.get_user(player.telegram_id).await?;
 
     let mut leaderboard_client_mut = leaderboard_client.clone();
-    let (leaderboard, user_rank, global_clicks) =
-        match fetch_leaderboard_data(&mut leaderboard_client_mut, &user_response.user_id).await {
-            Ok(data) => data,
-            Err(e) => {
-                tracing::warn!("Failed to fetch leaderboard data: {}, using placeholder", e);
-                (vec![], 0, user_response.total_clicks)
-            }
-        };
+    let dashboard = match fetch_leaderboard_data(&mut leaderboard_client_mut, &user_response.user_id).await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::warn!("Failed to fetch leaderboard data: {}, using placeholder", e);
+            DashboardData::placeholder(user_response.total_clicks)
+        }
+    };
 
     let text = format_welcome_message(
         &user_response.username,
         user_response.total_clicks,
-        global_clicks,
-        user_rank,
-        &leaderboard,
+        dashboard.global_clicks,
+        dashboard.user_rank,
+        dashboard.user_percentile,
+        &dashboard.leaderboard,
+        &dashboard.nearby,
     );
 
     let keyboard = make_game_keyboard(&mini_app_url);
@@ -467,7 +723,7 @@ async fn create_user_and_show_welcome(
         .await
         .map_err(map_teloxide_err)?;
 
-    Ok(())
+    Ok(true)
 }
 
 fn generate_random_username() -> String {
@@ -475,3 +731,46 @@ fn generate_random_username() -> String {
     let timestamp = Utc::now().timestamp() % 10000;
     format!("Player{}", timestamp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_top_command() {
+        let command = Command::parse("/top daily", "clicker_bot").unwrap();
+        assert!(matches!(command, Command::Top(ref period) if period == "daily"));
+
+        let command = Command::parse("/top", "clicker_bot").unwrap();
+        assert!(matches!(command, Command::Top(ref period) if period.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_start_command_payload() {
+        let command = Command::parse("/start ref_3fa85f64b1c2", "clicker_bot").unwrap();
+        assert!(matches!(command, Command::Start(ref payload) if payload == "ref_3fa85f64b1c2"));
+
+        let command = Command::parse("/start", "clicker_bot").unwrap();
+        assert!(matches!(command, Command::Start(ref payload) if payload.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_referral_code() {
+        assert_eq!(parse_referral_code("ref_3fa85f64b1c2"), Some("3fa85f64b1c2"));
+        assert_eq!(parse_referral_code(" ref_abc "), Some("abc"));
+        assert_eq!(parse_referral_code(""), None);
+        assert_eq!(parse_referral_code("ref_"), None);
+        assert_eq!(parse_referral_code("3fa85f64b1c2"), None);
+        assert_eq!(parse_referral_code("ref_abc:def"), None);
+        assert_eq!(parse_referral_code(&format!("ref_{}", "a".repeat(33))), None);
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("").map(|(p, _)| p), Some(LeaderboardPeriod::AllTime));
+        assert_eq!(parse_period("Daily").map(|(p, _)| p), Some(LeaderboardPeriod::Daily));
+        assert_eq!(parse_period(" week ").map(|(p, _)| p), Some(LeaderboardPeriod::Weekly));
+        assert_eq!(parse_period("monthly").map(|(p, _)| p), Some(LeaderboardPeriod::Monthly));
+        assert_eq!(parse_period("yearly"), None);
+    }
+}
@@ -17,9 +17,46 @@ pub fn make_game_keyboard(mini_app_url: &str) -> InlineKeyboardMarkup {
     ])
 }
 
-pub fn make_username_keyboard() -> InlineKeyboardMarkup {
+/// Carries the invite's referral code, if any, through to registration on
+/// both choices, as `<action>:<code>`.
+pub fn make_username_keyboard(referral_code: Option<&str>) -> InlineKeyboardMarkup {
+    let callback = |action: &str| match referral_code {
+        Some(code) => format!("{}:{}", action, code),
+        None => action.to_string(),
+    };
+
     InlineKeyboardMarkup::new(vec![vec![
-        InlineKeyboardButton::callback("🎲 Random", "username_random"),
-        InlineKeyboardButton::callback("✍️ Custom", "username_custom"),
+        InlineKeyboardButton::callback("🎲 Random", callback("username_random")),
+        InlineKeyboardButton::callback("✍️ Custom", callback("username_custom")),
     ]])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    fn callbacks(keyboard: &InlineKeyboardMarkup) -> Vec<String> {
+        keyboard
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .filter_map(|button| match &button.kind {
+                InlineKeyboardButtonKind::CallbackData(data) => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_username_keyboard_carries_referral_code_on_both_choices() {
+        assert_eq!(
+            callbacks(&make_username_keyboard(Some("abc123"))),
+            vec!["username_random:abc123", "username_custom:abc123"]
+        );
+        assert_eq!(
+            callbacks(&make_username_keyboard(None)),
+            vec!["username_random", "username_custom"]
+        );
+    }
+}
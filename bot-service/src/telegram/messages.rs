@@ -3,23 +3,113 @@ pub fn format_welcome_message(
     user_clicks: i64,
     global_clicks: i64,
     user_rank: i32,
+    user_percentile: f64,
     leaderboard: &[(i32, String, i64)],
+    nearby: &[(i32, String, i64)],
 ) -> String {
     let leaderboard_text = format_leaderboard(leaderboard);
+    let rank_text = format_rank(user_rank, user_percentile);
 
-    format!(
+    let mut message = format!(
         "🏆 Bitcoin Clicker Dashboard\n\
         ━━━━━━━━━━━━━━━━━\n\
         👤 Player: {}\n\
         🎯 Your Clicks: {}\n\
         🌍 Global Clicks: {}\n\
-        📈 Your Rank: #{}\n\n\
+        📈 Your Rank: {}\n\n\
         📊 Top Clickers:\n\
         {}",
-        username, user_clicks, global_clicks, user_rank, leaderboard_text
+        username, user_clicks, global_clicks, rank_text, leaderboard_text
+    );
+
+    // Only worth showing when the user's own row isn't already in the top list
+    let in_top = leaderboard.iter().any(|(rank, _, _)| *rank == user_rank);
+    if !in_top && !nearby.is_empty() {
+        message.push_str("\n\n🎯 Around You:\n");
+        message.push_str(&format_nearby(nearby, user_rank));
+    }
+
+    message
+}
+
+pub fn format_top_message(period_label: &str, leaderboard: &[(i32, String, i64)]) -> String {
+    format!(
+        "🏆 Top Clickers — {}\n\
+        ━━━━━━━━━━━━━━━━━\n\
+        {}",
+        period_label,
+        format_leaderboard(leaderboard)
+    )
+}
+
+pub fn format_friends_message(
+    friends: &[(i32, String, i64)],
+    user_rank: i32,
+    friend_count: i32,
+    invite_link: &str,
+) -> String {
+    // The user always counts themselves, so one entry means nobody else yet
+    let standing = if friend_count > 1 {
+        format!(
+            "📈 You're #{} of {}\n\n{}",
+            user_rank,
+            friend_count,
+            format_leaderboard(friends)
+        )
+    } else {
+        "No friends here yet. Invite some and you'll both show up!".to_string()
+    };
+
+    format!(
+        "🤝 Friends Leaderboard\n\
+        ━━━━━━━━━━━━━━━━━\n\
+        {}\n\n\
+        🔗 Invite friends and earn bonus clicks when they join:\n\
+        {}",
+        standing, invite_link
+    )
+}
+
+pub fn format_admin_stats_message(
+    total_users: i64,
+    total_clicks: i64,
+    active_sessions: i64,
+    flagged: i64,
+    shadow_banned: i64,
+    frozen: i64,
+) -> String {
+    format!(
+        "🛠 Admin Stats\n\
+        ━━━━━━━━━━━━━━━━━\n\
+        👥 Players: {}\n\
+        🌍 Global Clicks: {}\n\
+        🟢 Active Sessions: {}\n\n\
+        🕵️ Anti-cheat:\n\
+        ⚠️ Flagged: {}\n\
+        👻 Shadow-banned: {}\n\
+        🧊 Frozen: {}",
+        total_users, total_clicks, active_sessions, flagged, shadow_banned, frozen
     )
 }
 
+/// Ranks past this are also shown as a percentile, which says more than "#48213".
+const PERCENTILE_FROM_RANK: i32 = 100;
+
+fn format_rank(rank: i32, percentile: f64) -> String {
+    if rank <= PERCENTILE_FROM_RANK || percentile <= 0.0 {
+        return format!("#{}", rank);
+    }
+
+    // Rounded up, so nobody is told they are in a better bracket than they are
+    let percentile = if percentile < 1.0 {
+        format!("{}", (percentile * 10.0).ceil() / 10.0)
+    } else {
+        format!("{}", percentile.ceil())
+    };
+
+    format!("#{} (top {}%)", rank, percentile)
+}
+
 fn format_leaderboard(entries: &[(i32, String, i64)]) -> String {
     if entries.is_empty() {
         return "No players yet!".to_string();
@@ -41,6 +131,17 @@ fn format_leaderboard(entries: &[(i32, String, i64)]) -> String {
         .join("\n")
 }
 
+fn format_nearby(entries: &[(i32, String, i64)], user_rank: i32) -> String {
+    entries
+        .iter()
+        .map(|(rank, username, clicks)| {
+            let marker = if *rank == user_rank { "👉" } else { "  " };
+            format!("{} {}. {} - {} clicks", marker, rank, username, clicks)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,13 +154,82 @@ mod tests {
             (3, "Charlie".to_string(), 250),
         ];
 
-        let message = format_welcome_message("TestUser", 100, 1850, 4, &leaderboard);
+        let message = format_welcome_message("TestUser", 100, 1850, 4, 0.4, &leaderboard, &[]);
 
         assert!(message.contains("TestUser"));
         assert!(message.contains("100"));
         assert!(message.contains("1850"));
         assert!(message.contains("#4"));
+        assert!(!message.contains("top"));
         assert!(message.contains("Alice"));
+        assert!(!message.contains("Around You"));
+    }
+
+    #[test]
+    fn test_format_welcome_message_around_user() {
+        let leaderboard = vec![(1, "Alice".to_string(), 1000)];
+        let nearby = vec![
+            (41, "Dave".to_string(), 120),
+            (42, "TestUser".to_string(), 100),
+            (43, "Erin".to_string(), 90),
+        ];
+
+        let message = format_welcome_message("TestUser", 100, 1850, 42, 4.2, &leaderboard, &nearby);
+
+        assert!(message.contains("🎯 Around You:"));
+        assert!(message.contains("   41. Dave - 120 clicks"));
+        assert!(message.contains("👉 42. TestUser - 100 clicks"));
+        assert!(message.contains("   43. Erin - 90 clicks"));
+
+        // Already visible in the top list, so no second copy
+        let message = format_welcome_message("Alice", 1000, 1850, 1, 0.1, &leaderboard, &nearby);
+        assert!(!message.contains("Around You"));
+    }
+
+    #[test]
+    fn test_format_rank_percentile() {
+        assert_eq!(format_rank(100, 2.0), "#100");
+        assert_eq!(format_rank(4821, 2.41), "#4821 (top 3%)");
+        assert_eq!(format_rank(150, 0.0213), "#150 (top 0.1%)");
+        assert_eq!(format_rank(0, 0.0), "#0");
+    }
+
+    #[test]
+    fn test_format_top_message() {
+        let leaderboard = vec![(1, "Alice".to_string(), 42)];
+
+        let message = format_top_message("Today", &leaderboard);
+
+        assert!(message.starts_with("🏆 Top Clickers — Today"));
+        assert!(message.contains("🥇 1. Alice - 42 clicks"));
+    }
+
+    #[test]
+    fn test_format_friends_message() {
+        let friends = vec![(1, "Alice".to_string(), 500), (2, "TestUser".to_string(), 100)];
+        let link = "https://t.me/clicker_bot?start=ref_3fa85f64b1c2";
+
+        let message = format_friends_message(&friends, 2, 2, link);
+
+        assert!(message.starts_with("🤝 Friends Leaderboard"));
+        assert!(message.contains("📈 You're #2 of 2"));
+        assert!(message.contains("🥇 1. Alice - 500 clicks"));
+        assert!(message.ends_with(link));
+
+        let message = format_friends_message(&[(1, "TestUser".to_string(), 100)], 1, 1, link);
+        assert!(message.contains("No friends here yet"));
+        assert!(!message.contains("You're #"));
+    }
+
+    #[test]
+    fn test_format_admin_stats_message() {
+        let message = format_admin_stats_message(120, 98765, 7, 4, 2, 1);
+
+        assert!(message.starts_with("🛠 Admin Stats"));
+        assert!(message.contains("👥 Players: 120"));
+        assert!(message.contains("🌍 Global Clicks: 98765"));
+        assert!(message.contains("👻 Shadow-banned: 2"));
+        assert!(message.contains("🧊 Frozen: 1"));
     }
 
     #[test]
@@ -1,6 +1,10 @@
+pub mod admin;
 pub mod handlers;
 mod keyboards;
 mod messages;
 
 pub use keyboards::{make_game_keyboard, make_username_keyboard};
-pub use messages::format_welcome_message;
+pub use admin::{handle_admin_message, AdminTools};
+pub use messages::{
+    format_admin_stats_message, format_friends_message, format_top_message, format_welcome_message,
+};
@@ -1,4 +1,8 @@
-use crate::grpc_client::{GameServiceClient, LeaderboardServiceClient, GrpcClientPool, get_shard_for_user};
+use crate::grpc_client::leaderboard_client::UserRankUpdate;
+use crate::grpc_client::click_stream::BatchTimes;
+use crate::grpc_client::{ClickStreamPool, GameServiceClient, LeaderboardServiceClient, GrpcClientPool};
+use crate::websocket::init_data::InitDataValidator;
+use crate::websocket::rank_updates::{RankSubscription, RankUpdateRouter};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
@@ -8,14 +12,37 @@ use axum::{
 };
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 
 #[derive(Clone)]
 pub struct AppState {
     pub game_client_pool: Arc<GrpcClientPool<GameServiceClient>>,
+    pub click_streams: Arc<ClickStreamPool>,
     pub leaderboard_client_pool: Arc<GrpcClientPool<LeaderboardServiceClient>>,
     pub broadcast_tx: broadcast::Sender<BroadcastMessage>,
+    pub init_data_validator: Arc<InitDataValidator>,
+    pub rank_updates: Arc<RankUpdateRouter>,
+}
+
+/// Per-connection state established by a successful `init`. Later messages act on
+/// this context instead of trusting identifiers sent by the client.
+#[derive(Debug, Clone)]
+struct ConnectionContext {
+    telegram_id: i64,
+    user_id: String,
+    session_id: String,
+    shard: usize,
+}
+
+/// Identifiers older clients still attach to `click`/`refresh`. They are optional and,
+/// when present, must match the connection context.
+#[derive(Debug, Default, Deserialize)]
+struct ClaimedIdentity {
+    user_id: Option<String>,
+    telegram_id: Option<i64>,
+    session_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -29,21 +56,21 @@ pub enum BroadcastMessage {
 enum ClientMessage {
     #[serde(rename = "init")]
     Init {
-        user_id: String,
-        telegram_id: i64,
-        username: String,
+        init_data: String,
     },
     #[serde(rename = "click")]
     Click {
-        user_id: String,
-        telegram_id: i64,
-        session_id: String,
         click_count: Option<u32>,
+        /// Client clock, ms since the epoch, at the batch's first and last click.
+        batch_started_at: Option<i64>,
+        batch_ended_at: Option<i64>,
+        #[serde(flatten)]
+        claimed: ClaimedIdentity,
     },
     #[serde(rename = "refresh")]
     Refresh {
-        user_id: String,
-        telegram_id: i64,
+        #[serde(flatten)]
+        claimed: ClaimedIdentity,
     },
 }
 
@@ -61,6 +88,7 @@ pub enum ServerMessage {
     #[serde(rename = "score_update")]
     ScoreUpdate {
         score: i64,
+        /// 0 when only the score changed; the client keeps its current rank.
         rank: i32,
         user_id: Option<String>,
         username: Option<String>,
@@ -75,10 +103,84 @@ pub enum ServerMessage {
     LeaderboardUpdate {
         entries: Vec<LeaderboardEntry>,
     },
+    /// The players just above and below this connection's user, the user included.
+    #[serde(rename = "nearby_ranks")]
+    NearbyRanks {
+        entries: Vec<LeaderboardEntry>,
+    },
+    /// The user ranked against who invited them and who they invited.
+    #[serde(rename = "friends_leaderboard")]
+    FriendsLeaderboard {
+        entries: Vec<LeaderboardEntry>,
+        user_rank: i32,
+        total_count: i32,
+    },
     #[serde(rename = "error")]
     Error { message: String },
     #[serde(rename = "rate_limited")]
-    RateLimited { message: String },
+    RateLimited { message: String, retry_after_ms: u64 },
+}
+
+/// Players shown on each side of the user in `nearby_ranks`.
+const NEARBY_RADIUS: i32 = 2;
+
+async fn fetch_nearby_ranks(
+    leaderboard_client: &mut LeaderboardServiceClient,
+    user_id: &str,
+) -> Option<ServerMessage> {
+    match leaderboard_client
+        .get_leaderboard_around_user(user_id.to_string(), NEARBY_RADIUS)
+        .await
+    {
+        Ok(response) if response.found => Some(ServerMessage::NearbyRanks {
+            entries: response
+                .entries
+                .into_iter()
+                .map(|entry| LeaderboardEntry {
+                    rank: entry.rank,
+                    username: entry.username,
+                    total_clicks: entry.total_clicks,
+                })
+                .collect(),
+        }),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("Failed to get leaderboard around user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// Friends sent in `friends_leaderboard`; the user's own rank is sent regardless.
+const FRIENDS_LIMIT: i32 = 10;
+
+async fn fetch_friends_leaderboard(
+    leaderboard_client: &mut LeaderboardServiceClient,
+    user_id: &str,
+) -> Option<ServerMessage> {
+    match leaderboard_client
+        .get_friends_leaderboard(user_id.to_string(), Some(FRIENDS_LIMIT))
+        .await
+    {
+        Ok(response) if response.found => Some(ServerMessage::FriendsLeaderboard {
+            entries: response
+                .entries
+                .into_iter()
+                .map(|entry| LeaderboardEntry {
+                    rank: entry.rank,
+                    username: entry.username,
+                    total_clicks: entry.total_clicks,
+                })
+                .collect(),
+            user_rank: response.user_rank,
+            total_count: response.total_count,
+        }),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("Failed to get friends leaderboard for user {}: {}", user_id, e);
+            None
+        }
+    }
 }
 
 pub async fn websocket_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
@@ -108,17 +210,55 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
         }
     });
 
+    // Latest score sent to this socket. Pushed rank updates carry the flushed total,
+    // which can trail the estimate returned by the last click, so never send less.
+    let last_score = Arc::new(AtomicI64::new(0));
+    let (rank_tx, mut rank_rx) = mpsc::unbounded_channel::<UserRankUpdate>();
+
+    let sender_clone = Arc::clone(&sender);
+    let rank_last_score = Arc::clone(&last_score);
+    let mut rank_task = tokio::spawn(async move {
+        while let Some(update) = rank_rx.recv().await {
+            let message = ServerMessage::ScoreUpdate {
+                score: update.total_clicks.max(rank_last_score.load(Ordering::Relaxed)),
+                rank: update.rank,
+                user_id: None,
+                username: None,
+            };
+
+            if let Ok(json) = serde_json::to_string(&message) {
+                let mut sender_lock = sender_clone.lock().await;
+                if sender_lock.send(Message::Text(json.into())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
     let sender_clone = Arc::clone(&sender);
     let mut recv_task = tokio::spawn(async move {
+        let mut context: Option<ConnectionContext> = None;
+        let mut rank_subscription: Option<RankSubscription> = None;
+
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
                 tracing::debug!("Received WebSocket message: {}", text);
 
                 match serde_json::from_str::<ClientMessage>(&text) {
                     Ok(client_msg) => {
-                        let responses = handle_client_message(client_msg, &state).await;
+                        let responses = handle_client_message(client_msg, &state, &mut context).await;
+
+                        if let Some(ctx) = &context {
+                            if rank_subscription.is_none() {
+                                rank_subscription = Some(state.rank_updates.subscribe(&ctx.user_id, rank_tx.clone()));
+                            }
+                        }
 
                         for response in responses {
+                            if let ServerMessage::ScoreUpdate { score, .. } = &response {
+                                last_score.store(*score, Ordering::Relaxed);
+                            }
+
                             if let Ok(response_json) = serde_json::to_string(&response) {
                                 let mut sender_lock = sender_clone.lock().await;
                                 if sender_lock.send(Message::Text(response_json.into())).await.is_err() {
@@ -151,34 +291,91 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     tokio::select! {
         _ = &mut broadcast_task => {
             recv_task.abort();
+            rank_task.abort();
         }
         _ = &mut recv_task => {
             broadcast_task.abort();
+            rank_task.abort();
+        }
+        _ = &mut rank_task => {
+            recv_task.abort();
+            broadcast_task.abort();
         }
     }
 
     tracing::info!("WebSocket connection terminated");
 }
 
-#[tracing::instrument(skip(state), fields(msg_type = ?msg))]
-async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<ServerMessage> {
-    let overall_start = std::time::Instant::now();
+fn require_context<'a>(
+    context: &'a Option<ConnectionContext>,
+    claimed: &ClaimedIdentity,
+) -> Result<&'a ConnectionContext, ServerMessage> {
+    let context = context.as_ref().ok_or_else(|| {
+        shared::record_counter("websocket.unauthenticated", 1);
+        ServerMessage::Error {
+            message: "Not authenticated. Send init first.".to_string(),
+        }
+    })?;
+
+    let mismatch = claimed.user_id.as_deref().is_some_and(|id| id != context.user_id)
+        || claimed.telegram_id.is_some_and(|id| id != context.telegram_id)
+        || claimed.session_id.as_deref().is_some_and(|id| id != context.session_id);
+
+    if mismatch {
+        shared::record_counter("websocket.identity_mismatch", 1);
+        tracing::warn!(
+            bound_telegram_id = context.telegram_id,
+            claimed = ?claimed,
+            "Rejected message for a different identity than the one verified on init"
+        );
+        return Err(ServerMessage::Error {
+            message: "Identity mismatch".to_string(),
+        });
+    }
+
+    Ok(context)
+}
 
+#[tracing::instrument(skip(state, context, msg))]
+async fn handle_client_message(
+    msg: ClientMessage,
+    state: &AppState,
+    context: &mut Option<ConnectionContext>,
+) -> Vec<ServerMessage> {
     match msg {
-        ClientMessage::Init {
-            user_id: _,
-            telegram_id,
-            username,
-        } => {
+        ClientMessage::Init { init_data } => {
             let init_start = std::time::Instant::now();
+
+            shared::record_counter("websocket.init.requests", 1);
+
+            let verified = match state.init_data_validator.validate(&init_data) {
+                Ok(verified) => verified,
+                Err(e) => {
+                    shared::record_counter("websocket.init.invalid_init_data", 1);
+                    tracing::warn!(error = %e, "Rejected WebSocket init with invalid initData");
+                    return vec![ServerMessage::Error {
+                        message: "Invalid Telegram authentication data".to_string(),
+                    }];
+                }
+            };
+
+            let telegram_id = verified.telegram_id;
+
+            if let Some(bound) = context.as_ref() {
+                if bound.telegram_id != telegram_id {
+                    shared::record_counter("websocket.identity_mismatch", 1);
+                    return vec![ServerMessage::Error {
+                        message: "Identity mismatch".to_string(),
+                    }];
+                }
+            }
+
             tracing::info!(
                 telegram_id = telegram_id,
-                username = %username,
-                "WebSocket init request"
+                username = ?verified.username,
+                "WebSocket init request verified"
             );
 
-            shared::record_counter("websocket.init.requests", 1);
-
             let user_fetch_start = std::time::Instant::now();
             let client_mutex = state.game_client_pool.get_client();
             let pool_select_time = user_fetch_start.elapsed();
@@ -254,10 +451,25 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                                 }
                             };
 
+                            let mut friends_client = leaderboard_client.clone();
+                            let (nearby, friends) = tokio::join!(
+                                fetch_nearby_ranks(&mut leaderboard_client, &user_response.user_id),
+                                fetch_friends_leaderboard(&mut friends_client, &user_response.user_id)
+                            );
+
                             let total_time = init_start.elapsed();
                             tracing::info!("⏱️ TOTAL WebSocket init time: {:?}", total_time);
 
-                            vec![
+                            let shard = state.click_streams.shard_for(&user_response.user_id);
+
+                            *context = Some(ConnectionContext {
+                                telegram_id,
+                                user_id: user_response.user_id.clone(),
+                                session_id: session_response.session_id.clone(),
+                                shard,
+                            });
+
+                            let mut messages = vec![
                                 ServerMessage::SessionInfo {
                                     session_id: session_response.session_id,
                                     is_reconnection: session_response.is_reconnection,
@@ -269,7 +481,10 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                                     user_id: Some(user_response.user_id),
                                     username: Some(user_response.username),
                                 },
-                            ]
+                            ];
+                            messages.extend(nearby);
+                            messages.extend(friends);
+                            messages
                         }
                         Ok(_) => {
                             tracing::error!("Failed to create/resume session");
@@ -301,17 +516,22 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
         }
 
         ClientMessage::Click {
-            user_id,
-            telegram_id,
-            session_id,
             click_count,
+            batch_started_at,
+            batch_ended_at,
+            claimed,
         } => {
+            let ctx = match require_context(context, &claimed) {
+                Ok(ctx) => ctx,
+                Err(error) => return vec![error],
+            };
+
             let click_start = std::time::Instant::now();
             let batch_size = click_count.unwrap_or(1);
 
             tracing::debug!(
-                user_id = %user_id,
-                session_id = %session_id,
+                user_id = %ctx.user_id,
+                session_id = %ctx.session_id,
                 batch_size = batch_size,
                 "Processing click batch"
             );
@@ -319,35 +539,24 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
             shared::record_counter("click.requests", 1);
             shared::record_counter("click.total_clicks", batch_size as u64);
 
-            let pool_size = state.game_client_pool.size();
-            let shard_index = get_shard_for_user(&user_id, pool_size);
-            let client_mutex = state.game_client_pool.get_client_by_shard(shard_index);
-            let pool_time = click_start.elapsed();
-
-            tracing::debug!(
-                shard = shard_index,
-                pool_size = pool_size,
-                duration_ms = pool_time.as_millis(),
-                "Routed click to shard"
-            );
-            shared::record_timing("click.routing", pool_time.as_secs_f64());
-
-            let mut client = client_mutex.lock().await;
-            let lock_time = click_start.elapsed() - pool_time;
-
-            tracing::debug!(
-                duration_ms = lock_time.as_millis(),
-                "Acquired gRPC client lock for click"
-            );
-            shared::record_timing("click.lock_wait", lock_time.as_secs_f64());
-
-            let user_id_for_rank = user_id.clone();
-
             let grpc_call_start = std::time::Instant::now();
-            let result = client.process_click(user_id, telegram_id, session_id, batch_size).await;
+            let result = state
+                .click_streams
+                .process_click(
+                    ctx.shard,
+                    ctx.user_id.clone(),
+                    ctx.telegram_id,
+                    ctx.session_id.clone(),
+                    batch_size,
+                    BatchTimes {
+                        started_at_ms: batch_started_at.unwrap_or(0),
+                        ended_at_ms: batch_ended_at.unwrap_or(0),
+                    },
+                )
+                .await;
             let grpc_duration = grpc_call_start.elapsed();
 
-            shared::record_timing("grpc.process_click", grpc_duration.as_secs_f64());
+            shared::record_timing("grpc.click_stream", grpc_duration.as_secs_f64());
 
             let total_time = click_start.elapsed();
             shared::record_timing("click.total_latency", total_time.as_secs_f64());
@@ -355,8 +564,7 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
             tracing::info!(
                 total_ms = total_time.as_millis(),
                 grpc_ms = grpc_duration.as_millis(),
-                lock_ms = lock_time.as_millis(),
-                routing_ms = pool_time.as_millis(),
+                shard = ctx.shard,
                 "Click processing complete"
             );
 
@@ -366,29 +574,15 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                         shared::record_counter("click.rate_limited", 1);
                         vec![ServerMessage::RateLimited {
                             message: response.message,
+                            retry_after_ms: response.retry_after_ms,
                         }]
                     } else if response.success {
                         shared::record_counter("click.success", 1);
 
-                        let rank_fetch_start = std::time::Instant::now();
-                        let leaderboard_client_mutex = state.leaderboard_client_pool.get_client();
-                        let mut leaderboard_client = leaderboard_client_mutex.lock().await;
-
-                        let rank = match leaderboard_client.get_user_rank(user_id_for_rank.clone()).await {
-                            Ok(rank_response) if rank_response.found => {
-                                let rank_fetch_time = rank_fetch_start.elapsed();
-                                tracing::debug!("⏱️ Got user rank in {:?}", rank_fetch_time);
-                                rank_response.rank
-                            },
-                            _ => {
-                                tracing::warn!("Failed to get rank for user {}, using 0", user_id_for_rank);
-                                0
-                            }
-                        };
-
+                        // Rank changes arrive separately through the rank update router
                         vec![ServerMessage::ScoreUpdate {
                             score: response.new_total,
-                            rank,
+                            rank: 0,
                             user_id: None, // user_id already known by client
                             username: None, // username already known by client
                         }]
@@ -412,14 +606,17 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
             }
         }
 
-        ClientMessage::Refresh {
-            user_id,
-            telegram_id,
-        } => {
+        ClientMessage::Refresh { claimed } => {
+            let ctx = match require_context(context, &claimed) {
+                Ok(ctx) => ctx,
+                Err(error) => return vec![error],
+            };
+            let telegram_id = ctx.telegram_id;
+
             let refresh_start = std::time::Instant::now();
 
             tracing::debug!(
-                user_id = %user_id,
+                user_id = %ctx.user_id,
                 telegram_id = telegram_id,
                 "Processing refresh request"
             );
@@ -438,7 +635,7 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                     let leaderboard_client_mutex = state.leaderboard_client_pool.get_client();
                     let mut leaderboard_client = leaderboard_client_mutex.lock().await;
 
-                    let rank = match leaderboard_client.get_user_rank(user_id).await {
+                    let rank = match leaderboard_client.get_user_rank(ctx.user_id.clone()).await {
                         Ok(rank_response) if rank_response.found => rank_response.rank,
                         _ => {
                             tracing::warn!("Failed to get rank for user {}, using 0", user_response.user_id);
@@ -446,6 +643,12 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                         }
                     };
 
+                    let mut friends_client = leaderboard_client.clone();
+                    let (nearby, friends) = tokio::join!(
+                        fetch_nearby_ranks(&mut leaderboard_client, &ctx.user_id),
+                        fetch_friends_leaderboard(&mut friends_client, &ctx.user_id)
+                    );
+
                     let total_time = refresh_start.elapsed();
                     shared::record_timing("refresh.total_latency", total_time.as_secs_f64());
                     shared::record_counter("refresh.success", 1);
@@ -457,12 +660,15 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
                         "Refresh completed successfully"
                     );
 
-                    vec![ServerMessage::ScoreUpdate {
+                    let mut messages = vec![ServerMessage::ScoreUpdate {
                         score,
                         rank,
                         user_id: None,
                         username: None,
-                    }]
+                    }];
+                    messages.extend(nearby);
+                    messages.extend(friends);
+                    messages
                 }
                 Ok(_) => {
                     shared::record_counter("refresh.user_not_found", 1);
@@ -485,3 +691,137 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) -> Vec<Serv
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Option<ConnectionContext> {
+        Some(ConnectionContext {
+            telegram_id: 42,
+            user_id: "user-a".to_string(),
+            session_id: "session-a".to_string(),
+            shard: 1,
+        })
+    }
+
+    #[test]
+    fn test_click_without_identifiers_parses() {
+        let msg: ClientMessage = serde_json::from_str(r#"{"type":"click","click_count":5}"#).unwrap();
+
+        match msg {
+            ClientMessage::Click { click_count, batch_started_at, claimed, .. } => {
+                assert_eq!(click_count, Some(5));
+                assert!(batch_started_at.is_none());
+                assert!(claimed.user_id.is_none());
+                assert!(claimed.telegram_id.is_none());
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_click_batch_times_parse() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"type":"click","click_count":5,"batch_started_at":1700000000000,"batch_ended_at":1700000000900}"#,
+        )
+        .unwrap();
+
+        match msg {
+            ClientMessage::Click { batch_started_at, batch_ended_at, .. } => {
+                assert_eq!(batch_started_at, Some(1_700_000_000_000));
+                assert_eq!(batch_ended_at, Some(1_700_000_000_900));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_legacy_click_with_identifiers_parses() {
+        let msg: ClientMessage = serde_json::from_str(
+            r#"{"type":"click","user_id":"user-a","telegram_id":42,"session_id":"session-a","click_count":3}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(msg, ClientMessage::Click { claimed: ClaimedIdentity { telegram_id: Some(42), .. }, .. }));
+    }
+
+    #[test]
+    fn test_require_context_before_init() {
+        let result = require_context(&None, &ClaimedIdentity::default());
+        assert!(matches!(result, Err(ServerMessage::Error { .. })));
+    }
+
+    #[test]
+    fn test_require_context_accepts_matching_or_missing_ids() {
+        let context = context();
+
+        assert!(require_context(&context, &ClaimedIdentity::default()).is_ok());
+
+        let claimed = ClaimedIdentity {
+            user_id: Some("user-a".to_string()),
+            telegram_id: Some(42),
+            session_id: Some("session-a".to_string()),
+        };
+        assert_eq!(require_context(&context, &claimed).unwrap().shard, 1);
+    }
+
+    #[test]
+    fn test_require_context_rejects_spoofed_ids() {
+        let context = context();
+
+        let spoofed_user = ClaimedIdentity {
+            user_id: Some("user-b".to_string()),
+            ..Default::default()
+        };
+        assert!(require_context(&context, &spoofed_user).is_err());
+
+        let spoofed_telegram = ClaimedIdentity {
+            telegram_id: Some(7),
+            ..Default::default()
+        };
+        assert!(require_context(&context, &spoofed_telegram).is_err());
+    }
+
+    #[test]
+    fn test_rate_limited_carries_retry_after() {
+        let message = serde_json::to_value(ServerMessage::RateLimited {
+            message: "Rate limit exceeded".to_string(),
+            retry_after_ms: 300,
+        })
+        .unwrap();
+
+        assert_eq!(message["type"], "rate_limited");
+        assert_eq!(message["retry_after_ms"], 300);
+    }
+
+    #[test]
+    fn test_nearby_ranks_serializes_like_leaderboard_entries() {
+        let message = serde_json::to_value(ServerMessage::NearbyRanks {
+            entries: vec![LeaderboardEntry {
+                rank: 42,
+                username: "alice".to_string(),
+                total_clicks: 100,
+            }],
+        })
+        .unwrap();
+
+        assert_eq!(message["type"], "nearby_ranks");
+        assert_eq!(message["entries"][0]["rank"], 42);
+        assert_eq!(message["entries"][0]["totalClicks"], 100);
+    }
+
+    #[test]
+    fn test_friends_leaderboard_serializes_the_user_rank() {
+        let message = serde_json::to_value(ServerMessage::FriendsLeaderboard {
+            entries: vec![],
+            user_rank: 3,
+            total_count: 5,
+        })
+        .unwrap();
+
+        assert_eq!(message["type"], "friends_leaderboard");
+        assert_eq!(message["user_rank"], 3);
+        assert_eq!(message["total_count"], 5);
+    }
+}
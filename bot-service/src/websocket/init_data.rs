@@ -0,0 +1,196 @@
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use shared::errors::{Result, ServiceError};
+use std::collections::BTreeMap;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const WEB_APP_DATA_KEY: &[u8] = b"WebAppData";
+
+/// How far ahead of our clock an `auth_date` may be before it is rejected, to
+/// allow for drift between Telegram's clock and ours.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedUser {
+    pub telegram_id: i64,
+    pub username: Option<String>,
+    pub first_name: Option<String>,
+    pub auth_date: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct InitDataUser {
+    id: i64,
+    username: Option<String>,
+    first_name: Option<String>,
+}
+
+/// Verifies the `initData` string Telegram hands to Mini Apps, following
+/// https://core.telegram.org/bots/webapps#validating-data-received-via-the-mini-app
+#[derive(Clone)]
+pub struct InitDataValidator {
+    secret_key: Vec<u8>,
+    max_age_secs: i64,
+}
+
+impl InitDataValidator {
+    pub fn new(bot_token: &str, max_age_secs: i64) -> Self {
+        let mut mac = HmacSha256::new_from_slice(WEB_APP_DATA_KEY)
+            .expect("HMAC accepts keys of any length");
+        mac.update(bot_token.as_bytes());
+
+        Self {
+            secret_key: mac.finalize().into_bytes().to_vec(),
+            max_age_secs,
+        }
+    }
+
+    pub fn validate(&self, init_data: &str) -> Result<VerifiedUser> {
+        self.validate_at(init_data, chrono::Utc::now().timestamp())
+    }
+
+    fn validate_at(&self, init_data: &str, now: i64) -> Result<VerifiedUser> {
+        let mut fields: BTreeMap<String, String> = form_urlencoded::parse(init_data.as_bytes())
+            .into_owned()
+            .collect();
+
+        let hash = fields
+            .remove("hash")
+            .ok_or_else(|| ServiceError::Unauthenticated("initData is missing hash".to_string()))?;
+
+        let expected = hex::decode(&hash)
+            .map_err(|_| ServiceError::Unauthenticated("initData hash is not hex".to_string()))?;
+
+        let data_check_string = fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut mac = HmacSha256::new_from_slice(&self.secret_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(data_check_string.as_bytes());
+        mac.verify_slice(&expected)
+            .map_err(|_| ServiceError::Unauthenticated("initData signature mismatch".to_string()))?;
+
+        let auth_date = fields
+            .get("auth_date")
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| ServiceError::Unauthenticated("initData is missing auth_date".to_string()))?;
+
+        if now - auth_date > self.max_age_secs {
+            return Err(ServiceError::Unauthenticated("initData has expired".to_string()));
+        }
+
+        if auth_date > now + MAX_CLOCK_SKEW_SECS {
+            return Err(ServiceError::Unauthenticated(
+                "initData auth_date is in the future".to_string(),
+            ));
+        }
+
+        let user: InitDataUser = fields
+            .get("user")
+            .ok_or_else(|| ServiceError::Unauthenticated("initData is missing user".to_string()))
+            .and_then(|raw| {
+                serde_json::from_str(raw).map_err(|e| {
+                    ServiceError::Unauthenticated(format!("initData user is malformed: {}", e))
+                })
+            })?;
+
+        Ok(VerifiedUser {
+            telegram_id: user.id,
+            username: user.username,
+            first_name: user.first_name,
+            auth_date,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT_TOKEN: &str = "123456:TEST-TOKEN";
+    const USER_JSON: &str = r#"{"id":42,"first_name":"Alice","username":"alice"}"#;
+
+    fn sign(fields: &[(&str, &str)]) -> String {
+        let validator = InitDataValidator::new(BOT_TOKEN, 0);
+
+        let mut sorted: Vec<_> = fields.to_vec();
+        sorted.sort_by_key(|(key, _)| *key);
+        let data_check_string = sorted
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut mac = HmacSha256::new_from_slice(&validator.secret_key).unwrap();
+        mac.update(data_check_string.as_bytes());
+        let hash = hex::encode(mac.finalize().into_bytes());
+
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in fields {
+            serializer.append_pair(key, value);
+        }
+        serializer.append_pair("hash", &hash);
+        serializer.finish()
+    }
+
+    #[test]
+    fn test_valid_init_data() {
+        let init_data = sign(&[("auth_date", "1000"), ("query_id", "AAE"), ("user", USER_JSON)]);
+        let validator = InitDataValidator::new(BOT_TOKEN, 3600);
+
+        let user = validator.validate_at(&init_data, 1500).unwrap();
+
+        assert_eq!(user.telegram_id, 42);
+        assert_eq!(user.username.as_deref(), Some("alice"));
+        assert_eq!(user.auth_date, 1000);
+    }
+
+    #[test]
+    fn test_tampered_init_data_rejected() {
+        let init_data = sign(&[("auth_date", "1000"), ("user", USER_JSON)]);
+        let tampered = init_data.replace("42", "43");
+        let validator = InitDataValidator::new(BOT_TOKEN, 3600);
+
+        let result = validator.validate_at(&tampered, 1500);
+        assert!(matches!(result, Err(ServiceError::Unauthenticated(_))));
+    }
+
+    #[test]
+    fn test_wrong_bot_token_rejected() {
+        let init_data = sign(&[("auth_date", "1000"), ("user", USER_JSON)]);
+        let validator = InitDataValidator::new("654321:OTHER-TOKEN", 3600);
+
+        assert!(validator.validate_at(&init_data, 1500).is_err());
+    }
+
+    #[test]
+    fn test_expired_init_data_rejected() {
+        let init_data = sign(&[("auth_date", "1000"), ("user", USER_JSON)]);
+        let validator = InitDataValidator::new(BOT_TOKEN, 3600);
+
+        assert!(validator.validate_at(&init_data, 1000 + 3601).is_err());
+    }
+
+    #[test]
+    fn test_future_init_data_rejected() {
+        let init_data = sign(&[("auth_date", "5000"), ("user", USER_JSON)]);
+        let validator = InitDataValidator::new(BOT_TOKEN, 3600);
+
+        let result = validator.validate_at(&init_data, 5000 - MAX_CLOCK_SKEW_SECS - 1);
+        assert!(matches!(result, Err(ServiceError::Unauthenticated(_))));
+        assert!(validator.validate_at(&init_data, 5000 - MAX_CLOCK_SKEW_SECS).is_ok());
+    }
+
+    #[test]
+    fn test_missing_hash_rejected() {
+        let validator = InitDataValidator::new(BOT_TOKEN, 3600);
+
+        let result = validator.validate_at("auth_date=1000&user=%7B%22id%22%3A42%7D", 1500);
+        assert!(matches!(result, Err(ServiceError::Unauthenticated(_))));
+    }
+}
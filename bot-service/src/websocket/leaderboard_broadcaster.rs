@@ -1,7 +1,7 @@
 use tokio::sync::broadcast;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{debug, error, info};
+use tracing::{error, info};
 
 use crate::grpc_client::{LeaderboardServiceClient, GrpcClientPool};
 use crate::websocket::handler::{ServerMessage, LeaderboardEntry, BroadcastMessage};
@@ -96,6 +96,7 @@ impl LeaderboardBroadcaster {
     }
 
     #[cfg(test)]
+    #[allow(dead_code)]
     pub fn get_interval(&self) -> Duration {
         self.broadcast_interval
     }
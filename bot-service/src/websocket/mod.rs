@@ -1,5 +1,9 @@
 mod handler;
+mod init_data;
 mod leaderboard_broadcaster;
+mod rank_updates;
 
 pub use handler::{websocket_handler, AppState};
+pub use init_data::InitDataValidator;
 pub use leaderboard_broadcaster::LeaderboardBroadcaster;
+pub use rank_updates::RankUpdateRouter;
@@ -0,0 +1,167 @@
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::grpc_client::leaderboard_client::UserRankUpdate;
+use crate::grpc_client::{GrpcClientPool, LeaderboardServiceClient};
+use shared::ServiceError;
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+type Subscribers = HashMap<String, HashMap<u64, mpsc::UnboundedSender<UserRankUpdate>>>;
+
+/// Holds one `SubscribeUserRank` stream per bot-service instance and routes each
+/// update to the sockets of the user it concerns.
+pub struct RankUpdateRouter {
+    leaderboard_client_pool: Arc<GrpcClientPool<LeaderboardServiceClient>>,
+    subscribers: RwLock<Subscribers>,
+    next_subscription_id: AtomicU64,
+}
+
+/// Registration of one socket for a user's rank updates; unregisters on drop.
+pub struct RankSubscription {
+    router: Arc<RankUpdateRouter>,
+    user_id: String,
+    id: u64,
+}
+
+impl Drop for RankSubscription {
+    fn drop(&mut self) {
+        self.router.unsubscribe(&self.user_id, self.id);
+    }
+}
+
+impl RankUpdateRouter {
+    pub fn new(leaderboard_client_pool: Arc<GrpcClientPool<LeaderboardServiceClient>>) -> Self {
+        Self {
+            leaderboard_client_pool,
+            subscribers: RwLock::new(HashMap::new()),
+            next_subscription_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn subscribe(
+        self: &Arc<Self>,
+        user_id: &str,
+        sender: mpsc::UnboundedSender<UserRankUpdate>,
+    ) -> RankSubscription {
+        let id = self.next_subscription_id.fetch_add(1, Ordering::Relaxed);
+
+        self.subscribers
+            .write()
+            .unwrap()
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(id, sender);
+
+        RankSubscription {
+            router: Arc::clone(self),
+            user_id: user_id.to_string(),
+            id,
+        }
+    }
+
+    fn unsubscribe(&self, user_id: &str, id: u64) {
+        let mut subscribers = self.subscribers.write().unwrap();
+        if let Some(sockets) = subscribers.get_mut(user_id) {
+            sockets.remove(&id);
+            if sockets.is_empty() {
+                subscribers.remove(user_id);
+            }
+        }
+    }
+
+    fn dispatch(&self, update: UserRankUpdate) {
+        let subscribers = self.subscribers.read().unwrap();
+        let Some(sockets) = subscribers.get(&update.user_id) else {
+            return;
+        };
+
+        for sender in sockets.values() {
+            let _ = sender.send(update.clone());
+        }
+        shared::record_counter("rank_updates.forwarded", sockets.len() as u64);
+    }
+
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            info!("Started rank update router");
+
+            loop {
+                if let Err(e) = self.consume_updates().await {
+                    error!(error = %e, "Rank update subscription failed");
+                }
+                warn!("Rank update subscription ended, resubscribing in {:?}", RESUBSCRIBE_DELAY);
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+    }
+
+    async fn consume_updates(&self) -> Result<(), ServiceError> {
+        let client_mutex = self.leaderboard_client_pool.get_client();
+        let mut client = client_mutex.lock().await.clone();
+
+        let mut updates = client.subscribe_user_rank(Vec::new()).await?;
+        info!("Subscribed to leaderboard rank updates");
+
+        while let Some(update) = updates.next().await {
+            let update = update?;
+            debug!(user_id = %update.user_id, rank = update.rank, "Received rank update");
+            self.dispatch(update);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::transport::Channel;
+
+    fn router() -> Arc<RankUpdateRouter> {
+        let channel = Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        let pool = GrpcClientPool::new(vec![LeaderboardServiceClient::new(channel)]);
+        Arc::new(RankUpdateRouter::new(Arc::new(pool)))
+    }
+
+    fn update(user_id: &str, rank: i32) -> UserRankUpdate {
+        UserRankUpdate {
+            user_id: user_id.to_string(),
+            username: String::new(),
+            rank,
+            total_clicks: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_routes_to_matching_user() {
+        let router = router();
+        let (alice_tx, mut alice_rx) = mpsc::unbounded_channel();
+        let (bob_tx, mut bob_rx) = mpsc::unbounded_channel();
+        let _alice = router.subscribe("alice", alice_tx);
+        let _bob = router.subscribe("bob", bob_tx);
+
+        router.dispatch(update("alice", 2));
+
+        assert_eq!(alice_rx.try_recv().unwrap().rank, 2);
+        assert!(bob_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_subscription_stops_delivery() {
+        let router = router();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let subscription = router.subscribe("alice", tx);
+
+        drop(subscription);
+        router.dispatch(update("alice", 1));
+
+        assert!(rx.try_recv().is_err());
+        assert!(router.subscribers.read().unwrap().is_empty());
+    }
+}
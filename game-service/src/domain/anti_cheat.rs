@@ -0,0 +1,455 @@
+use chrono::Duration;
+use shared::{AntiCheatConfig, Result, ServiceError};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::click_validator::{BatchSample, ClientBatchTimes};
+
+/// Batches needed before the statistical signals are trusted.
+const MIN_SAMPLES: usize = 10;
+
+/// Below this implied rate a user is idling, and a steady rhythm means nothing.
+const MIN_ACTIVE_CLICKS_PER_SECOND: f64 = 3.0;
+
+/// Human click rates wander by well over this coefficient of variation.
+const MAX_BOT_RATE_VARIATION: f64 = 0.05;
+
+/// Share of batches carrying the same click count that counts as a fixed cadence.
+const REGULAR_CADENCE_SHARE: f64 = 0.8;
+
+/// Batches this small repeat naturally when clicking slowly.
+const MIN_REGULAR_BATCH: u32 = 3;
+
+/// Share of the rate limit that counts as clicking at the limit. The limiter
+/// makes batches at the limit uniform by itself, so they only count towards
+/// `SustainedMaxRate`, never the rhythm signals.
+const MAX_RATE_SHARE: f64 = 0.9;
+const SUSTAINED_MAX_RATE_SECS: i64 = 60;
+
+/// Largest gap between a batch's last click on the client clock and its arrival.
+const MAX_CLOCK_DRIFT_SECS: i64 = 30;
+
+/// Slack added to a batch's client span for timer and clock granularity.
+const CLIENT_SPAN_SLACK_MS: i64 = 100;
+
+/// Evidence of automated clicking found in a user's batch history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CheatSignal {
+    /// Click rate barely changes from batch to batch.
+    LowTimingVariance,
+    /// Nearly every batch holds the same number of clicks.
+    RegularCadence,
+    /// Clicking at the rate limit for over a minute without a break.
+    SustainedMaxRate,
+    /// Client batch times that are far off the server clock, run backwards, or
+    /// leave too little time for the batch's clicks.
+    BatchTimestampMismatch,
+}
+
+impl CheatSignal {
+    pub fn weight(&self) -> f64 {
+        match self {
+            Self::LowTimingVariance => 0.3,
+            Self::RegularCadence => 0.3,
+            Self::SustainedMaxRate => 0.25,
+            Self::BatchTimestampMismatch => 0.5,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LowTimingVariance => "low_timing_variance",
+            Self::RegularCadence => "regular_cadence",
+            Self::SustainedMaxRate => "sustained_max_rate",
+            Self::BatchTimestampMismatch => "batch_timestamp_mismatch",
+        }
+    }
+}
+
+/// What the anti-cheat does about a user, from mildest to harshest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CheatAction {
+    /// Recorded for review only.
+    Flag,
+    /// Clicks still count, but the user is hidden from the leaderboard.
+    ShadowBan,
+    /// Clicks are refused.
+    Freeze,
+}
+
+impl CheatAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Flag => "flag",
+            Self::ShadowBan => "shadow_ban",
+            Self::Freeze => "freeze",
+        }
+    }
+
+    pub fn hides_from_leaderboard(&self) -> bool {
+        *self >= Self::ShadowBan
+    }
+}
+
+impl fmt::Display for CheatAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CheatAction {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "flag" => Ok(Self::Flag),
+            "shadow_ban" => Ok(Self::ShadowBan),
+            "freeze" => Ok(Self::Freeze),
+            other => Err(ServiceError::Internal(format!("Unknown cheat action: {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspicionReport {
+    /// Sum of the signal weights, capped at 1.0.
+    pub score: f64,
+    pub signals: Vec<CheatSignal>,
+}
+
+impl SuspicionReport {
+    pub fn signal_names(&self) -> Vec<String> {
+        self.signals.iter().map(|signal| signal.as_str().to_string()).collect()
+    }
+}
+
+/// Scores a user's recent batches for signs of an autoclicker.
+#[derive(Debug, Clone)]
+pub struct CheatDetector {
+    config: AntiCheatConfig,
+    max_clicks_per_second: u32,
+}
+
+impl CheatDetector {
+    pub fn new(config: AntiCheatConfig, max_clicks_per_second: u32) -> Self {
+        Self {
+            config,
+            max_clicks_per_second,
+        }
+    }
+
+    /// `history` is oldest first, as returned by `ClickValidator::record_batch`.
+    pub fn analyze(&self, history: &[BatchSample]) -> SuspicionReport {
+        let mut signals = Vec::new();
+
+        if history.len() >= MIN_SAMPLES {
+            let at_limit = self.max_rate_threshold();
+            let rates: Vec<f64> = implied_rates(history)
+                .into_iter()
+                .filter(|rate| *rate < at_limit)
+                .collect();
+
+            if has_low_timing_variance(&rates) {
+                signals.push(CheatSignal::LowTimingVariance);
+            }
+            if has_regular_cadence(history, at_limit) {
+                signals.push(CheatSignal::RegularCadence);
+            }
+            if self.has_sustained_max_rate(history) {
+                signals.push(CheatSignal::SustainedMaxRate);
+            }
+        }
+
+        if self.has_timestamp_mismatch(history) {
+            signals.push(CheatSignal::BatchTimestampMismatch);
+        }
+
+        let score = signals.iter().map(CheatSignal::weight).sum::<f64>().min(1.0);
+
+        SuspicionReport { score, signals }
+    }
+
+    /// Harshest action whose threshold the score reaches.
+    pub fn action_for(&self, score: f64) -> Option<CheatAction> {
+        if score >= self.config.freeze_score {
+            Some(CheatAction::Freeze)
+        } else if score >= self.config.shadow_ban_score {
+            Some(CheatAction::ShadowBan)
+        } else if score >= self.config.flag_score {
+            Some(CheatAction::Flag)
+        } else {
+            None
+        }
+    }
+
+    fn max_rate_threshold(&self) -> f64 {
+        self.max_clicks_per_second as f64 * MAX_RATE_SHARE
+    }
+
+    fn has_sustained_max_rate(&self, history: &[BatchSample]) -> bool {
+        let threshold = self.max_rate_threshold();
+        let sustained = Duration::seconds(SUSTAINED_MAX_RATE_SECS);
+
+        let mut run_start = None;
+        for (i, pair) in history.windows(2).enumerate() {
+            match rate_between(&pair[0], &pair[1]) {
+                Some(rate) if rate >= threshold => {
+                    let start = *run_start.get_or_insert(i);
+                    if pair[1].received_at - history[start].received_at >= sustained {
+                        return true;
+                    }
+                }
+                _ => run_start = None,
+            }
+        }
+
+        false
+    }
+
+    fn has_timestamp_mismatch(&self, history: &[BatchSample]) -> bool {
+        history.iter().any(|sample| {
+            sample
+                .client_times
+                .is_some_and(|times| self.times_mismatch(sample, times))
+        })
+    }
+
+    fn times_mismatch(&self, sample: &BatchSample, times: ClientBatchTimes) -> bool {
+        let drifted = (times.ended_at - sample.received_at).num_seconds().abs() > MAX_CLOCK_DRIFT_SECS;
+
+        let span_ms = (times.ended_at - times.started_at).num_milliseconds();
+        if span_ms < 0 {
+            return true;
+        }
+
+        // n clicks need n - 1 gaps between the first and the last
+        let gaps = sample.click_count.saturating_sub(1) as f64;
+        let possible = self.config.max_human_clicks_per_second as f64 * (span_ms + CLIENT_SPAN_SLACK_MS) as f64 / 1000.0;
+
+        drifted || gaps > possible
+    }
+}
+
+fn rate_between(previous: &BatchSample, current: &BatchSample) -> Option<f64> {
+    let elapsed_ms = (current.received_at - previous.received_at).num_milliseconds();
+    if elapsed_ms <= 0 {
+        return None;
+    }
+
+    Some(current.click_count as f64 * 1000.0 / elapsed_ms as f64)
+}
+
+fn implied_rates(history: &[BatchSample]) -> Vec<f64> {
+    history
+        .windows(2)
+        .filter_map(|pair| rate_between(&pair[0], &pair[1]))
+        .collect()
+}
+
+fn has_low_timing_variance(rates: &[f64]) -> bool {
+    if rates.len() + 1 < MIN_SAMPLES {
+        return false;
+    }
+
+    let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+    if mean < MIN_ACTIVE_CLICKS_PER_SECOND {
+        return false;
+    }
+
+    let variance = rates.iter().map(|rate| (rate - mean).powi(2)).sum::<f64>() / rates.len() as f64;
+    variance.sqrt() / mean < MAX_BOT_RATE_VARIATION
+}
+
+/// Batches at the rate limit are left out; see `MAX_RATE_SHARE`.
+fn has_regular_cadence(history: &[BatchSample], at_limit: f64) -> bool {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for pair in history.windows(2) {
+        if rate_between(&pair[0], &pair[1]).is_some_and(|rate| rate < at_limit) {
+            *counts.entry(pair[1].click_count).or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .filter(|(click_count, _)| *click_count >= MIN_REGULAR_BATCH)
+        .any(|(_, batches)| batches as f64 / history.len() as f64 >= REGULAR_CADENCE_SHARE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::click_validator::BATCH_HISTORY_LEN;
+    use chrono::{DateTime, Utc};
+
+    fn detector() -> CheatDetector {
+        CheatDetector::new(AntiCheatConfig::default(), 10)
+    }
+
+    /// Builds a history from `(ms since previous batch, click count)` pairs.
+    fn history(batches: &[(i64, u32)]) -> Vec<BatchSample> {
+        let mut at: DateTime<Utc> = Utc::now() - Duration::minutes(10);
+        batches
+            .iter()
+            .map(|&(gap_ms, click_count)| {
+                at += Duration::milliseconds(gap_ms);
+                BatchSample {
+                    received_at: at,
+                    // The first click lands a little after the previous batch went out
+                    client_times: Some(ClientBatchTimes {
+                        started_at: at - Duration::milliseconds((gap_ms - 100).max(0)),
+                        ended_at: at,
+                    }),
+                    click_count,
+                }
+            })
+            .collect()
+    }
+
+    fn autoclicker(batches: usize, click_count: u32) -> Vec<BatchSample> {
+        history(&vec![(1_000, click_count); batches])
+    }
+
+    #[test]
+    fn test_human_clicking_is_not_suspicious() {
+        let counts = [3, 7, 5, 2, 6, 4, 8, 5, 3, 6, 7, 4, 2, 5, 6, 3];
+        let gaps = [1_000, 1_130, 870, 1_240, 960, 1_050, 910, 1_180, 1_020, 890, 1_310, 940, 1_070, 980, 1_150, 860];
+        let batches: Vec<(i64, u32)> = gaps.into_iter().zip(counts).collect();
+
+        let report = detector().analyze(&history(&batches));
+
+        assert!(report.signals.is_empty(), "Unexpected signals: {:?}", report.signals);
+        assert_eq!(report.score, 0.0);
+        assert_eq!(detector().action_for(report.score), None);
+    }
+
+    #[test]
+    fn test_too_few_batches_are_not_analyzed() {
+        let report = detector().analyze(&autoclicker(MIN_SAMPLES - 1, 10));
+        assert!(report.signals.is_empty());
+    }
+
+    #[test]
+    fn test_steady_autoclicker_is_caught() {
+        let report = detector().analyze(&autoclicker(20, 6));
+
+        assert_eq!(report.signals, vec![CheatSignal::LowTimingVariance, CheatSignal::RegularCadence]);
+        assert!((report.score - 0.6).abs() < f64::EPSILON);
+        assert_eq!(detector().action_for(report.score), Some(CheatAction::ShadowBan));
+    }
+
+    #[test]
+    fn test_sustained_max_rate() {
+        let report = detector().analyze(&autoclicker(SUSTAINED_MAX_RATE_SECS as usize + 2, 10));
+        assert!(report.signals.contains(&CheatSignal::SustainedMaxRate));
+
+        // A short burst at the limit is fine
+        let report = detector().analyze(&autoclicker(30, 10));
+        assert!(!report.signals.contains(&CheatSignal::SustainedMaxRate));
+    }
+
+    /// Taps at the rate limit, sent on the mini-app's one-second timer: every
+    /// batch is capped at the same size and arrives at the same pace, with the odd
+    /// batch sent late after a retry-after wait.
+    #[test]
+    fn test_steady_max_rate_player_stays_below_flag() {
+        let mut batches = Vec::new();
+        for i in 0..BATCH_HISTORY_LEN {
+            let gap = [1_000, 1_004, 996, 1_002, 998][i % 5];
+            if i % 20 == 19 {
+                batches.push((gap + 1_000, 20));
+            } else {
+                batches.push((gap, 10));
+            }
+        }
+
+        let detector = detector();
+        let report = detector.analyze(&history(&batches));
+
+        assert_eq!(report.signals, vec![CheatSignal::SustainedMaxRate]);
+        assert!(report.score < AntiCheatConfig::default().flag_score, "Score {}", report.score);
+        assert_eq!(detector.action_for(report.score), None);
+    }
+
+    #[test]
+    fn test_batch_larger_than_client_times_allow() {
+        let mut batches = history(&[(1_000, 5), (1_000, 5)]);
+        let ended_at = batches[1].received_at;
+        batches[1].client_times = Some(ClientBatchTimes {
+            started_at: ended_at - Duration::milliseconds(500),
+            ended_at,
+        });
+        batches[1].click_count = 45;
+
+        let report = detector().analyze(&batches);
+        assert_eq!(report.signals, vec![CheatSignal::BatchTimestampMismatch]);
+    }
+
+    #[test]
+    fn test_batch_times_running_backwards_are_a_mismatch() {
+        let mut batches = history(&[(1_000, 5)]);
+        let ended_at = batches[0].received_at;
+        batches[0].client_times = Some(ClientBatchTimes {
+            started_at: ended_at + Duration::seconds(1),
+            ended_at,
+        });
+
+        let report = detector().analyze(&batches);
+        assert_eq!(report.signals, vec![CheatSignal::BatchTimestampMismatch]);
+    }
+
+    #[test]
+    fn test_client_clock_drift_is_a_mismatch() {
+        let mut batches = history(&[(1_000, 5)]);
+        let ended_at = batches[0].received_at - Duration::minutes(5);
+        batches[0].client_times = Some(ClientBatchTimes {
+            started_at: ended_at - Duration::milliseconds(900),
+            ended_at,
+        });
+
+        let report = detector().analyze(&batches);
+        assert_eq!(report.signals, vec![CheatSignal::BatchTimestampMismatch]);
+    }
+
+    #[test]
+    fn test_batches_without_client_times_are_not_a_mismatch() {
+        let mut batches = history(&[(1_000, 5), (1_000, 45)]);
+        for batch in &mut batches {
+            batch.client_times = None;
+        }
+
+        let report = detector().analyze(&batches);
+        assert!(report.signals.is_empty());
+    }
+
+    #[test]
+    fn test_action_thresholds() {
+        let detector = detector();
+
+        assert_eq!(detector.action_for(0.29), None);
+        assert_eq!(detector.action_for(0.3), Some(CheatAction::Flag));
+        assert_eq!(detector.action_for(0.6), Some(CheatAction::ShadowBan));
+        assert_eq!(detector.action_for(1.0), Some(CheatAction::Freeze));
+    }
+
+    #[test]
+    fn test_threshold_above_one_disables_action() {
+        let config = AntiCheatConfig {
+            freeze_score: 1.1,
+            ..AntiCheatConfig::default()
+        };
+        let detector = CheatDetector::new(config, 10);
+
+        assert_eq!(detector.action_for(1.0), Some(CheatAction::ShadowBan));
+    }
+
+    #[test]
+    fn test_cheat_action_round_trips() {
+        for action in [CheatAction::Flag, CheatAction::ShadowBan, CheatAction::Freeze] {
+            assert_eq!(action.as_str().parse::<CheatAction>().unwrap(), action);
+        }
+        assert!("ban".parse::<CheatAction>().is_err());
+        assert!(CheatAction::Freeze > CheatAction::ShadowBan);
+        assert!(!CheatAction::Flag.hides_from_leaderboard());
+    }
+}
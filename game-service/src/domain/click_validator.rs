@@ -1,11 +1,36 @@
 use chrono::{DateTime, Duration, Utc};
 use shared::{Result, ServiceError, UserId};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, RwLock};
 
+/// Batches kept per user for cheat analysis, about two minutes of steady play.
+pub const BATCH_HISTORY_LEN: usize = 64;
 
+/// Histories untouched for this long are dropped by `cleanup_old_data`.
+const BATCH_HISTORY_IDLE_SECS: i64 = 300;
+
+/// The player's own clock at a batch's first and last click, as the mini-app
+/// reported it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientBatchTimes {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+}
+
+/// One click batch as this instance received it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSample {
+    pub received_at: DateTime<Utc>,
+    /// `None` for callers that don't report batch times.
+    pub client_times: Option<ClientBatchTimes>,
+    pub click_count: u32,
+}
+
+/// Per-user click history held in memory. Each user's clicks always reach the
+/// shard that owns them, so one instance sees a user's whole history.
 pub struct ClickValidator {
     recent_clicks: Arc<RwLock<HashMap<UserId, Vec<DateTime<Utc>>>>>,
+    batch_history: Arc<RwLock<HashMap<UserId, VecDeque<BatchSample>>>>,
     max_clicks_per_second: u32,
 }
 
@@ -14,6 +39,7 @@ impl ClickValidator {
     pub fn new(max_clicks_per_second: u32) -> Self {
         Self {
             recent_clicks: Arc::new(RwLock::new(HashMap::new())),
+            batch_history: Arc::new(RwLock::new(HashMap::new())),
             max_clicks_per_second,
         }
     }
@@ -31,7 +57,13 @@ impl ClickValidator {
         user_clicks.retain(|&click_time| click_time > cutoff);
 
         if user_clicks.len() >= self.max_clicks_per_second as usize {
-            return Err(ServiceError::RateLimitExceeded);
+            // The oldest click in the window is the next to age out
+            let retry_after_ms = user_clicks
+                .iter()
+                .min()
+                .map(|oldest| (*oldest - cutoff).num_milliseconds().max(0) as u64)
+                .unwrap_or(0);
+            return Err(ServiceError::RateLimitExceeded { retry_after_ms });
         }
 
         user_clicks.push(timestamp);
@@ -39,6 +71,27 @@ impl ClickValidator {
         Ok(())
     }
 
+    pub fn max_clicks_per_second(&self) -> u32 {
+        self.max_clicks_per_second
+    }
+
+    /// Appends a batch to the user's history and returns the history, oldest
+    /// first. Only the last `BATCH_HISTORY_LEN` batches are kept.
+    pub fn record_batch(&self, user_id: &UserId, sample: BatchSample) -> Result<Vec<BatchSample>> {
+        let mut batch_history = self
+            .batch_history
+            .write()
+            .map_err(|e| ServiceError::Internal(format!("Lock error: {}", e)))?;
+
+        let history = batch_history.entry(*user_id).or_default();
+        if history.len() == BATCH_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(sample);
+
+        Ok(history.iter().copied().collect())
+    }
+
     pub fn cleanup_old_data(&self) {
         if let Ok(mut recent_clicks) = self.recent_clicks.write() {
             let cutoff = Utc::now() - Duration::seconds(10);
@@ -46,6 +99,13 @@ impl ClickValidator {
                 clicks.iter().any(|&click_time| click_time > cutoff)
             });
         }
+
+        if let Ok(mut batch_history) = self.batch_history.write() {
+            let cutoff = Utc::now() - Duration::seconds(BATCH_HISTORY_IDLE_SECS);
+            batch_history.retain(|_, history| {
+                history.back().is_some_and(|sample| sample.received_at > cutoff)
+            });
+        }
     }
 
     pub fn get_current_rate(&self, user_id: &UserId) -> u32 {
@@ -171,6 +231,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_record_batch_keeps_bounded_history() {
+        let validator = ClickValidator::new(10);
+        let user_id = UserId::new();
+        let start = Utc::now();
+
+        let mut history = Vec::new();
+        for i in 0..(BATCH_HISTORY_LEN as i64 + 5) {
+            let at = start + Duration::seconds(i);
+            history = validator
+                .record_batch(&user_id, BatchSample { received_at: at, client_times: None, click_count: 1 })
+                .unwrap();
+        }
+
+        assert_eq!(history.len(), BATCH_HISTORY_LEN);
+        assert_eq!(history[0].received_at, start + Duration::seconds(5));
+    }
+
+    #[test]
+    fn test_cleanup_old_data_drops_idle_batch_history() {
+        let validator = ClickValidator::new(10);
+        let idle_user = UserId::new();
+        let active_user = UserId::new();
+        let old_time = Utc::now() - Duration::seconds(BATCH_HISTORY_IDLE_SECS + 60);
+        let now = Utc::now();
+
+        validator
+            .record_batch(&idle_user, BatchSample { received_at: old_time, client_times: None, click_count: 3 })
+            .unwrap();
+        validator
+            .record_batch(&active_user, BatchSample { received_at: now, client_times: None, click_count: 3 })
+            .unwrap();
+
+        validator.cleanup_old_data();
+
+        let history = validator.batch_history.read().unwrap();
+        assert!(!history.contains_key(&idle_user));
+        assert!(history.contains_key(&active_user));
+    }
+
     #[test]
     fn test_rate_limit_error_type() {
         let validator = ClickValidator::new(1);
@@ -180,6 +280,9 @@ mod tests {
         validator.validate_click(&user_id, now).unwrap();
 
         let result = validator.validate_click(&user_id, now);
-        assert!(matches!(result, Err(ServiceError::RateLimitExceeded)));
+        assert!(matches!(
+            result,
+            Err(ServiceError::RateLimitExceeded { retry_after_ms: 1000 })
+        ));
     }
 }
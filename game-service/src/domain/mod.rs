@@ -1,6 +1,9 @@
-
+pub mod anti_cheat;
 pub mod click_validator;
+pub mod moderation;
 pub mod rate_limiter;
 
-pub use click_validator::ClickValidator;
+pub use anti_cheat::{CheatAction, CheatDetector, CheatSignal, SuspicionReport};
+pub use click_validator::{BatchSample, ClientBatchTimes, ClickValidator};
+pub use moderation::{AdminAction, ScoreAdjustment};
 pub use rate_limiter::RateLimiter;
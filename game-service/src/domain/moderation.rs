@@ -0,0 +1,59 @@
+use std::fmt;
+
+/// Admin actions, as named in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminAction {
+    Ban,
+    Unban,
+    AdjustScore,
+    Rename,
+    RemoveFromLeaderboard,
+}
+
+impl AdminAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ban => "ban",
+            Self::Unban => "unban",
+            Self::AdjustScore => "adjust_score",
+            Self::Rename => "rename",
+            Self::RemoveFromLeaderboard => "remove_from_leaderboard",
+        }
+    }
+}
+
+impl fmt::Display for AdminAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How an admin changes a user's total. Totals never go below zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreAdjustment {
+    Delta(i64),
+    SetTo(i64),
+}
+
+impl ScoreAdjustment {
+    pub fn apply(&self, total_clicks: i64) -> i64 {
+        match self {
+            Self::Delta(delta) => total_clicks.saturating_add(*delta).max(0),
+            Self::SetTo(total) => (*total).max(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_adjustment_never_goes_negative() {
+        assert_eq!(ScoreAdjustment::Delta(25).apply(100), 125);
+        assert_eq!(ScoreAdjustment::Delta(-250).apply(100), 0);
+        assert_eq!(ScoreAdjustment::SetTo(40).apply(100), 40);
+        assert_eq!(ScoreAdjustment::SetTo(-1).apply(100), 0);
+        assert_eq!(ScoreAdjustment::Delta(i64::MAX).apply(100), i64::MAX);
+    }
+}
@@ -1,62 +1,106 @@
 use redis::aio::MultiplexedConnection;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, Script};
 use shared::{Result, ServiceError, UserId};
 
+/// Token bucket refilled continuously at the per-second rate and holding
+/// `burst_secs` of clicks, so a batch delayed by timer jitter, a reconnect or a
+/// retry-after wait still fits once it goes out. Runs as one script so the
+/// bucket and its TTL are always written together, and reads the Redis clock so
+/// every game-service instance refills at the same pace.
+/// Returns `{allowed, retry_after_ms}`, with `allowed` -1 for a batch larger
+/// than the bucket, which no amount of waiting would let through.
+const TOKEN_BUCKET_SCRIPT: &str = r"
+local capacity = tonumber(ARGV[1])
+local per_second = tonumber(ARGV[2])
+local requested = tonumber(ARGV[3])
+if requested > capacity then
+    return {-1, 0}
+end
+local per_ms = per_second / 1000
+
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * per_ms)
+
+local allowed = 0
+local retry_after = 0
+if tokens >= requested then
+    tokens = tokens - requested
+    allowed = 1
+else
+    retry_after = math.ceil((requested - tokens) / per_ms)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / per_ms) + 1000)
+return {allowed, retry_after}
+";
+
+/// Cheap to clone: clones share one multiplexed connection, so concurrent clicks
+/// check their limits in parallel instead of queueing behind a lock.
+#[derive(Clone)]
 pub struct RateLimiter {
     redis: MultiplexedConnection,
     max_clicks_per_second: u32,
+    burst_secs: u32,
+    script: Script,
 }
 
+/// Shortest burst window: one second of clicks would reject every batch that
+/// arrives even slightly late.
+const MIN_BURST_SECS: u32 = 2;
+
 impl RateLimiter {
 
-    pub fn new(redis: MultiplexedConnection, max_clicks_per_second: u32) -> Self {
+    /// `burst_secs` below two seconds is raised to two.
+    pub fn new(redis: MultiplexedConnection, max_clicks_per_second: u32, burst_secs: u32) -> Self {
         Self {
             redis,
             max_clicks_per_second,
+            burst_secs: burst_secs.max(MIN_BURST_SECS),
+            script: Script::new(TOKEN_BUCKET_SCRIPT),
         }
     }
 
-    pub async fn check_rate_limit(&mut self, user_id: &UserId, click_count: u32) -> Result<()> {
-        // Create key with 1-second window
+    pub async fn check_rate_limit(&self, user_id: &UserId, click_count: u32) -> Result<()> {
         let key = format!("rate_limit:{}", user_id);
+        let mut redis = self.redis.clone();
 
-        let count: u32 = self
-            .redis
-            .incr(&key, click_count)
+        let (allowed, retry_after_ms): (i8, u64) = self
+            .script
+            .key(&key)
+            .arg(self.capacity())
+            .arg(self.max_clicks_per_second)
+            .arg(click_count)
+            .invoke_async(&mut redis)
             .await
             .map_err(|e| ServiceError::Redis(e.to_string()))?;
 
-        if count == click_count {
-            self.redis
-                .expire(&key, 1)
-                .await
-                .map_err(|e| ServiceError::Redis(e.to_string()))?;
-        }
-
-        if count > self.max_clicks_per_second {
-            return Err(ServiceError::RateLimitExceeded);
+        match allowed {
+            -1 => Err(ServiceError::Validation(format!(
+                "Batch of {} clicks exceeds the burst limit of {}",
+                click_count,
+                self.capacity()
+            ))),
+            0 => Err(ServiceError::RateLimitExceeded { retry_after_ms }),
+            _ => Ok(()),
         }
-
-        Ok(())
     }
 
-    pub async fn get_current_count(&mut self, user_id: &UserId) -> Result<u32> {
-        let key = format!("rate_limit:{}", user_id);
-
-        let count: Option<u32> = self
-            .redis
-            .get(&key)
-            .await
-            .map_err(|e| ServiceError::Redis(e.to_string()))?;
-
-        Ok(count.unwrap_or(0))
+    fn capacity(&self) -> u64 {
+        u64::from(self.max_clicks_per_second) * u64::from(self.burst_secs)
     }
 
-    pub async fn reset(&mut self, user_id: &UserId) -> Result<()> {
+    pub async fn reset(&self, user_id: &UserId) -> Result<()> {
         let key = format!("rate_limit:{}", user_id);
 
         self.redis
-            .del(&key)
+            .clone()
+            .del::<_, ()>(&key)
             .await
             .map_err(|e| ServiceError::Redis(e.to_string()))?;
 
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use shared::proto::{
+    admin_service_server::AdminService,
+    adjust_score_request::Change,
+    AdjustScoreRequest, AdjustScoreResponse,
+    AdminActionResponse,
+    BanUserRequest, UnbanUserRequest,
+    RenameUserRequest, RemoveFromLeaderboardRequest,
+    ListFlaggedUsersRequest, ListFlaggedUsersResponse, FlaggedUser,
+    ListTelegramIdsRequest, ListTelegramIdsResponse,
+};
+use shared::UserId;
+
+use crate::domain::{CheatAction, ScoreAdjustment};
+use crate::repository::AuditEntry;
+use crate::service::ModerationService;
+
+const DEFAULT_FLAGGED_LIMIT: i32 = 50;
+const MAX_FLAGGED_LIMIT: i32 = 500;
+const DEFAULT_TELEGRAM_ID_LIMIT: i32 = 500;
+const MAX_TELEGRAM_ID_LIMIT: i32 = 5_000;
+
+#[derive(Clone)]
+pub struct AdminServerImpl {
+    moderation: Arc<ModerationService>,
+}
+
+impl AdminServerImpl {
+    pub fn new(moderation: Arc<ModerationService>) -> Self {
+        Self { moderation }
+    }
+}
+
+fn parse_user_id(user_id: &str) -> Result<UserId, Status> {
+    UserId::from_string(user_id).map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+fn audit_entry(actor: String, reason: String) -> Result<AuditEntry, Status> {
+    if actor.trim().is_empty() {
+        return Err(Status::invalid_argument("actor is required"));
+    }
+
+    Ok(AuditEntry { actor, reason })
+}
+
+fn done(message: &str) -> Response<AdminActionResponse> {
+    Response::new(AdminActionResponse {
+        success: true,
+        message: message.to_string(),
+    })
+}
+
+#[tonic::async_trait]
+impl AdminService for AdminServerImpl {
+    async fn ban_user(&self, request: Request<BanUserRequest>) -> Result<Response<AdminActionResponse>, Status> {
+        let req = request.into_inner();
+        let user_id = parse_user_id(&req.user_id)?;
+        let audit = audit_entry(req.actor, req.reason)?;
+
+        self.moderation.ban_user(&user_id, &audit).await?;
+        shared::record_counter("game_service.admin.ban", 1);
+
+        Ok(done("User banned"))
+    }
+
+    async fn unban_user(&self, request: Request<UnbanUserRequest>) -> Result<Response<AdminActionResponse>, Status> {
+        let req = request.into_inner();
+        let user_id = parse_user_id(&req.user_id)?;
+        let audit = audit_entry(req.actor, req.reason)?;
+
+        self.moderation.unban_user(&user_id, &audit).await?;
+        shared::record_counter("game_service.admin.unban", 1);
+
+        Ok(done("User unbanned"))
+    }
+
+    async fn adjust_score(&self, request: Request<AdjustScoreRequest>) -> Result<Response<AdjustScoreResponse>, Status> {
+        let req = request.into_inner();
+        let user_id = parse_user_id(&req.user_id)?;
+
+        let adjustment = match req.change {
+            Some(Change::Delta(delta)) => ScoreAdjustment::Delta(delta),
+            Some(Change::SetTo(total)) => ScoreAdjustment::SetTo(total),
+            None => return Err(Status::invalid_argument("delta or set_to is required")),
+        };
+        if req.reason.trim().is_empty() {
+            return Err(Status::invalid_argument("reason is required"));
+        }
+        let audit = audit_entry(req.actor, req.reason)?;
+
+        let new_total = self.moderation.adjust_score(&user_id, adjustment, &audit).await?;
+        shared::record_counter("game_service.admin.adjust_score", 1);
+
+        Ok(Response::new(AdjustScoreResponse {
+            success: true,
+            message: "Score adjusted".to_string(),
+            new_total,
+        }))
+    }
+
+    async fn rename_user(&self, request: Request<RenameUserRequest>) -> Result<Response<AdminActionResponse>, Status> {
+        let req = request.into_inner();
+        let user_id = parse_user_id(&req.user_id)?;
+        let audit = audit_entry(req.actor, req.reason)?;
+
+        self.moderation.rename_user(&user_id, &req.new_username, &audit).await?;
+        shared::record_counter("game_service.admin.rename", 1);
+
+        Ok(done("User renamed"))
+    }
+
+    async fn remove_from_leaderboard(
+        &self,
+        request: Request<RemoveFromLeaderboardRequest>,
+    ) -> Result<Response<AdminActionResponse>, Status> {
+        let req = request.into_inner();
+        let user_id = parse_user_id(&req.user_id)?;
+        let audit = audit_entry(req.actor, req.reason)?;
+
+        self.moderation.remove_from_leaderboard(&user_id, &audit).await?;
+        shared::record_counter("game_service.admin.remove_from_leaderboard", 1);
+
+        Ok(done("User removed from leaderboard"))
+    }
+
+    async fn list_flagged_users(
+        &self,
+        request: Request<ListFlaggedUsersRequest>,
+    ) -> Result<Response<ListFlaggedUsersResponse>, Status> {
+        let req = request.into_inner();
+
+        let action = match req.action.as_str() {
+            "" => None,
+            action => Some(
+                action
+                    .parse::<CheatAction>()
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?,
+            ),
+        };
+        let limit = if req.limit <= 0 { DEFAULT_FLAGGED_LIMIT } else { req.limit.min(MAX_FLAGGED_LIMIT) };
+        let offset = req.offset.max(0);
+
+        let (users, total_count) = self
+            .moderation
+            .list_flagged_users(action, limit as i64, offset as i64)
+            .await?;
+
+        let users = users
+            .into_iter()
+            .map(|user| FlaggedUser {
+                user_id: user.user_id.to_string(),
+                username: user.username,
+                total_clicks: user.total_clicks,
+                score: user.score,
+                signals: user.signals,
+                action: user.action.to_string(),
+                banned: user.banned,
+                first_detected_at: user.first_detected_at.timestamp(),
+                updated_at: user.updated_at.timestamp(),
+            })
+            .collect();
+
+        Ok(Response::new(ListFlaggedUsersResponse { users, total_count }))
+    }
+
+    async fn list_telegram_ids(
+        &self,
+        request: Request<ListTelegramIdsRequest>,
+    ) -> Result<Response<ListTelegramIdsResponse>, Status> {
+        let req = request.into_inner();
+        let limit = if req.limit <= 0 { DEFAULT_TELEGRAM_ID_LIMIT } else { req.limit.min(MAX_TELEGRAM_ID_LIMIT) };
+
+        let telegram_ids = self
+            .moderation
+            .list_telegram_ids(req.after_telegram_id, limit as i64)
+            .await?;
+
+        Ok(Response::new(ListTelegramIdsResponse { telegram_ids }))
+    }
+}
@@ -1,6 +1,11 @@
-use tonic::{Request, Response, Status};
+use futures::{SinkExt, Stream};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{watch, Semaphore};
+use tonic::{Request, Response, Status, Streaming};
 use shared::proto::{
     game_service_server::GameService,
+    ClickStreamRequest, ClickStreamResponse,
     CreateUserRequest, CreateUserResponse,
     GetUserRequest, GetUserResponse,
     UpdateUsernameRequest, UpdateUsernameResponse,
@@ -11,32 +16,169 @@ use shared::proto::{
     GetSessionStatsRequest, GetSessionStatsResponse,
     GetOrCreateSessionRequest, GetOrCreateSessionResponse,
 };
-use shared::{UserId, SessionId};
+use shared::{ShardMap, UserId, SessionId};
 
-use crate::service::{UserService, ClickService, SessionService};
+use crate::domain::ClientBatchTimes;
+use crate::service::{UserService, ReferralService, ClickService, SessionService};
 
+/// Acks buffered per click stream before the reader stops pulling new batches.
+const CLICK_STREAM_BUFFER: usize = 1024;
 
+/// Batches processed at once per click stream. Past this the reader waits for a
+/// slot, so a flood of batches backs up on the stream instead of in tasks.
+const CLICK_STREAM_MAX_IN_FLIGHT: usize = 64;
+
+#[derive(Clone)]
 pub struct GameServerImpl {
-    user_service: UserService,
-    click_service: ClickService,
-    session_service: SessionService,
+    user_service: Arc<UserService>,
+    referral_service: Arc<ReferralService>,
+    click_service: Arc<ClickService>,
+    session_service: Arc<SessionService>,
+    shard_map: Arc<ShardMap>,
+    instance_id: String,
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 impl GameServerImpl {
 
     pub fn new(
         user_service: UserService,
+        referral_service: Arc<ReferralService>,
         click_service: ClickService,
         session_service: SessionService,
+        shard_map: Arc<ShardMap>,
+        instance_id: String,
     ) -> Self {
         Self {
-            user_service,
-            click_service,
-            session_service,
+            user_service: Arc::new(user_service),
+            referral_service,
+            click_service: Arc::new(click_service),
+            session_service: Arc::new(session_service),
+            shard_map,
+            instance_id,
+            shutdown: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Stops click streams from reading new batches. Batches already received are
+    /// still processed and acked before each stream ends, so the server can drain.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    async fn handle_click(&self, req: ProcessClickRequest) -> Result<ProcessClickResponse, Status> {
+        let click_count = if req.click_count == 0 { 1 } else { req.click_count };
+
+        let user_id = UserId::from_string(&req.user_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let session_id = SessionId::from_string(&req.session_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        // Another shard's clicks would land in this instance's pending hash, so
+        // refuse them and name the owner for the caller to retry there
+        let owner = self.shard_map.shard_for(&req.user_id);
+        if owner.id != self.instance_id {
+            shared::record_counter("game_service.click.wrong_shard", 1);
+            tracing::debug!(user_id = %req.user_id, owner = %owner.id, "Refusing click for user owned by another shard");
+            return Ok(ProcessClickResponse {
+                owner_shard: owner.id.clone(),
+                ..failed_click("User is owned by another shard")
+            });
+        }
+
+        // Get user to retrieve username for batch accumulation
+        let user = match self.user_service.get_user_by_id(&user_id).await {
+            Ok(user) => user,
+            Err(_) => {
+                return Err(Status::not_found("User not found"));
+            }
+        };
+
+        // 0 means the client didn't report when it clicked
+        let client_times = (req.batch_started_at_ms > 0 && req.batch_ended_at_ms > 0)
+            .then(|| {
+                Some(ClientBatchTimes {
+                    started_at: chrono::DateTime::from_timestamp_millis(req.batch_started_at_ms)?,
+                    ended_at: chrono::DateTime::from_timestamp_millis(req.batch_ended_at_ms)?,
+                })
+            })
+            .flatten();
+
+        match self
+            .click_service
+            .process_click(&user_id, user.username.as_str(), &session_id, click_count, client_times)
+            .await
+        {
+            Ok(click_result) => {
+                let current_rank = 0;
+
+                Ok(ProcessClickResponse {
+                    new_total: click_result.total_clicks,
+                    current_rank,
+                    rate_limited: false,
+                    message: "Click processed".to_string(),
+                    success: true,
+                    session_clicks: 0, // Deprecated - no longer tracked
+                    owner_shard: String::new(),
+                    retry_after_ms: 0,
+                })
+            }
+            Err(shared::ServiceError::RateLimitExceeded { retry_after_ms }) => Ok(ProcessClickResponse {
+                new_total: 0,
+                current_rank: 0,
+                rate_limited: true,
+                message: "Rate limit exceeded".to_string(),
+                success: false,
+                session_clicks: 0,
+                owner_shard: String::new(),
+                retry_after_ms,
+            }),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to process click");
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Processes one batch from a click stream. Failures are reported in the ack
+    /// rather than as a stream error so other users on the stream are unaffected.
+    async fn handle_stream_click(&self, request: ClickStreamRequest) -> ClickStreamResponse {
+        let Some(click) = request.click else {
+            return ClickStreamResponse {
+                request_id: request.request_id,
+                user_id: String::new(),
+                result: Some(failed_click("Missing click payload")),
+            };
+        };
+
+        let user_id = click.user_id.clone();
+        let result = match self.handle_click(click).await {
+            Ok(response) => response,
+            Err(status) => failed_click(status.message()),
+        };
+
+        ClickStreamResponse {
+            request_id: request.request_id,
+            user_id,
+            result: Some(result),
         }
     }
 }
 
+fn failed_click(message: &str) -> ProcessClickResponse {
+    ProcessClickResponse {
+        new_total: 0,
+        current_rank: 0,
+        rate_limited: false,
+        message: message.to_string(),
+        success: false,
+        session_clicks: 0,
+        owner_shard: String::new(),
+        retry_after_ms: 0,
+    }
+}
+
 #[tonic::async_trait]
 impl GameService for GameServerImpl {
     async fn create_user(
@@ -51,14 +193,29 @@ impl GameService for GameServerImpl {
             "CreateUser request"
         );
 
-        match self.user_service.register_user(req.telegram_id, &req.username).await {
-            Ok(user) => {
+        // A bad code never fails the registration, but a referral that can't be
+        // recorded fails it as a whole, so the caller can retry
+        let registered = if req.referral_code.is_empty() {
+            self.user_service
+                .register_user(req.telegram_id, &req.username)
+                .await
+                .map(|user| (user, None))
+        } else {
+            self.referral_service
+                .register_referred_user(req.telegram_id, &req.username, &req.referral_code)
+                .await
+        };
+
+        match registered {
+            Ok((user, inviter)) => {
+                let referred_by = inviter.map(|inviter| inviter.username).unwrap_or_default();
                 let response = CreateUserResponse {
                     user_id: user.id.to_string(),
                     username: user.username.as_str().to_string(),
                     total_clicks: user.total_clicks,
                     success: true,
                     message: "User created successfully".to_string(),
+                    referred_by,
                 };
                 Ok(Response::new(response))
             }
@@ -140,53 +297,68 @@ impl GameService for GameServerImpl {
         &self,
         request: Request<ProcessClickRequest>,
     ) -> Result<Response<ProcessClickResponse>, Status> {
-        let req = request.into_inner();
-        let click_count = if req.click_count == 0 { 1 } else { req.click_count };
+        let response = self.handle_click(request.into_inner()).await?;
+        Ok(Response::new(response))
+    }
 
-        let user_id = UserId::from_string(&req.user_id)
-            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    type ClickStreamStream =
+        Pin<Box<dyn Stream<Item = Result<ClickStreamResponse, Status>> + Send + 'static>>;
 
-        let session_id = SessionId::from_string(&req.session_id)
-            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    async fn click_stream(
+        &self,
+        request: Request<Streaming<ClickStreamRequest>>,
+    ) -> Result<Response<Self::ClickStreamStream>, Status> {
+        let mut inbound = request.into_inner();
+        let (ack_tx, ack_rx) = futures::channel::mpsc::channel(CLICK_STREAM_BUFFER);
+        let server = self.clone();
+        let mut shutdown = self.shutdown.subscribe();
+        let in_flight = Arc::new(Semaphore::new(CLICK_STREAM_MAX_IN_FLIGHT));
+
+        tracing::info!("Click stream opened");
+        shared::record_counter("game_service.click_stream.opened", 1);
+
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    _ = shutdown.wait_for(|stopping| *stopping) => {
+                        tracing::info!("Closing click stream for shutdown");
+                        break;
+                    }
+                    message = inbound.message() => message,
+                };
 
-        // Get user to retrieve username for batch accumulation
-        let user = match self.user_service.get_user_by_id(&user_id).await {
-            Ok(user) => user,
-            Err(_) => {
-                return Err(Status::not_found("User not found"));
+                match message {
+                    Ok(Some(request)) => {
+                        shared::record_counter("game_service.click_stream.batches", 1);
+
+                        // Batches from different users are independent, so don't let a slow
+                        // one hold up the rest of the stream.
+                        let Ok(permit) = in_flight.clone().acquire_owned().await else {
+                            break;
+                        };
+                        let server = server.clone();
+                        let mut ack_tx = ack_tx.clone();
+                        tokio::spawn(async move {
+                            let _permit = permit;
+                            let ack = server.handle_stream_click(request).await;
+                            if ack_tx.send(Ok(ack)).await.is_err() {
+                                tracing::debug!("Click stream closed before ack was sent");
+                            }
+                        });
+                    }
+                    Ok(None) => break,
+                    Err(status) => {
+                        tracing::warn!(error = %status, "Click stream receive error");
+                        break;
+                    }
+                }
             }
-        };
 
-        match self.click_service.process_click(&user_id, user.username.as_str(), &session_id, click_count).await {
-            Ok(click_result) => {
-                let current_rank = 0;
+            tracing::info!("Click stream closed");
+            shared::record_counter("game_service.click_stream.closed", 1);
+        });
 
-                let response = ProcessClickResponse {
-                    new_total: click_result.total_clicks,
-                    current_rank,
-                    rate_limited: false,
-                    message: "Click processed".to_string(),
-                    success: true,
-                    session_clicks: 0, // Deprecated - no longer tracked
-                };
-                Ok(Response::new(response))
-            }
-            Err(shared::ServiceError::RateLimitExceeded) => {
-                let response = ProcessClickResponse {
-                    new_total: 0,
-                    current_rank: 0,
-                    rate_limited: true,
-                    message: "Rate limit exceeded".to_string(),
-                    success: false,
-                    session_clicks: 0,
-                };
-                Ok(Response::new(response))
-            }
-            Err(e) => {
-                tracing::error!(error = %e, "Failed to process click");
-                Err(e.into())
-            }
-        }
+        Ok(Response::new(Box::pin(ack_rx)))
     }
 
     async fn start_session(
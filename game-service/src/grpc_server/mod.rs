@@ -1,4 +1,6 @@
 
 pub mod game_server;
+pub mod admin_server;
 
 pub use game_server::GameServerImpl;
+pub use admin_server::AdminServerImpl;
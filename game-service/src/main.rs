@@ -5,17 +5,24 @@ use tonic::transport::Server;
 use sqlx::postgres::PgPoolOptions;
 use redis::Client as RedisClient;
 
+use shared::proto::admin_service_server::AdminServiceServer;
 use shared::proto::game_service_server::GameServiceServer;
 use shared::config::BatchConfig;
+use shared::{AdminAuth, AntiCheatConfig, Shard, ShardMap};
 use game_service::{
-    domain::RateLimiter,
-    repository::{UserRepository, ClickRepository, SessionRepository},
-    service::{UserService, ClickService, SessionService, RedisClickAccumulator},
-    grpc_server::GameServerImpl,
+    domain::{CheatDetector, ClickValidator, RateLimiter},
+    repository::{AdminRepository, CheatRepository, ReferralRepository, UserRepository, SessionRepository},
+    service::{shard_slot, AntiCheatService, ModerationService, ReferralService, UserService, ClickService, SessionService, RedisClickAccumulator},
+    grpc_server::{AdminServerImpl, GameServerImpl},
     stream::ClickEventPublisher,
 };
 use std::sync::Arc;
 
+const CLICK_FLUSH_RETENTION_HOURS: i64 = 24;
+const FINAL_FLUSH_ATTEMPTS: usize = 3;
+/// Unpublished referral bonuses republished per cleanup tick.
+const REFERRAL_BACKFILL_BATCH: i64 = 100;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
@@ -50,27 +57,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .expect("Invalid CLICK_RATE_LIMIT");
 
+    let click_burst_secs: u32 = std::env::var("CLICK_BURST_SECS")
+        .unwrap_or_else(|_| "2".to_string())
+        .parse()
+        .expect("Invalid CLICK_BURST_SECS");
+
     let session_timeout: i64 = std::env::var("SESSION_TIMEOUT_SECS")
         .unwrap_or_else(|_| "60".to_string())
         .parse()
         .expect("Invalid SESSION_TIMEOUT_SECS");
 
+    let referral_bonus_clicks: i64 = std::env::var("REFERRAL_BONUS_CLICKS")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse()
+        .expect("Invalid REFERRAL_BONUS_CLICKS");
+
     let batch_config = BatchConfig::from_env()?;
+    let anti_cheat_config = AntiCheatConfig::from_env()?;
+
+    let admin_token = AdminAuth::token_from_env();
 
     let instance_id = std::env::var("INSTANCE_ID")
         .unwrap_or_else(|_| "game-1".to_string());
 
-    let shard_id: usize = instance_id
-        .split('-')
-        .nth(1)
-        .and_then(|s| s.parse::<usize>().ok())
-        .map(|n| n - 1)
-        .unwrap_or(0);
+    let shard_id = shard_slot(&instance_id);
 
-    let num_shards: usize = std::env::var("NUM_SHARDS")
-        .unwrap_or_else(|_| "3".to_string())
-        .parse()
-        .expect("Invalid NUM_SHARDS");
+    let shard_map = Arc::new(ShardMap::from_env(Shard {
+        id: instance_id.clone(),
+        url: format!("http://localhost:{}", port),
+    })?);
+
+    if shard_map.index_of(&instance_id).is_none() {
+        return Err(format!("INSTANCE_ID {} is not in SHARD_MAP", instance_id).into());
+    }
 
     tracing::info!(
         database_url = %database_url,
@@ -78,10 +97,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         port = port,
         click_rate_limit = click_rate_limit,
         session_timeout = session_timeout,
+        referral_bonus_clicks = referral_bonus_clicks,
         click_flush_interval_ms = batch_config.click_flush_interval_ms,
         instance_id = %instance_id,
         shard_id = shard_id,
-        num_shards = num_shards,
+        shards = shard_map.len(),
+        anti_cheat = ?anti_cheat_config,
+        admin_service = admin_token.is_some(),
         "Configuration loaded"
     );
 
@@ -118,44 +140,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let redis_conn_rate_limiter = redis_client.get_multiplexed_tokio_connection().await?;
     let redis_conn_publisher = redis_client.get_multiplexed_tokio_connection().await?;
     let redis_conn_accumulator = redis_client.get_multiplexed_tokio_connection().await?;
-    tracing::info!("Connected to Redis successfully (3 multiplexed connections)");
+    let redis_conn_anti_cheat = redis_client.get_multiplexed_tokio_connection().await?;
+    let redis_conn_moderation = redis_client.get_multiplexed_tokio_connection().await?;
+    tracing::info!("Connected to Redis successfully (5 multiplexed connections)");
 
-    let rate_limiter = Arc::new(tokio::sync::Mutex::new(
-        RateLimiter::new(redis_conn_rate_limiter, click_rate_limit)
+    let rate_limiter = RateLimiter::new(redis_conn_rate_limiter, click_rate_limit, click_burst_secs);
+
+    let anti_cheat = Arc::new(AntiCheatService::new(
+        ClickValidator::new(click_rate_limit),
+        CheatDetector::new(anti_cheat_config, click_rate_limit),
+        CheatRepository::new(db_pool.clone()),
+        redis_conn_anti_cheat,
     ));
 
+    match anti_cheat.sync_hidden_users().await {
+        Ok(hidden) => tracing::info!(hidden_users = hidden, "Synced hidden leaderboard users"),
+        Err(e) => tracing::error!(error = %e, "Failed to sync hidden leaderboard users"),
+    }
+
     let event_publisher = ClickEventPublisher::new(redis_conn_publisher);
     tracing::info!("Initialized Redis Streams publisher");
 
-    let user_repo = UserRepository::new(db_pool.clone());
-    let click_repo = ClickRepository::new(db_pool.clone());
-    let session_repo = SessionRepository::new(db_pool.clone());
-
     let batch_accumulator = Arc::new(RedisClickAccumulator::new(
         redis_conn_accumulator,
         UserRepository::new(db_pool.clone()),
-        Some(event_publisher),
+        Some(event_publisher.clone()),
         batch_config.click_flush_interval_ms,
+        batch_config.click_flush_chunk_size,
         shard_id,
-        num_shards,
     ));
 
+    let moderation = Arc::new(ModerationService::new(
+        AdminRepository::new(db_pool.clone()),
+        redis_conn_moderation,
+        event_publisher.clone(),
+        anti_cheat.clone(),
+        batch_accumulator.clone(),
+        shard_map.shards().iter().map(|shard| shard_slot(&shard.id)).collect(),
+    ));
+
+    match moderation.sync_banned_users().await {
+        Ok(banned) => tracing::info!(banned_users = banned, "Synced banned users"),
+        Err(e) => tracing::error!(error = %e, "Failed to sync banned users"),
+    }
+
+    let referral_service = Arc::new(ReferralService::new(
+        ReferralRepository::new(db_pool.clone()),
+        event_publisher,
+        referral_bonus_clicks,
+    ));
+
+    let user_repo = UserRepository::new(db_pool.clone());
+    let session_repo = SessionRepository::new(db_pool.clone());
+
     tracing::info!(
         interval_ms = batch_config.click_flush_interval_ms,
         "Starting Redis-based click batch flusher (distributed)"
     );
-    batch_accumulator.clone().start_background_flusher();
+    let flusher_handle = batch_accumulator.clone().start_background_flusher();
 
     let user_service = UserService::new(user_repo);
     let click_service = ClickService::new(
         UserRepository::new(db_pool.clone()),
         SessionRepository::new(db_pool.clone()),
         rate_limiter,
-        batch_accumulator,
+        moderation.clone(),
+        anti_cheat.clone(),
+        batch_accumulator.clone(),
     );
     let session_service = SessionService::new(session_repo, session_timeout);
 
-    let game_server = GameServerImpl::new(user_service, click_service, session_service);
+    let game_server = GameServerImpl::new(
+        user_service,
+        referral_service.clone(),
+        click_service,
+        session_service,
+        shard_map.clone(),
+        instance_id.clone(),
+    );
+    let shutdown_server = game_server.clone();
 
     let addr: SocketAddr = format!("0.0.0.0:{}", port).parse()?;
 
@@ -165,10 +228,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     let cleanup_pool = db_pool.clone();
-    tokio::spawn(async move {
+    let flush_log_repo = UserRepository::new(db_pool.clone());
+    let cleanup_anti_cheat = anti_cheat.clone();
+    let backfill_referrals = referral_service.clone();
+    let cleanup_handle = tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
         loop {
             interval.tick().await;
+
+            cleanup_anti_cheat.cleanup_old_data();
             match sqlx::query!(
                 "UPDATE sessions
                  SET is_active = false, ended_at = NOW()
@@ -194,17 +262,122 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     );
                 }
             }
+
+            // Flush ids only need to outlive a crashed flush being retried
+            match flush_log_repo.prune_click_flushes(CLICK_FLUSH_RETENTION_HOURS).await {
+                Ok(rows) if rows > 0 => {
+                    tracing::debug!(pruned_flushes = rows, "Pruned old click flush records");
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to prune click flush records");
+                }
+            }
+
+            match backfill_referrals.republish_pending_bonuses(REFERRAL_BACKFILL_BATCH).await {
+                Ok(published) if published > 0 => {
+                    tracing::info!(published = published, "Back-filled referral bonuses");
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to back-fill referral bonuses");
+                }
+            }
         }
     });
 
     tracing::info!("Started session cleanup background task");
 
+    let admin_service = match admin_token {
+        Some(token) => Some(AdminServiceServer::with_interceptor(
+            AdminServerImpl::new(moderation.clone()),
+            AdminAuth::new(token),
+        )),
+        None => {
+            tracing::warn!("ADMIN_TOKEN is not set, admin service disabled");
+            None
+        }
+    };
+
     Server::builder()
         .add_service(GameServiceServer::new(game_server))
-        .serve(addr)
+        .add_optional_service(admin_service)
+        .serve_with_shutdown(addr, async move {
+            shutdown_signal().await;
+            tracing::info!("Shutdown signal received, draining in-flight clicks");
+            shutdown_server.begin_shutdown();
+        })
         .await?;
 
+    tracing::info!("gRPC server stopped, flushing pending clicks");
+
+    // An aborted flush is safe to cut short: its batch stays in flight and the
+    // final flush below picks it up under the same flush id.
+    flusher_handle.abort();
+    cleanup_handle.abort();
+
+    final_flush(&batch_accumulator).await;
+
+    match end_owned_sessions(&SessionRepository::new(db_pool.clone()), &shard_map, &instance_id).await {
+        Ok(ended) => tracing::info!(ended_sessions = ended, "Ended this shard's sessions"),
+        Err(e) => tracing::error!(error = %e, "Failed to end sessions on shutdown"),
+    }
+
     tracing::info!("Server shut down gracefully");
 
     Ok(())
 }
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Flushes until Redis has nothing left for this shard. The first pass may only
+/// finish a batch left in flight, so the pending hash can need a second one.
+async fn final_flush(accumulator: &RedisClickAccumulator) {
+    let mut accumulator = accumulator.clone();
+
+    for _ in 0..FINAL_FLUSH_ATTEMPTS {
+        match accumulator.flush_batch().await {
+            Ok(0) => break,
+            Ok(users) => tracing::info!(users = users, "Flushed pending clicks on shutdown"),
+            Err(e) => tracing::error!(error = %e, "Final click flush failed"),
+        }
+    }
+}
+
+async fn end_owned_sessions(
+    session_repo: &SessionRepository,
+    shard_map: &ShardMap,
+    instance_id: &str,
+) -> shared::Result<u64> {
+    let owned: Vec<_> = session_repo
+        .get_active_session_owners()
+        .await?
+        .into_iter()
+        .filter(|(_, user_id)| shard_map.shard_for(&user_id.to_string()).id == instance_id)
+        .map(|(session_id, _)| session_id)
+        .collect();
+
+    session_repo.end_sessions(&owned).await
+}
@@ -0,0 +1,329 @@
+use chrono::{DateTime, Utc};
+use shared::{Result, ServiceError, UserId, Username};
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+use crate::domain::{AdminAction, CheatAction, ScoreAdjustment};
+use crate::repository::UserTotal;
+
+/// Who made an admin change and why, written to `admin_audit_log` in the same
+/// transaction as the change.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub actor: String,
+    pub reason: String,
+}
+
+/// A user as left by an admin change, for republishing to the leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeratedUser {
+    pub user_id: UserId,
+    pub username: String,
+    pub total_clicks: i64,
+    pub score_reached_at: DateTime<Utc>,
+    pub score_version: i64,
+}
+
+impl ModeratedUser {
+    pub fn total(&self) -> UserTotal {
+        UserTotal {
+            total_clicks: self.total_clicks,
+            score_reached_at: self.score_reached_at,
+            score_version: self.score_version,
+        }
+    }
+}
+
+/// A score adjustment as committed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdjustedScore {
+    pub user: ModeratedUser,
+    /// New total minus the stored one, pending clicks included. Negative when
+    /// clicks were taken away.
+    pub clicks_added: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlaggedUser {
+    pub user_id: UserId,
+    pub username: String,
+    pub total_clicks: i64,
+    pub score: f64,
+    pub signals: Vec<String>,
+    pub action: CheatAction,
+    pub banned: bool,
+    pub first_detected_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct AdminRepository {
+    pool: PgPool,
+}
+
+impl AdminRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ban_user(&self, user_id: &UserId, audit: &AuditEntry) -> Result<ModeratedUser> {
+        self.restrict(user_id, "banned", AdminAction::Ban, audit).await
+    }
+
+    pub async fn remove_from_leaderboard(&self, user_id: &UserId, audit: &AuditEntry) -> Result<ModeratedUser> {
+        self.restrict(user_id, "hidden_from_leaderboard", AdminAction::RemoveFromLeaderboard, audit)
+            .await
+    }
+
+    /// Lifts every restriction on the user, including anything the anti-cheat
+    /// decided, since an admin has reviewed them.
+    pub async fn unban_user(&self, user_id: &UserId, audit: &AuditEntry) -> Result<ModeratedUser> {
+        let mut tx = self.pool.begin().await?;
+        let user = lock_user(&mut tx, user_id).await?;
+
+        let restrictions = sqlx::query("DELETE FROM user_restrictions WHERE user_id = $1")
+            .bind(user_id.0)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let detections = sqlx::query("DELETE FROM suspicious_users WHERE user_id = $1")
+            .bind(user_id.0)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let details = format!("cleared {} restriction(s), {} detection(s)", restrictions, detections);
+        insert_audit(&mut tx, AdminAction::Unban, user_id, audit, &details).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// `pending_clicks` are clicks the user made that were not flushed yet. A
+    /// delta counts them like stored clicks; setting a total discards them.
+    pub async fn adjust_score(
+        &self,
+        user_id: &UserId,
+        adjustment: ScoreAdjustment,
+        pending_clicks: i64,
+        audit: &AuditEntry,
+    ) -> Result<AdjustedScore> {
+        let mut tx = self.pool.begin().await?;
+        let mut user = lock_user(&mut tx, user_id).await?;
+        let previous_total = user.total_clicks;
+        let new_total = adjustment.apply(previous_total.saturating_add(pending_clicks));
+
+        let (score_reached_at, score_version): (DateTime<Utc>, i64) = sqlx::query_as(
+            "UPDATE users SET total_clicks = $1, updated_at = NOW() WHERE id = $2 \
+             RETURNING score_reached_at, score_version",
+        )
+        .bind(new_total)
+        .bind(user_id.0)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut details = format!("total_clicks {} -> {} ({:?})", previous_total, new_total, adjustment);
+        if pending_clicks > 0 {
+            details.push_str(&format!(", {} pending click(s)", pending_clicks));
+        }
+        insert_audit(&mut tx, AdminAction::AdjustScore, user_id, audit, &details).await?;
+        tx.commit().await?;
+
+        user.total_clicks = new_total;
+        user.score_reached_at = score_reached_at;
+        user.score_version = score_version;
+        Ok(AdjustedScore {
+            user,
+            clicks_added: new_total - previous_total,
+        })
+    }
+
+    pub async fn rename_user(
+        &self,
+        user_id: &UserId,
+        username: &Username,
+        audit: &AuditEntry,
+    ) -> Result<ModeratedUser> {
+        let mut tx = self.pool.begin().await?;
+        let mut user = lock_user(&mut tx, user_id).await?;
+
+        sqlx::query("UPDATE users SET username = $1, updated_at = NOW() WHERE id = $2")
+            .bind(username.as_str())
+            .bind(user_id.0)
+            .execute(&mut *tx)
+            .await?;
+
+        let details = format!("username {} -> {}", user.username, username.as_str());
+        insert_audit(&mut tx, AdminAction::Rename, user_id, audit, &details).await?;
+        tx.commit().await?;
+
+        user.username = username.as_str().to_string();
+        Ok(user)
+    }
+
+    /// Users the anti-cheat has acted on, most suspicious first, with the total
+    /// matching `action`.
+    pub async fn list_flagged_users(
+        &self,
+        action: Option<CheatAction>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<FlaggedUser>, i64)> {
+        let action = action.map(|action| action.as_str());
+
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                s.user_id,
+                u.username,
+                u.total_clicks,
+                s.score,
+                s.signals,
+                s.action,
+                COALESCE(r.banned, FALSE) AS banned,
+                s.first_detected_at,
+                s.updated_at
+            FROM suspicious_users s
+            JOIN users u ON u.id = s.user_id
+            LEFT JOIN user_restrictions r ON r.user_id = s.user_id
+            WHERE $1::TEXT IS NULL OR s.action = $1
+            ORDER BY s.score DESC, s.updated_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(action)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let users = rows
+            .into_iter()
+            .map(|row| {
+                Ok(FlaggedUser {
+                    user_id: UserId(row.get("user_id")),
+                    username: row.get("username"),
+                    total_clicks: row.get("total_clicks"),
+                    score: row.get("score"),
+                    signals: row.get("signals"),
+                    action: row.get::<String, _>("action").parse()?,
+                    banned: row.get("banned"),
+                    first_detected_at: row.get("first_detected_at"),
+                    updated_at: row.get("updated_at"),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (total,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM suspicious_users WHERE $1::TEXT IS NULL OR action = $1",
+        )
+        .bind(action)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((users, total))
+    }
+
+    /// Telegram ids of users who aren't banned, ascending, after `after`.
+    pub async fn list_telegram_ids(&self, after: i64, limit: i64) -> Result<Vec<i64>> {
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT u.telegram_id
+            FROM users u
+            WHERE u.telegram_id > $1
+            AND NOT EXISTS (SELECT 1 FROM user_restrictions r WHERE r.user_id = u.id AND r.banned)
+            ORDER BY u.telegram_id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(telegram_id,)| telegram_id).collect())
+    }
+
+    pub async fn get_banned_user_ids(&self) -> Result<Vec<UserId>> {
+        let rows: Vec<(uuid::Uuid,)> = sqlx::query_as("SELECT user_id FROM user_restrictions WHERE banned")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|(user_id,)| UserId(user_id)).collect())
+    }
+
+    /// Sets one of the `user_restrictions` flags. `flag` is a column name, never
+    /// caller input.
+    async fn restrict(
+        &self,
+        user_id: &UserId,
+        flag: &'static str,
+        action: AdminAction,
+        audit: &AuditEntry,
+    ) -> Result<ModeratedUser> {
+        let mut tx = self.pool.begin().await?;
+        let user = lock_user(&mut tx, user_id).await?;
+
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO user_restrictions (user_id, {flag}, reason, updated_at)
+            VALUES ($1, TRUE, $2, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                {flag} = TRUE,
+                reason = EXCLUDED.reason,
+                updated_at = NOW()
+            "#,
+            flag = flag,
+        ))
+        .bind(user_id.0)
+        .bind(&audit.reason)
+        .execute(&mut *tx)
+        .await?;
+
+        insert_audit(&mut tx, action, user_id, audit, "").await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+}
+
+async fn lock_user(tx: &mut Transaction<'_, Postgres>, user_id: &UserId) -> Result<ModeratedUser> {
+    let row = sqlx::query(
+        "SELECT username, total_clicks, score_reached_at, score_version FROM users WHERE id = $1 FOR UPDATE",
+    )
+        .bind(user_id.0)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| ServiceError::UserNotFound(user_id.to_string()))?;
+
+    Ok(ModeratedUser {
+        user_id: *user_id,
+        username: row.get("username"),
+        total_clicks: row.get("total_clicks"),
+        score_reached_at: row.get("score_reached_at"),
+        score_version: row.get("score_version"),
+    })
+}
+
+async fn insert_audit(
+    tx: &mut Transaction<'_, Postgres>,
+    action: AdminAction,
+    user_id: &UserId,
+    audit: &AuditEntry,
+    details: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO admin_audit_log (actor, action, user_id, reason, details)
+        VALUES ($1, $2, $3, $4, NULLIF($5, ''))
+        "#,
+    )
+    .bind(&audit.actor)
+    .bind(action.as_str())
+    .bind(user_id.0)
+    .bind(&audit.reason)
+    .bind(details)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
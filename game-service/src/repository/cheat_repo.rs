@@ -0,0 +1,73 @@
+use crate::domain::{CheatAction, SuspicionReport};
+use shared::{Result, UserId};
+use sqlx::PgPool;
+
+/// Severity order used to keep a stored action from being downgraded.
+const ACTION_ORDER: &str = "ARRAY['flag', 'shadow_ban', 'freeze']";
+
+pub struct CheatRepository {
+    pool: PgPool,
+}
+
+impl CheatRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Saves the latest report for a user and returns the action now in force.
+    /// Score and signals are replaced, but the action only ever escalates, so a
+    /// calmer stretch of clicking does not lift a shadow-ban or freeze.
+    pub async fn record_detection(
+        &self,
+        user_id: &UserId,
+        report: &SuspicionReport,
+        action: CheatAction,
+    ) -> Result<CheatAction> {
+        let (stored,): (String,) = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO suspicious_users (user_id, score, signals, action, first_detected_at, updated_at)
+            VALUES ($1, $2, $3, $4, NOW(), NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                score = EXCLUDED.score,
+                signals = EXCLUDED.signals,
+                action = CASE
+                    WHEN array_position({order}, EXCLUDED.action::text)
+                        > array_position({order}, suspicious_users.action::text)
+                    THEN EXCLUDED.action
+                    ELSE suspicious_users.action
+                END,
+                updated_at = NOW()
+            RETURNING action
+            "#,
+            order = ACTION_ORDER,
+        ))
+        .bind(user_id.0)
+        .bind(report.score)
+        .bind(report.signal_names())
+        .bind(action.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        stored.parse()
+    }
+
+    pub async fn get_action(&self, user_id: &UserId) -> Result<Option<CheatAction>> {
+        let action: Option<(String,)> = sqlx::query_as(
+            "SELECT action FROM suspicious_users WHERE user_id = $1",
+        )
+        .bind(user_id.0)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        action.map(|(action,)| action.parse()).transpose()
+    }
+
+    /// Users kept off the leaderboard, by the anti-cheat or by an admin.
+    pub async fn get_hidden_user_ids(&self) -> Result<Vec<UserId>> {
+        let rows: Vec<(uuid::Uuid,)> = sqlx::query_as("SELECT user_id FROM hidden_users")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|(user_id,)| UserId(user_id)).collect())
+    }
+}
@@ -1,4 +1,4 @@
-use shared::{ClickEvent, Result, ServiceError, SessionId, UserId};
+use shared::{ClickEvent, Result, SessionId, UserId};
 use sqlx::{PgPool, Row};
 
 
@@ -99,8 +99,6 @@ impl ClickRepository {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[tokio::test]
     #[ignore] // Requires database
     async fn test_record_click() {
@@ -1,9 +1,14 @@
 
-
 pub mod user_repo;
 pub mod click_repo;
 pub mod session_repo;
+pub mod cheat_repo;
+pub mod admin_repo;
+pub mod referral_repo;
 
-pub use user_repo::UserRepository;
+pub use user_repo::{UserRepository, UserTotal};
 pub use click_repo::ClickRepository;
 pub use session_repo::SessionRepository;
+pub use cheat_repo::CheatRepository;
+pub use admin_repo::{AdjustedScore, AdminRepository, AuditEntry, FlaggedUser, ModeratedUser};
+pub use referral_repo::{ReferralRepository, UnpublishedBonus};
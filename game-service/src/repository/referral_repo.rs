@@ -0,0 +1,163 @@
+use shared::{Result, User, UserId};
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+use crate::repository::user_repo::insert_user;
+use crate::repository::ModeratedUser;
+
+/// A referral bonus that was committed but never made it onto the click stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpublishedBonus {
+    pub invitee: UserId,
+    pub bonus_clicks: i64,
+    /// The inviter as they are now, not as the bonus left them.
+    pub inviter: ModeratedUser,
+}
+
+pub struct ReferralRepository {
+    pool: PgPool,
+}
+
+impl ReferralRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Records that the owner of `referral_code` invited `invitee` and credits them
+    /// `bonus_clicks`, in one transaction. Returns the inviter as left by the bonus,
+    /// or `None` if the code is unknown, is the invitee's own, or the invitee was
+    /// already referred.
+    pub async fn record_referral(
+        &self,
+        invitee: &UserId,
+        referral_code: &str,
+        bonus_clicks: i64,
+    ) -> Result<Option<ModeratedUser>> {
+        let mut tx = self.pool.begin().await?;
+        let inviter = credit_referral(&mut tx, invitee, referral_code, bonus_clicks).await?;
+        tx.commit().await?;
+
+        Ok(inviter)
+    }
+
+    /// Creates a user and records their referral in the same transaction, so a
+    /// registration never commits without the invite it came with. The inviter is
+    /// `None` when the code earns no bonus; the user is created either way.
+    pub async fn create_referred_user(
+        &self,
+        telegram_id: i64,
+        username: &str,
+        referral_code: &str,
+        bonus_clicks: i64,
+    ) -> Result<(User, Option<ModeratedUser>)> {
+        let mut tx = self.pool.begin().await?;
+        let user = insert_user(&mut *tx, telegram_id, username).await?;
+        let inviter = credit_referral(&mut tx, &user.id, referral_code, bonus_clicks).await?;
+        tx.commit().await?;
+
+        Ok((user, inviter))
+    }
+
+    pub async fn mark_published(&self, invitee: &UserId) -> Result<()> {
+        sqlx::query("UPDATE referrals SET published_at = NOW() WHERE invitee_id = $1")
+            .bind(invitee.0)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Takes up to `limit` bonuses due for republishing and leases them for
+    /// `lease_secs`, so other instances skip them while they are published. A
+    /// lease that runs out before `mark_published` makes the bonus due again.
+    pub async fn claim_unpublished(&self, lease_secs: i64, limit: i64) -> Result<Vec<UnpublishedBonus>> {
+        let rows = sqlx::query(
+            r#"
+            WITH claimed AS (
+                UPDATE referrals
+                SET publish_after = NOW() + make_interval(secs => $1)
+                WHERE invitee_id IN (
+                    SELECT invitee_id
+                    FROM referrals
+                    WHERE published_at IS NULL AND publish_after <= NOW()
+                    ORDER BY publish_after
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING invitee_id, inviter_id, bonus_clicks
+            )
+            SELECT c.invitee_id, c.bonus_clicks, u.id, u.username, u.total_clicks,
+                   u.score_reached_at, u.score_version
+            FROM claimed c
+            JOIN users u ON u.id = c.inviter_id
+            "#,
+        )
+        .bind(lease_secs as f64)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| UnpublishedBonus {
+                invitee: UserId(row.get("invitee_id")),
+                bonus_clicks: row.get("bonus_clicks"),
+                inviter: ModeratedUser {
+                    user_id: UserId(row.get("id")),
+                    username: row.get("username"),
+                    total_clicks: row.get("total_clicks"),
+                    score_reached_at: row.get("score_reached_at"),
+                    score_version: row.get("score_version"),
+                },
+            })
+            .collect())
+    }
+}
+
+async fn credit_referral(
+    tx: &mut Transaction<'_, Postgres>,
+    invitee: &UserId,
+    referral_code: &str,
+    bonus_clicks: i64,
+) -> Result<Option<ModeratedUser>> {
+    let recorded = sqlx::query(
+        r#"
+        INSERT INTO referrals (invitee_id, inviter_id, bonus_clicks)
+        SELECT $1, id, $3
+        FROM users
+        WHERE referral_code = $2 AND id <> $1
+        ON CONFLICT (invitee_id) DO NOTHING
+        RETURNING inviter_id
+        "#,
+    )
+    .bind(invitee.0)
+    .bind(referral_code)
+    .bind(bonus_clicks)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some(recorded) = recorded else {
+        return Ok(None);
+    };
+    let inviter = UserId(recorded.get("inviter_id"));
+
+    let row = sqlx::query(
+        r#"
+        UPDATE users
+        SET total_clicks = total_clicks + $1, updated_at = NOW()
+        WHERE id = $2
+        RETURNING username, total_clicks, score_reached_at, score_version
+        "#,
+    )
+    .bind(bonus_clicks)
+    .bind(inviter.0)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(Some(ModeratedUser {
+        user_id: inviter,
+        username: row.get("username"),
+        total_clicks: row.get("total_clicks"),
+        score_reached_at: row.get("score_reached_at"),
+        score_version: row.get("score_version"),
+    }))
+}
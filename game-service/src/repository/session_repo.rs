@@ -1,6 +1,5 @@
 use shared::{Result, ServiceError, Session, SessionId, SessionStats, UserId};
 use sqlx::{PgPool, Row};
-use chrono::{DateTime, Utc};
 
 
 pub struct SessionRepository {
@@ -251,6 +250,35 @@ impl SessionRepository {
         Ok(sessions)
     }
 
+    /// Ids of every active session with the user it belongs to.
+    pub async fn get_active_session_owners(&self) -> Result<Vec<(SessionId, UserId)>> {
+        let rows = sqlx::query("SELECT id, user_id FROM sessions WHERE is_active = TRUE")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (SessionId(row.get("id")), UserId(row.get("user_id"))))
+            .collect())
+    }
+
+    pub async fn end_sessions(&self, session_ids: &[SessionId]) -> Result<u64> {
+        let ids: Vec<uuid::Uuid> = session_ids.iter().map(|id| id.0).collect();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET is_active = FALSE, ended_at = NOW()
+            WHERE id = ANY($1) AND is_active = TRUE
+            "#,
+        )
+        .bind(&ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn cleanup_expired_sessions(&self, timeout_secs: i64) -> Result<u64> {
         let result = sqlx::query(
             r#"
@@ -270,8 +298,6 @@ impl SessionRepository {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[tokio::test]
     #[ignore] // Requires database
     async fn test_session_lifecycle() {
@@ -1,5 +1,16 @@
+use chrono::{DateTime, Utc};
 use shared::{Result, ServiceError, User, UserId, Username};
-use sqlx::{PgPool, Row};
+use sqlx::{PgExecutor, PgPool, Row};
+
+/// A user's total as left by a committed change, with the time Postgres
+/// stamped it as reached, which breaks ties between equal totals, and its
+/// version, which orders totals by commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserTotal {
+    pub total_clicks: i64,
+    pub score_reached_at: DateTime<Utc>,
+    pub score_version: i64,
+}
 
 #[derive(Clone)]
 pub struct UserRepository {
@@ -14,36 +25,9 @@ impl UserRepository {
 
 
     pub async fn create_user(&self, telegram_id: i64, username: &str) -> Result<User> {
-        let row = sqlx::query(
-            r#"
-            INSERT INTO users (telegram_id, username, total_clicks)
-            VALUES ($1, $2, 0)
-            RETURNING id, telegram_id, username, total_clicks, created_at, updated_at
-            "#,
-        )
-        .bind(telegram_id)
-        .bind(username)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| {
-            if e.to_string().contains("duplicate key") {
-                ServiceError::UserAlreadyExists(telegram_id.to_string())
-            } else {
-                ServiceError::Database(e.to_string())
-            }
-        })?;
-
-        Ok(User {
-            id: UserId(row.get("id")),
-            telegram_id: row.get("telegram_id"),
-            username: Username::new(row.get::<String, _>("username"))?,
-            total_clicks: row.get("total_clicks"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        })
+        insert_user(&self.pool, telegram_id, username).await
     }
 
-
     
     pub async fn get_by_telegram_id(&self, telegram_id: i64) -> Result<User> {
         let row = sqlx::query(
@@ -135,46 +119,15 @@ impl UserRepository {
     pub async fn bulk_increment_clicks(
         &self,
         batches: &std::collections::HashMap<String, crate::service::UserClickBatch>,
-    ) -> Result<std::collections::HashMap<String, i64>> {
+    ) -> Result<std::collections::HashMap<String, UserTotal>> {
         use std::collections::HashMap;
 
         if batches.is_empty() {
             return Ok(HashMap::new());
         }
 
-    
-        let mut sorted_batches: Vec<_> = batches.iter().collect();
-        sorted_batches.sort_by_key(|(user_id_str, _)| *user_id_str);
-
-      
-        let mut query = String::from(
-            "UPDATE users AS u \
-             SET total_clicks = total_clicks + v.increment::bigint, updated_at = NOW() \
-             FROM (VALUES "
-        );
-
-        let mut bind_values: Vec<(uuid::Uuid, i64)> = Vec::new();
-        let mut first = true;
-
-        for (user_id_str, batch) in sorted_batches.iter() {
-            let user_id = uuid::Uuid::parse_str(user_id_str).map_err(|e| {
-                ServiceError::Internal(format!("Invalid user_id UUID: {}", e))
-            })?;
-
-            if !first {
-                query.push_str(", ");
-            }
-            first = false;
-
-            let param_idx = bind_values.len();
-            query.push_str(&format!("(${}, ${})", param_idx * 2 + 1, param_idx * 2 + 2));
-
-            bind_values.push((user_id, batch.accumulated_clicks as i64));
-        }
-
-        query.push_str(") AS v(user_id, increment) WHERE u.id = v.user_id RETURNING u.id, u.total_clicks");
+        let (query, bind_values) = build_bulk_increment(batches)?;
 
-    
         let mut query_builder = sqlx::query(&query);
         for (user_id, increment) in bind_values.iter() {
             query_builder = query_builder.bind(user_id).bind(increment);
@@ -185,12 +138,7 @@ impl UserRepository {
             ServiceError::Database(e.to_string())
         })?;
 
-        let mut result_map = HashMap::new();
-        for row in rows {
-            let user_id: uuid::Uuid = row.get("id");
-            let total_clicks: i64 = row.get("total_clicks");
-            result_map.insert(user_id.to_string(), total_clicks);
-        }
+        let result_map = totals_from_rows(rows);
 
         tracing::debug!(
             users_updated = result_map.len(),
@@ -201,6 +149,79 @@ impl UserRepository {
         Ok(result_map)
     }
 
+    /// Applies a flushed click batch exactly once. The flush id is recorded in the
+    /// same transaction as the increments; if it is already present the batch was
+    /// committed before and `None` is returned without touching any totals.
+    pub async fn apply_click_flush(
+        &self,
+        flush_id: uuid::Uuid,
+        shard_id: usize,
+        batches: &std::collections::HashMap<String, crate::service::UserClickBatch>,
+    ) -> Result<Option<std::collections::HashMap<String, UserTotal>>> {
+        let db_err = |e: sqlx::Error| {
+            tracing::error!(error = %e, flush_id = %flush_id, "Click flush failed");
+            ServiceError::Database(e.to_string())
+        };
+
+        let total_clicks: i64 = batches.values().map(|b| b.accumulated_clicks as i64).sum();
+
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+
+        let recorded = sqlx::query(
+            "INSERT INTO click_flushes (flush_id, shard_id, user_count, total_clicks) \
+             VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (flush_id) DO NOTHING",
+        )
+        .bind(flush_id)
+        .bind(shard_id as i32)
+        .bind(batches.len() as i32)
+        .bind(total_clicks)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?
+        .rows_affected();
+
+        if recorded == 0 {
+            tracing::warn!(flush_id = %flush_id, "Click flush already applied, skipping");
+            return Ok(None);
+        }
+
+        let totals = if batches.is_empty() {
+            std::collections::HashMap::new()
+        } else {
+            let (query, bind_values) = build_bulk_increment(batches)?;
+
+            let mut query_builder = sqlx::query(&query);
+            for (user_id, increment) in bind_values.iter() {
+                query_builder = query_builder.bind(user_id).bind(increment);
+            }
+
+            totals_from_rows(query_builder.fetch_all(&mut *tx).await.map_err(db_err)?)
+        };
+
+        tx.commit().await.map_err(db_err)?;
+
+        tracing::debug!(
+            flush_id = %flush_id,
+            users_updated = totals.len(),
+            batches_submitted = batches.len(),
+            "Click flush committed"
+        );
+
+        Ok(Some(totals))
+    }
+
+    pub async fn prune_click_flushes(&self, retention_hours: i64) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM click_flushes WHERE applied_at < NOW() - make_interval(hours => $1)",
+        )
+        .bind(retention_hours as i32)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
 
     pub async fn count_total_users(&self) -> Result<i64> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM users")
@@ -211,10 +232,89 @@ impl UserRepository {
     }
 }
 
+/// Builds one `UPDATE ... FROM (VALUES ...)` for all batches. Rows are sorted by
+/// user id so concurrent flushes lock users in the same order.
+fn build_bulk_increment(
+    batches: &std::collections::HashMap<String, crate::service::UserClickBatch>,
+) -> Result<(String, Vec<(uuid::Uuid, i64)>)> {
+    let mut sorted_batches: Vec<_> = batches.iter().collect();
+    sorted_batches.sort_by_key(|(user_id_str, _)| *user_id_str);
+
+    let mut query = String::from(
+        "UPDATE users AS u \
+         SET total_clicks = total_clicks + v.increment::bigint, updated_at = NOW() \
+         FROM (VALUES "
+    );
+
+    let mut bind_values: Vec<(uuid::Uuid, i64)> = Vec::new();
+
+    for (user_id_str, batch) in sorted_batches.iter() {
+        let user_id = uuid::Uuid::parse_str(user_id_str).map_err(|e| {
+            ServiceError::Internal(format!("Invalid user_id UUID: {}", e))
+        })?;
+
+        if !bind_values.is_empty() {
+            query.push_str(", ");
+        }
+
+        let param_idx = bind_values.len();
+        query.push_str(&format!("(${}, ${})", param_idx * 2 + 1, param_idx * 2 + 2));
+
+        bind_values.push((user_id, batch.accumulated_clicks as i64));
+    }
+
+    query.push_str(") AS v(user_id, increment) WHERE u.id = v.user_id RETURNING u.id, u.total_clicks, u.score_reached_at, u.score_version");
+
+    Ok((query, bind_values))
+}
+
+fn totals_from_rows(rows: Vec<sqlx::postgres::PgRow>) -> std::collections::HashMap<String, UserTotal> {
+    rows.into_iter()
+        .map(|row| {
+            let user_id: uuid::Uuid = row.get("id");
+            let total = UserTotal {
+                total_clicks: row.get("total_clicks"),
+                score_reached_at: row.get("score_reached_at"),
+                score_version: row.get("score_version"),
+            };
+            (user_id.to_string(), total)
+        })
+        .collect()
+}
+
+/// Inserts a new user, on the pool or inside a caller's transaction.
+pub(crate) async fn insert_user(executor: impl PgExecutor<'_>, telegram_id: i64, username: &str) -> Result<User> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO users (telegram_id, username, total_clicks)
+        VALUES ($1, $2, 0)
+        RETURNING id, telegram_id, username, total_clicks, created_at, updated_at
+        "#,
+    )
+    .bind(telegram_id)
+    .bind(username)
+    .fetch_one(executor)
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("duplicate key") {
+            ServiceError::UserAlreadyExists(telegram_id.to_string())
+        } else {
+            ServiceError::Database(e.to_string())
+        }
+    })?;
+
+    Ok(User {
+        id: UserId(row.get("id")),
+        telegram_id: row.get("telegram_id"),
+        username: Username::new(row.get::<String, _>("username"))?,
+        total_clicks: row.get("total_clicks"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::*;
-
 
     #[tokio::test]
     #[ignore]
@@ -0,0 +1,197 @@
+use chrono::Utc;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use shared::events::HIDDEN_USERS_KEY;
+use shared::{Result, ServiceError, UserId};
+use crate::domain::{BatchSample, CheatAction, CheatDetector, ClickValidator, ClientBatchTimes};
+use crate::repository::CheatRepository;
+
+/// How long a user's stored action is trusted before Postgres is read again.
+const ACTION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Marks a user whose action an admin lifted, for as long as any instance may
+/// still have the old one cached.
+const ACTION_CLEARED_PREFIX: &str = "anti_cheat:cleared:";
+
+struct CachedAction {
+    action: Option<CheatAction>,
+    loaded_at: Instant,
+}
+
+/// Scores every click batch and enforces the result. Postgres is only written
+/// when a user escalates, and the action in force is cached between reads.
+pub struct AntiCheatService {
+    validator: ClickValidator,
+    detector: CheatDetector,
+    cheat_repo: CheatRepository,
+    redis: MultiplexedConnection,
+    actions: RwLock<HashMap<UserId, CachedAction>>,
+}
+
+impl AntiCheatService {
+    pub fn new(
+        validator: ClickValidator,
+        detector: CheatDetector,
+        cheat_repo: CheatRepository,
+        redis: MultiplexedConnection,
+    ) -> Self {
+        Self {
+            validator,
+            detector,
+            cheat_repo,
+            redis,
+            actions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Adds the batch to the user's history, rescores it and returns the action
+    /// now in force, escalating it if the new score calls for something harsher.
+    #[tracing::instrument(skip(self), fields(user_id = %user_id))]
+    pub async fn inspect_batch(
+        &self,
+        user_id: &UserId,
+        client_times: Option<ClientBatchTimes>,
+        click_count: u32,
+    ) -> Result<Option<CheatAction>> {
+        let history = self.validator.record_batch(
+            user_id,
+            BatchSample {
+                received_at: Utc::now(),
+                client_times,
+                click_count,
+            },
+        )?;
+
+        let report = self.detector.analyze(&history);
+        let current = self.current_action(user_id).await?;
+
+        let detected = match self.detector.action_for(report.score) {
+            Some(detected) if current.is_none_or(|current| detected > current) => detected,
+            _ => return Ok(current),
+        };
+
+        let action = self.cheat_repo.record_detection(user_id, &report, detected).await?;
+
+        shared::record_counter(
+            match action {
+                CheatAction::Flag => "game_service.anti_cheat.flagged",
+                CheatAction::ShadowBan => "game_service.anti_cheat.shadow_banned",
+                CheatAction::Freeze => "game_service.anti_cheat.frozen",
+            },
+            1,
+        );
+        tracing::warn!(
+            score = report.score,
+            signals = ?report.signal_names(),
+            action = %action,
+            "Suspicious clicking detected"
+        );
+
+        if action.hides_from_leaderboard() {
+            self.hide_from_leaderboard(&[*user_id]).await?;
+        }
+
+        self.cache_action(user_id, Some(action));
+        Ok(Some(action))
+    }
+
+    /// Forgets the user's cached action on every instance, after an admin lifted
+    /// it. Other instances see the marker on the user's next batch and reload.
+    pub async fn clear_action(&self, user_id: &UserId) -> Result<()> {
+        if let Ok(mut actions) = self.actions.write() {
+            actions.remove(user_id);
+        }
+
+        self.redis
+            .clone()
+            .set_ex::<_, _, ()>(cleared_key(user_id), 1, ACTION_CACHE_TTL.as_secs())
+            .await
+            .map_err(|e| ServiceError::Redis(e.to_string()))
+    }
+
+    /// Re-adds every user the anti-cheat or an admin has hidden to the hidden set,
+    /// in case Redis lost it.
+    pub async fn sync_hidden_users(&self) -> Result<usize> {
+        let user_ids = self.cheat_repo.get_hidden_user_ids().await?;
+        if !user_ids.is_empty() {
+            self.hide_from_leaderboard(&user_ids).await?;
+        }
+
+        Ok(user_ids.len())
+    }
+
+    pub fn cleanup_old_data(&self) {
+        self.validator.cleanup_old_data();
+
+        if let Ok(mut actions) = self.actions.write() {
+            actions.retain(|_, cached| cached.loaded_at.elapsed() < ACTION_CACHE_TTL);
+        }
+    }
+
+    async fn current_action(&self, user_id: &UserId) -> Result<Option<CheatAction>> {
+        let cached = {
+            let actions = self
+                .actions
+                .read()
+                .map_err(|e| ServiceError::Internal(format!("Lock error: {}", e)))?;
+
+            actions
+                .get(user_id)
+                .filter(|cached| cached.loaded_at.elapsed() < ACTION_CACHE_TTL)
+                .map(|cached| cached.action)
+        };
+
+        match cached {
+            Some(None) => return Ok(None),
+            // Only a cached action can be stale after an unban, so clean users
+            // cost no extra round trip
+            Some(Some(action)) => {
+                let cleared: bool = self
+                    .redis
+                    .clone()
+                    .exists(cleared_key(user_id))
+                    .await
+                    .map_err(|e| ServiceError::Redis(e.to_string()))?;
+
+                if !cleared {
+                    return Ok(Some(action));
+                }
+            }
+            None => {}
+        }
+
+        let action = self.cheat_repo.get_action(user_id).await?;
+        self.cache_action(user_id, action);
+        Ok(action)
+    }
+
+    fn cache_action(&self, user_id: &UserId, action: Option<CheatAction>) {
+        if let Ok(mut actions) = self.actions.write() {
+            actions.insert(
+                *user_id,
+                CachedAction {
+                    action,
+                    loaded_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    async fn hide_from_leaderboard(&self, user_ids: &[UserId]) -> Result<()> {
+        let members: Vec<String> = user_ids.iter().map(UserId::to_string).collect();
+
+        self.redis
+            .clone()
+            .sadd::<_, _, ()>(HIDDEN_USERS_KEY, members)
+            .await
+            .map_err(|e| ServiceError::Redis(e.to_string()))
+    }
+}
+
+fn cleared_key(user_id: &UserId) -> String {
+    format!("{}{}", ACTION_CLEARED_PREFIX, user_id)
+}
@@ -9,7 +9,7 @@ use tracing::{debug, error, info, warn};
 use futures::future::join_all;
 
 use shared::{Result, ServiceError};
-use crate::repository::UserRepository;
+use crate::repository::{UserRepository, UserTotal};
 use crate::stream::ClickEventPublisher;
 
 pub struct ClickBatchAccumulator {
@@ -25,6 +25,7 @@ pub struct ClickBatchAccumulator {
 #[derive(Debug, Clone)]
 pub struct UserClickBatch {
     pub username: String,
+    pub session_id: Option<String>,
     pub accumulated_clicks: u32,
     pub last_click_time: chrono::DateTime<Utc>,
 }
@@ -59,6 +60,7 @@ impl ClickBatchAccumulator {
             })
             .or_insert(UserClickBatch {
                 username: username.to_string(),
+                session_id: None,
                 accumulated_clicks: 1,
                 last_click_time: Utc::now(),
             });
@@ -98,7 +100,7 @@ impl ClickBatchAccumulator {
             info!(
                 batch_size = batch_size,
                 chunk_size = MAX_CHUNK_SIZE,
-                chunks = (batch_size + MAX_CHUNK_SIZE - 1) / MAX_CHUNK_SIZE,
+                chunks = batch_size.div_ceil(MAX_CHUNK_SIZE),
                 "Processing large batch with concurrent chunks"
             );
 
@@ -171,7 +173,7 @@ impl ClickBatchAccumulator {
     async fn bulk_increment_clicks(
         &self,
         batches: &HashMap<String, UserClickBatch>,
-    ) -> Result<HashMap<String, i64>> {
+    ) -> Result<HashMap<String, UserTotal>> {
         self.user_repo.bulk_increment_clicks(batches).await
     }
 
@@ -180,24 +182,30 @@ impl ClickBatchAccumulator {
         &self,
         publisher: &ClickEventPublisher,
         batches: &HashMap<String, UserClickBatch>,
-        updated_totals: &HashMap<String, i64>,
+        updated_totals: &HashMap<String, UserTotal>,
     ) {
         for (user_id, batch) in batches.iter() {
-            let total_clicks = updated_totals.get(user_id).copied().unwrap_or_else(|| {
+            let total = updated_totals.get(user_id).copied().unwrap_or_else(|| {
                 warn!(
                     user_id = %user_id,
                     "User not found in updated totals, using batch count as fallback"
                 );
-                batch.accumulated_clicks as i64
+                UserTotal {
+                    total_clicks: batch.accumulated_clicks as i64,
+                    score_reached_at: Utc::now(),
+                    score_version: 0,
+                }
             });
 
             let publisher_clone = publisher.clone();
             let user_id = user_id.clone();
             let username = batch.username.clone();
+            let session_id = batch.session_id.clone();
+            let delta = batch.accumulated_clicks as i64;
 
             tokio::spawn(async move {
                 if let Err(e) = publisher_clone
-                    .publish_click_event(&user_id, &username, total_clicks)
+                    .publish_click_event(&user_id, &username, total, delta, session_id.as_deref())
                     .await
                 {
                     error!(
@@ -1,7 +1,7 @@
-use shared::{Result, UserId, SessionId};
-use crate::domain::RateLimiter;
+use shared::{Result, ServiceError, UserId, SessionId};
+use crate::domain::{CheatAction, ClientBatchTimes, RateLimiter};
 use crate::repository::{UserRepository, SessionRepository};
-use crate::service::RedisClickAccumulator;
+use crate::service::{AntiCheatService, ModerationService, RedisClickAccumulator};
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
@@ -12,8 +12,11 @@ pub struct ClickResult {
 
 pub struct ClickService {
     user_repo: UserRepository,
+    #[allow(dead_code)]
     session_repo: SessionRepository,
-    rate_limiter: Arc<tokio::sync::Mutex<RateLimiter>>,
+    rate_limiter: RateLimiter,
+    moderation: Arc<ModerationService>,
+    anti_cheat: Arc<AntiCheatService>,
     batch_accumulator: Arc<RedisClickAccumulator>,
 }
 
@@ -22,13 +25,17 @@ impl ClickService {
     pub fn new(
         user_repo: UserRepository,
         session_repo: SessionRepository,
-        rate_limiter: Arc<tokio::sync::Mutex<RateLimiter>>,
+        rate_limiter: RateLimiter,
+        moderation: Arc<ModerationService>,
+        anti_cheat: Arc<AntiCheatService>,
         batch_accumulator: Arc<RedisClickAccumulator>,
     ) -> Self {
         Self {
             user_repo,
             session_repo,
             rate_limiter,
+            moderation,
+            anti_cheat,
             batch_accumulator,
         }
     }
@@ -41,25 +48,37 @@ impl ClickService {
         username: &str,
         session_id: &SessionId,
         click_count: u32,
+        client_times: Option<ClientBatchTimes>,
     ) -> Result<ClickResult> {
         let total_start = std::time::Instant::now();
 
         shared::record_counter("game_service.click.requests", 1);
 
-        let rate_limit_start = std::time::Instant::now();
-        let mut rate_limiter = self.rate_limiter.lock().await;
-        let rate_limit_lock_time = rate_limit_start.elapsed();
-        shared::record_timing("game_service.rate_limit.lock_wait", rate_limit_lock_time.as_secs_f64());
+        if self.moderation.is_banned(user_id).await? {
+            shared::record_counter("game_service.click.banned", 1);
+            return Err(ServiceError::Forbidden("User is banned".to_string()));
+        }
+
+        // Inspected before the rate limit, since rejected bursts are evidence too.
+        // A failed check lets the batch through rather than blocking play.
+        match self.anti_cheat.inspect_batch(user_id, client_times, click_count).await {
+            Ok(Some(CheatAction::Freeze)) => {
+                shared::record_counter("game_service.click.frozen", 1);
+                return Err(ServiceError::Forbidden("Account is frozen".to_string()));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!(error = %e, "Anti-cheat check failed");
+            }
+        }
 
         let rate_check_start = std::time::Instant::now();
-        match rate_limiter.check_rate_limit(user_id, click_count).await {
+        match self.rate_limiter.check_rate_limit(user_id, click_count).await {
             Ok(_) => {
                 let rate_check_time = rate_check_start.elapsed();
                 shared::record_timing("game_service.rate_limit.check", rate_check_time.as_secs_f64());
-                drop(rate_limiter); // Release lock immediately
 
                 tracing::debug!(
-                    lock_ms = rate_limit_lock_time.as_millis(),
                     check_ms = rate_check_time.as_millis(),
                     click_count = click_count,
                     "Rate limit check passed for batch"
@@ -67,10 +86,10 @@ impl ClickService {
             }
             Err(e) => {
                 shared::record_counter("game_service.click.rate_limited", 1);
-                drop(rate_limiter);
                 tracing::warn!(
                     click_count = click_count,
-                    "Rate limit exceeded for batch"
+                    error = %e,
+                    "Rate limit check rejected batch"
                 );
                 return Err(e);
             }
@@ -78,7 +97,7 @@ impl ClickService {
 
         let accumulate_start = std::time::Instant::now();
         let pending_count = self.batch_accumulator
-            .accumulate_click(&user_id.to_string(), username, click_count)
+            .accumulate_click(&user_id.to_string(), username, &session_id.to_string(), click_count)
             .await?;
         let accumulate_time = accumulate_start.elapsed();
         shared::record_timing("game_service.click.accumulate", accumulate_time.as_secs_f64());
@@ -96,7 +115,7 @@ impl ClickService {
 
         tracing::info!(
             total_ms = total_time.as_millis(),
-            rate_limit_ms = (rate_limit_lock_time + rate_check_start.elapsed()).as_millis(),
+            rate_limit_ms = (accumulate_start - rate_check_start).as_millis(),
             accumulate_ms = accumulate_time.as_millis(),
             user_fetch_ms = user_fetch_time.as_millis(),
             pending = pending_count,
@@ -119,8 +138,8 @@ mod tests {
 
     #[test]
     fn test_click_error_types() {
-        let error = ServiceError::RateLimitExceeded;
-        assert!(matches!(error, ServiceError::RateLimitExceeded));
+        let error = ServiceError::RateLimitExceeded { retry_after_ms: 250 };
+        assert!(matches!(error, ServiceError::RateLimitExceeded { retry_after_ms: 250 }));
 
         let user_id = UserId::new();
         let error = ServiceError::UserNotFound(user_id.to_string());
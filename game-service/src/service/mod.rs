@@ -4,9 +4,15 @@ pub mod click_service;
 pub mod session_service;
 pub mod click_batch_accumulator;
 pub mod redis_click_accumulator;
+pub mod anti_cheat_service;
+pub mod moderation_service;
+pub mod referral_service;
 
 pub use user_service::UserService;
 pub use click_service::ClickService;
 pub use session_service::SessionService;
 pub use click_batch_accumulator::{ClickBatchAccumulator, UserClickBatch};
-pub use redis_click_accumulator::RedisClickAccumulator;
+pub use redis_click_accumulator::{shard_slot, RedisClickAccumulator};
+pub use anti_cheat_service::AntiCheatService;
+pub use moderation_service::ModerationService;
+pub use referral_service::ReferralService;
@@ -0,0 +1,205 @@
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::sync::Arc;
+use std::time::Duration;
+
+use shared::events::HIDDEN_USERS_KEY;
+use shared::{Result, ServiceError, UserId, Username};
+use crate::domain::{CheatAction, ScoreAdjustment};
+use crate::repository::{AdminRepository, AuditEntry, FlaggedUser, ModeratedUser};
+use crate::service::{AntiCheatService, RedisClickAccumulator};
+use crate::stream::ClickEventPublisher;
+
+/// Redis set of banned user ids, checked on every click batch so a ban applies
+/// on every shard at once.
+const BANNED_USERS_KEY: &str = "users:banned";
+
+/// How often a score adjustment waits for a flush holding the user's clicks,
+/// which takes well under a flush interval, before giving up.
+const PENDING_CLICKS_ATTEMPTS: u32 = 10;
+const PENDING_CLICKS_RETRY: Duration = Duration::from_millis(200);
+
+/// Admin changes to users. Postgres stays the source of truth; the Redis sets
+/// mirror it for the hot path, and every change is republished on the click
+/// stream so the leaderboard picks it up without waiting for the next click.
+pub struct ModerationService {
+    admin_repo: AdminRepository,
+    redis: MultiplexedConnection,
+    publisher: ClickEventPublisher,
+    anti_cheat: Arc<AntiCheatService>,
+    accumulator: Arc<RedisClickAccumulator>,
+    /// Every shard's pending-click slot, since a user's unflushed clicks can sit
+    /// on a shard that owned them before the shard map changed.
+    shard_slots: Vec<usize>,
+}
+
+impl ModerationService {
+    pub fn new(
+        admin_repo: AdminRepository,
+        redis: MultiplexedConnection,
+        publisher: ClickEventPublisher,
+        anti_cheat: Arc<AntiCheatService>,
+        accumulator: Arc<RedisClickAccumulator>,
+        shard_slots: Vec<usize>,
+    ) -> Self {
+        Self {
+            admin_repo,
+            redis,
+            publisher,
+            anti_cheat,
+            accumulator,
+            shard_slots,
+        }
+    }
+
+    #[tracing::instrument(skip(self, audit), fields(user_id = %user_id, actor = %audit.actor))]
+    pub async fn ban_user(&self, user_id: &UserId, audit: &AuditEntry) -> Result<()> {
+        let user = self.admin_repo.ban_user(user_id, audit).await?;
+
+        let member = user_id.to_string();
+        let mut pipe = redis::pipe();
+        pipe.sadd(BANNED_USERS_KEY, &member).ignore();
+        pipe.sadd(HIDDEN_USERS_KEY, &member).ignore();
+        self.run(pipe).await?;
+
+        tracing::warn!(reason = %audit.reason, "User banned");
+        self.republish(&user, 0).await
+    }
+
+    #[tracing::instrument(skip(self, audit), fields(user_id = %user_id, actor = %audit.actor))]
+    pub async fn unban_user(&self, user_id: &UserId, audit: &AuditEntry) -> Result<()> {
+        let user = self.admin_repo.unban_user(user_id, audit).await?;
+
+        let member = user_id.to_string();
+        let mut pipe = redis::pipe();
+        pipe.srem(BANNED_USERS_KEY, &member).ignore();
+        pipe.srem(HIDDEN_USERS_KEY, &member).ignore();
+        self.run(pipe).await?;
+        self.anti_cheat.clear_action(user_id).await?;
+
+        tracing::info!(reason = %audit.reason, "User unbanned");
+        self.republish(&user, 0).await
+    }
+
+    #[tracing::instrument(skip(self, audit), fields(user_id = %user_id, actor = %audit.actor))]
+    pub async fn remove_from_leaderboard(&self, user_id: &UserId, audit: &AuditEntry) -> Result<()> {
+        let user = self.admin_repo.remove_from_leaderboard(user_id, audit).await?;
+
+        let mut pipe = redis::pipe();
+        pipe.sadd(HIDDEN_USERS_KEY, user_id.to_string()).ignore();
+        self.run(pipe).await?;
+
+        tracing::info!(reason = %audit.reason, "User removed from leaderboard");
+        self.republish(&user, 0).await
+    }
+
+    /// Returns the user's new total. Clicks still waiting in the accumulator are
+    /// taken out first and written with the adjustment, so a flush can't add them
+    /// on top of it afterwards, and the change reaches the period boards too.
+    #[tracing::instrument(skip(self, audit), fields(user_id = %user_id, actor = %audit.actor))]
+    pub async fn adjust_score(
+        &self,
+        user_id: &UserId,
+        adjustment: ScoreAdjustment,
+        audit: &AuditEntry,
+    ) -> Result<i64> {
+        let pending_clicks = self.take_pending_clicks(user_id).await?;
+
+        let adjusted = match self.admin_repo.adjust_score(user_id, adjustment, pending_clicks, audit).await {
+            Ok(adjusted) => adjusted,
+            Err(e) => {
+                if pending_clicks > 0 {
+                    self.accumulator
+                        .restore_pending_clicks(&user_id.to_string(), pending_clicks)
+                        .await?;
+                }
+                return Err(e);
+            }
+        };
+
+        tracing::info!(
+            new_total = adjusted.user.total_clicks,
+            pending_clicks = pending_clicks,
+            reason = %audit.reason,
+            "User score adjusted"
+        );
+        self.republish(&adjusted.user, adjusted.clicks_added).await?;
+
+        Ok(adjusted.user.total_clicks)
+    }
+
+    #[tracing::instrument(skip(self, audit), fields(user_id = %user_id, actor = %audit.actor))]
+    pub async fn rename_user(&self, user_id: &UserId, new_username: &str, audit: &AuditEntry) -> Result<()> {
+        let username = Username::new(new_username)?;
+        let user = self.admin_repo.rename_user(user_id, &username, audit).await?;
+
+        tracing::info!(username = %user.username, "User renamed");
+        self.republish(&user, 0).await
+    }
+
+    pub async fn list_flagged_users(
+        &self,
+        action: Option<CheatAction>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<FlaggedUser>, i64)> {
+        self.admin_repo.list_flagged_users(action, limit, offset).await
+    }
+
+    pub async fn list_telegram_ids(&self, after: i64, limit: i64) -> Result<Vec<i64>> {
+        self.admin_repo.list_telegram_ids(after, limit).await
+    }
+
+    pub async fn is_banned(&self, user_id: &UserId) -> Result<bool> {
+        self.redis
+            .clone()
+            .sismember(BANNED_USERS_KEY, user_id.to_string())
+            .await
+            .map_err(|e| ServiceError::Redis(e.to_string()))
+    }
+
+    /// Re-adds every banned user to the Redis set, in case Redis lost it.
+    pub async fn sync_banned_users(&self) -> Result<usize> {
+        let user_ids = self.admin_repo.get_banned_user_ids().await?;
+        if !user_ids.is_empty() {
+            let members: Vec<String> = user_ids.iter().map(UserId::to_string).collect();
+            let mut pipe = redis::pipe();
+            pipe.sadd(BANNED_USERS_KEY, members).ignore();
+            self.run(pipe).await?;
+        }
+
+        Ok(user_ids.len())
+    }
+
+    /// Publishes the user's current state. The leaderboard sets the user's
+    /// absolute total, or drops them if they are now hidden, and adds
+    /// `clicks_added` to the period boards.
+    async fn republish(&self, user: &ModeratedUser, clicks_added: i64) -> Result<()> {
+        self.publisher
+            .publish_click_event(&user.user_id.to_string(), &user.username, user.total(), clicks_added, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn take_pending_clicks(&self, user_id: &UserId) -> Result<i64> {
+        let member = user_id.to_string();
+
+        for _ in 0..PENDING_CLICKS_ATTEMPTS {
+            if let Some(clicks) = self.accumulator.take_pending_clicks(&member, &self.shard_slots).await? {
+                return Ok(clicks);
+            }
+            tokio::time::sleep(PENDING_CLICKS_RETRY).await;
+        }
+
+        Err(ServiceError::Internal(
+            "The user's clicks are being flushed, try again".to_string(),
+        ))
+    }
+
+    async fn run(&self, pipe: redis::Pipeline) -> Result<()> {
+        pipe.query_async::<()>(&mut self.redis.clone())
+            .await
+            .map_err(|e| ServiceError::Redis(e.to_string()))
+    }
+}
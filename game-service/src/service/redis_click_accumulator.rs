@@ -1,29 +1,87 @@
 
 use redis::aio::MultiplexedConnection;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, Script};
 use std::collections::HashMap;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
 use shared::{Result, ServiceError};
-use crate::repository::UserRepository;
+use crate::repository::{UserRepository, UserTotal};
 use crate::stream::ClickEventPublisher;
 
 const REDIS_CLICKS_PREFIX: &str = "clicks:pending:shard:";
+const REDIS_INFLIGHT_PREFIX: &str = "clicks:inflight:shard:";
 const REDIS_USERNAMES_KEY: &str = "clicks:usernames";
-
-const MAX_BATCH_SIZE: usize = 20;
+const REDIS_SESSIONS_KEY: &str = "clicks:sessions";
+
+/// Moves the pending hash aside under a new flush id, unless a previous flush is
+/// still in flight, in which case its id is returned so it can be retried. Clicks
+/// arriving after the RENAME start a fresh pending hash.
+const CLAIM_BATCH_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[2]) == 1 then
+    local flush_id = redis.call('GET', KEYS[3])
+    if flush_id then
+        return flush_id
+    end
+    redis.call('SET', KEYS[3], ARGV[1])
+    return ARGV[1]
+end
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return false
+end
+redis.call('RENAME', KEYS[1], KEYS[2])
+redis.call('SET', KEYS[3], ARGV[1])
+return ARGV[1]
+";
+
+/// Hands a chunk that could not be written back to the pending hash, taking it
+/// out of the in-flight batch in the same step. ARGV is user id/count pairs.
+const REQUEUE_CHUNK_SCRIPT: &str = r"
+for i = 1, #ARGV, 2 do
+    redis.call('HINCRBY', KEYS[1], ARGV[i], ARGV[i + 1])
+    redis.call('HDEL', KEYS[2], ARGV[i])
+end
+return #ARGV / 2
+";
+
+/// Takes a user's pending clicks out of every shard's pending hash, unless one of
+/// the shards is flushing them, in which case nothing is taken and -1 is returned.
+/// KEYS are pending/in-flight hash pairs, ARGV[1] is the user id.
+const TAKE_PENDING_SCRIPT: &str = r"
+for i = 2, #KEYS, 2 do
+    if redis.call('HEXISTS', KEYS[i], ARGV[1]) == 1 then
+        return -1
+    end
+end
+local taken = 0
+for i = 1, #KEYS, 2 do
+    taken = taken + tonumber(redis.call('HGET', KEYS[i], ARGV[1]) or '0')
+    redis.call('HDEL', KEYS[i], ARGV[1])
+end
+return taken
+";
+
+/// The slot an instance's pending clicks are kept under: `game-3` uses slot 2.
+pub fn shard_slot(instance_id: &str) -> usize {
+    instance_id
+        .split('-')
+        .nth(1)
+        .and_then(|s| s.parse::<usize>().ok())
+        .map(|n| n - 1)
+        .unwrap_or(0)
+}
 
 pub struct RedisClickAccumulator {
     redis: MultiplexedConnection,
     user_repo: UserRepository,
     event_publisher: Option<ClickEventPublisher>,
     flush_interval: Duration,
+    flush_chunk_size: usize,
     shard_id: usize,
-    num_shards: usize,
+    claim_script: Script,
+    requeue_script: Script,
+    take_pending_script: Script,
 }
 
 impl RedisClickAccumulator {
@@ -33,16 +91,19 @@ impl RedisClickAccumulator {
         user_repo: UserRepository,
         event_publisher: Option<ClickEventPublisher>,
         flush_interval_ms: u64,
+        flush_chunk_size: usize,
         shard_id: usize,
-        num_shards: usize,
     ) -> Self {
         Self {
             redis,
             user_repo,
             event_publisher,
             flush_interval: Duration::from_millis(flush_interval_ms),
+            flush_chunk_size: flush_chunk_size.max(1),
             shard_id,
-            num_shards,
+            claim_script: Script::new(CLAIM_BATCH_SCRIPT),
+            requeue_script: Script::new(REQUEUE_CHUNK_SCRIPT),
+            take_pending_script: Script::new(TAKE_PENDING_SCRIPT),
         }
     }
 
@@ -51,6 +112,7 @@ impl RedisClickAccumulator {
         &self,
         user_id: &str,
         username: &str,
+        session_id: &str,
         count: u32,
     ) -> Result<u32> {
         let mut redis = self.redis.clone();
@@ -65,11 +127,13 @@ impl RedisClickAccumulator {
                 ServiceError::Internal(format!("Redis HINCRBY failed: {}", e))
             })?;
 
-        let _: () = redis
+        let _: () = redis::pipe()
             .hset(REDIS_USERNAMES_KEY, user_id, username)
+            .hset(REDIS_SESSIONS_KEY, user_id, session_id)
+            .query_async(&mut redis)
             .await
             .map_err(|e| {
-                warn!(error = %e, "Failed to cache username in Redis");
+                warn!(error = %e, "Failed to cache username/session in Redis");
                 e
             })
             .unwrap_or(());
@@ -84,109 +148,253 @@ impl RedisClickAccumulator {
         Ok(new_count)
     }
 
-    pub async fn flush_batch(&mut self) -> Result<usize> {
+    /// Removes the user's unflushed clicks from the given shard slots and returns
+    /// how many there were, so they can be written some other way. Returns `None`
+    /// while a flush holds some of them, since they may be committed at any moment.
+    pub async fn take_pending_clicks(&self, user_id: &str, shard_slots: &[usize]) -> Result<Option<i64>> {
+        let mut invocation = self.take_pending_script.prepare_invoke();
+        for slot in shard_slots {
+            invocation
+                .key(format!("{}{}", REDIS_CLICKS_PREFIX, slot))
+                .key(format!("{}{}", REDIS_INFLIGHT_PREFIX, slot));
+        }
+        invocation.arg(user_id);
+
+        let taken: i64 = invocation.invoke_async(&mut self.redis.clone()).await.map_err(|e| {
+            error!(error = %e, "Failed to take pending clicks");
+            ServiceError::Internal(format!("Redis take-pending script failed: {}", e))
+        })?;
+
+        Ok((taken >= 0).then_some(taken))
+    }
+
+    /// Puts clicks taken by `take_pending_clicks` back on this shard.
+    pub async fn restore_pending_clicks(&self, user_id: &str, clicks: i64) -> Result<()> {
         let clicks_key = format!("{}{}", REDIS_CLICKS_PREFIX, self.shard_id);
 
-        let mut pending_clicks: HashMap<String, i64> = self
+        self.redis
+            .clone()
+            .hincr::<_, _, _, ()>(&clicks_key, user_id, clicks)
+            .await
+            .map_err(|e| {
+                error!(error = %e, clicks = clicks, "Failed to restore pending clicks");
+                ServiceError::Internal(format!("Redis HINCRBY failed: {}", e))
+            })
+    }
+
+    /// Flushes this shard's pending clicks to Postgres in chunks of
+    /// `flush_chunk_size` users. The batch stays in Redis under its flush id until
+    /// each chunk is either committed or re-queued, and every chunk records an id
+    /// derived from the flush id, so a retried flush never applies a chunk twice.
+    pub async fn flush_batch(&mut self) -> Result<usize> {
+        let Some(flush_id) = self.claim_inflight_batch().await? else {
+            debug!(shard_id = self.shard_id, "No user clicks to flush for this shard");
+            return Ok(0);
+        };
+
+        let pending_clicks: HashMap<String, i64> = self
             .redis
-            .hgetall(&clicks_key)
+            .hgetall(self.inflight_key())
             .await
             .map_err(|e| {
-                error!(error = %e, "Failed to fetch pending clicks from Redis");
+                error!(error = %e, "Failed to fetch in-flight clicks from Redis");
                 ServiceError::Internal(format!("Redis HGETALL failed: {}", e))
             })?;
 
-        let user_count = if pending_clicks.is_empty() {
-            debug!(shard_id = self.shard_id, "No user clicks to flush for this shard");
-            0
-        } else {
-            let batch_size = pending_clicks.len();
-
-            let _: () = self
-                .redis
-                .del(&clicks_key)
-                .await
-                .map_err(|e| {
-                    warn!(error = %e, "Failed to clear Redis clicks after fetch");
-                    e
-                })
-                .unwrap_or(());
-
-            let user_ids: Vec<&String> = pending_clicks.keys().collect();
-            let usernames: HashMap<String, String> = self
-                .redis
-                .hget(REDIS_USERNAMES_KEY, &user_ids)
-                .await
-                .unwrap_or_default();
-
-            let total_clicks: i64 = pending_clicks.values().sum();
+        if pending_clicks.is_empty() {
+            self.clear_inflight_batch().await?;
+            return Ok(0);
+        }
 
-            info!(
-                shard_id = self.shard_id,
-                users = batch_size,
-                total_clicks = total_clicks,
-                "Flushing Redis user click batch to database"
-            );
+        // Sorted so a retried flush cuts the same chunks and derives the same ids
+        let mut user_ids: Vec<String> = pending_clicks.keys().cloned().collect();
+        user_ids.sort_unstable();
 
-            let pending_clicks: HashMap<String, i64> = if pending_clicks.len() > MAX_BATCH_SIZE {
-                warn!(
-                    total = pending_clicks.len(),
-                    limit = MAX_BATCH_SIZE,
-                    "Batch size exceeded limit, processing first {} users",
-                    MAX_BATCH_SIZE
-                );
-                pending_clicks.into_iter().take(MAX_BATCH_SIZE).collect()
-            } else {
-                pending_clicks
-            };
-
-            let batches: HashMap<String, super::click_batch_accumulator::UserClickBatch> =
-                pending_clicks
-                    .into_iter()
-                    .map(|(user_id, count)| {
-                        let username = usernames
-                            .get(&user_id)
-                            .cloned()
-                            .unwrap_or_else(|| "Unknown".to_string());
-
-                        (
-                            user_id.clone(),
-                            super::click_batch_accumulator::UserClickBatch {
-                                username,
-                                accumulated_clicks: count as u32,
-                                last_click_time: chrono::Utc::now(),
-                            },
-                        )
-                    })
-                    .collect();
-
-            let updated_totals = self.bulk_update_with_retry(&batches).await?;
-
-            if let Some(publisher) = &self.event_publisher {
-                self.publish_batch_events(publisher, &batches, &updated_totals).await;
+        let (usernames, sessions): (Vec<Option<String>>, Vec<Option<String>>) = redis::pipe()
+            .cmd("HMGET").arg(REDIS_USERNAMES_KEY).arg(&user_ids)
+            .cmd("HMGET").arg(REDIS_SESSIONS_KEY).arg(&user_ids)
+            .query_async(&mut self.redis)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "Failed to fetch usernames/sessions for batch");
+                (vec![None; user_ids.len()], vec![None; user_ids.len()])
+            });
+
+        let now = chrono::Utc::now();
+        let batches: Vec<(String, super::click_batch_accumulator::UserClickBatch)> = user_ids
+            .into_iter()
+            .zip(usernames.into_iter().zip(sessions))
+            .map(|(user_id, (username, session_id))| {
+                let batch = super::click_batch_accumulator::UserClickBatch {
+                    username: username.unwrap_or_else(|| "Unknown".to_string()),
+                    session_id,
+                    accumulated_clicks: pending_clicks[&user_id] as u32,
+                    last_click_time: now,
+                };
+                (user_id, batch)
+            })
+            .collect();
+
+        let total_clicks: i64 = pending_clicks.values().sum();
+
+        info!(
+            shard_id = self.shard_id,
+            flush_id = %flush_id,
+            users = batches.len(),
+            total_clicks = total_clicks,
+            chunks = batches.len().div_ceil(self.flush_chunk_size),
+            "Flushing Redis user click batch to database"
+        );
+
+        let mut flushed_users = 0;
+        let mut requeued_clicks = 0;
+
+        for chunk in batches.chunks(self.flush_chunk_size) {
+            let chunk_ids: Vec<&str> = chunk.iter().map(|(user_id, _)| user_id.as_str()).collect();
+            let chunk_id = chunk_flush_id(flush_id, &chunk_ids);
+            let chunk: HashMap<String, super::click_batch_accumulator::UserClickBatch> =
+                chunk.iter().cloned().collect();
+
+            match self.bulk_update_with_retry(chunk_id, &chunk).await {
+                Ok(Some(updated_totals)) => {
+                    if let Some(publisher) = &self.event_publisher {
+                        self.publish_batch_events(publisher, &chunk, &updated_totals).await;
+                    }
+                    flushed_users += chunk.len();
+                }
+                Ok(None) => {
+                    // Committed by an earlier attempt that died before clearing Redis.
+                    // Its events may be lost; the next flush for these users carries
+                    // their absolute totals, so the leaderboard catches up.
+                    shared::record_counter("game_service.flush.already_applied", 1);
+                }
+                Err(e) => {
+                    error!(
+                        chunk_id = %chunk_id,
+                        users = chunk.len(),
+                        error = %e,
+                        "Failed to flush click chunk, re-queueing"
+                    );
+                    requeued_clicks += self.requeue_chunk(&chunk).await?;
+                    continue;
+                }
             }
 
-            info!(
-                users = batch_size,
-                total_clicks = total_clicks,
-                "Redis user click batch flushed successfully"
-            );
+            self.release_chunk(&chunk_ids).await?;
+        }
 
-            batch_size
+        self.clear_inflight_batch().await?;
+
+        if requeued_clicks > 0 {
+            shared::record_counter("game_service.flush.requeued_clicks", requeued_clicks);
+        }
+
+        info!(
+            flush_id = %flush_id,
+            users = flushed_users,
+            requeued_clicks = requeued_clicks,
+            "Redis user click batch flushed"
+        );
+
+        Ok(flushed_users)
+    }
+
+    fn inflight_key(&self) -> String {
+        format!("{}{}", REDIS_INFLIGHT_PREFIX, self.shard_id)
+    }
+
+    fn inflight_id_key(&self) -> String {
+        format!("{}{}:flush_id", REDIS_INFLIGHT_PREFIX, self.shard_id)
+    }
+
+    async fn claim_inflight_batch(&mut self) -> Result<Option<uuid::Uuid>> {
+        let new_flush_id = uuid::Uuid::new_v4();
+
+        let flush_id: Option<String> = self
+            .claim_script
+            .key(format!("{}{}", REDIS_CLICKS_PREFIX, self.shard_id))
+            .key(self.inflight_key())
+            .key(self.inflight_id_key())
+            .arg(new_flush_id.to_string())
+            .invoke_async(&mut self.redis)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Failed to claim pending clicks for flush");
+                ServiceError::Internal(format!("Redis claim script failed: {}", e))
+            })?;
+
+        let Some(flush_id) = flush_id else {
+            return Ok(None);
         };
 
-        Ok(user_count)
+        let flush_id = uuid::Uuid::parse_str(&flush_id)
+            .map_err(|e| ServiceError::Internal(format!("Invalid in-flight flush id: {}", e)))?;
+
+        if flush_id != new_flush_id {
+            warn!(flush_id = %flush_id, "Retrying unfinished click flush");
+            shared::record_counter("game_service.flush.retried", 1);
+        }
+
+        Ok(Some(flush_id))
+    }
+
+    /// Drops a committed chunk from the in-flight batch.
+    async fn release_chunk(&mut self, user_ids: &[&str]) -> Result<()> {
+        let inflight_key = self.inflight_key();
+
+        self.redis
+            .hdel::<_, _, ()>(inflight_key, user_ids)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Failed to release flushed click chunk");
+                ServiceError::Internal(format!("Redis HDEL failed: {}", e))
+            })
+    }
+
+    async fn requeue_chunk(
+        &mut self,
+        chunk: &HashMap<String, super::click_batch_accumulator::UserClickBatch>,
+    ) -> Result<u64> {
+        let mut invocation = self.requeue_script.prepare_invoke();
+        invocation
+            .key(format!("{}{}", REDIS_CLICKS_PREFIX, self.shard_id))
+            .key(self.inflight_key());
+
+        for (user_id, batch) in chunk {
+            invocation.arg(user_id).arg(batch.accumulated_clicks);
+        }
+
+        let _: i64 = invocation.invoke_async(&mut self.redis).await.map_err(|e| {
+            error!(error = %e, "Failed to re-queue click chunk");
+            ServiceError::Internal(format!("Redis re-queue script failed: {}", e))
+        })?;
+
+        Ok(chunk.values().map(|batch| batch.accumulated_clicks as u64).sum())
+    }
+
+    async fn clear_inflight_batch(&mut self) -> Result<()> {
+        let inflight_key = self.inflight_key();
+        let inflight_id_key = self.inflight_id_key();
+
+        self.redis
+            .del::<_, ()>(&[inflight_key, inflight_id_key])
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Failed to clear in-flight click batch");
+                ServiceError::Internal(format!("Redis DEL failed: {}", e))
+            })
     }
 
     async fn bulk_update_with_retry(
         &self,
+        flush_id: uuid::Uuid,
         batches: &HashMap<String, super::click_batch_accumulator::UserClickBatch>,
-    ) -> Result<HashMap<String, i64>> {
+    ) -> Result<Option<HashMap<String, UserTotal>>> {
         const MAX_RETRIES: u32 = 3;
         let mut attempt = 0;
 
         loop {
-            match self.user_repo.bulk_increment_clicks(batches).await {
+            match self.user_repo.apply_click_flush(flush_id, self.shard_id, batches).await {
                 Ok(result) => return Ok(result),
                 Err(e) => {
                     let err_msg = e.to_string();
@@ -211,24 +419,30 @@ impl RedisClickAccumulator {
         &self,
         publisher: &ClickEventPublisher,
         batches: &HashMap<String, super::click_batch_accumulator::UserClickBatch>,
-        updated_totals: &HashMap<String, i64>,
+        updated_totals: &HashMap<String, UserTotal>,
     ) {
         for (user_id, batch) in batches.iter() {
-            let total_clicks = updated_totals.get(user_id).copied().unwrap_or_else(|| {
+            let total = updated_totals.get(user_id).copied().unwrap_or_else(|| {
                 warn!(
                     user_id = %user_id,
                     "User not found in updated totals, using batch count as fallback"
                 );
-                batch.accumulated_clicks as i64
+                UserTotal {
+                    total_clicks: batch.accumulated_clicks as i64,
+                    score_reached_at: chrono::Utc::now(),
+                    score_version: 0,
+                }
             });
 
             let publisher_clone = publisher.clone();
             let user_id = user_id.clone();
             let username = batch.username.clone();
+            let session_id = batch.session_id.clone();
+            let delta = batch.accumulated_clicks as i64;
 
             tokio::spawn(async move {
                 if let Err(e) = publisher_clone
-                    .publish_click_event(&user_id, &username, total_clicks)
+                    .publish_click_event(&user_id, &username, total, delta, session_id.as_deref())
                     .await
                 {
                     error!(
@@ -246,7 +460,7 @@ impl RedisClickAccumulator {
         );
     }
 
-    pub fn start_background_flusher(self: Arc<Self>) {
+    pub fn start_background_flusher(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
         let interval = self.flush_interval;
 
         tokio::spawn(async move {
@@ -270,8 +484,8 @@ impl RedisClickAccumulator {
                     user_repo,
                     event_publisher,
                     flush_interval.as_millis() as u64,
+                    self.flush_chunk_size,
                     self.shard_id,
-                    self.num_shards,
                 );
 
                 match accumulator.flush_batch().await {
@@ -286,7 +500,7 @@ impl RedisClickAccumulator {
                     }
                 }
             }
-        });
+        })
     }
 }
 
@@ -297,15 +511,41 @@ impl Clone for RedisClickAccumulator {
             user_repo: self.user_repo.clone(),
             event_publisher: self.event_publisher.clone(),
             flush_interval: self.flush_interval,
+            flush_chunk_size: self.flush_chunk_size,
             shard_id: self.shard_id,
-            num_shards: self.num_shards,
+            claim_script: self.claim_script.clone(),
+            requeue_script: self.requeue_script.clone(),
+            take_pending_script: self.take_pending_script.clone(),
         }
     }
 }
 
+/// Idempotency key for one chunk of a flush. Derived from the flush id and the
+/// chunk's users so that a retry of the same in-flight batch reproduces it.
+fn chunk_flush_id(flush_id: uuid::Uuid, user_ids: &[&str]) -> uuid::Uuid {
+    uuid::Uuid::new_v5(&flush_id, user_ids.join(",").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_flush_id_is_stable() {
+        let flush_id = uuid::Uuid::new_v4();
 
-fn get_shard_for_user(user_id: &str, num_shards: usize) -> usize {
-    let mut hasher = DefaultHasher::new();
-    user_id.hash(&mut hasher);
-    (hasher.finish() as usize) % num_shards
+        assert_eq!(
+            chunk_flush_id(flush_id, &["user-a", "user-b"]),
+            chunk_flush_id(flush_id, &["user-a", "user-b"])
+        );
+    }
+
+    #[test]
+    fn test_chunk_flush_id_differs_per_chunk_and_flush() {
+        let flush_id = uuid::Uuid::new_v4();
+        let first = chunk_flush_id(flush_id, &["user-a", "user-b"]);
+
+        assert_ne!(first, chunk_flush_id(flush_id, &["user-c"]));
+        assert_ne!(first, chunk_flush_id(uuid::Uuid::new_v4(), &["user-a", "user-b"]));
+    }
 }
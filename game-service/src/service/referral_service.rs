@@ -0,0 +1,102 @@
+use shared::{Result, User, UserId, Username};
+
+use crate::repository::{ModeratedUser, ReferralRepository};
+use crate::stream::ClickEventPublisher;
+
+/// How long a back-filled bonus is leased to one instance before another may
+/// retry it.
+const BACKFILL_LEASE_SECS: i64 = 60;
+
+/// Credits inviters for the players they bring in. The bonus lands in Postgres
+/// first and is then published on the click stream like any other clicks, so it
+/// counts on the period boards too. A bonus that fails to publish is left for
+/// `republish_pending_bonuses` rather than failing the registration.
+pub struct ReferralService {
+    referral_repo: ReferralRepository,
+    publisher: ClickEventPublisher,
+    bonus_clicks: i64,
+}
+
+impl ReferralService {
+    pub fn new(referral_repo: ReferralRepository, publisher: ClickEventPublisher, bonus_clicks: i64) -> Self {
+        Self {
+            referral_repo,
+            publisher,
+            bonus_clicks,
+        }
+    }
+
+    /// Registers a player who came through an invite link. The user and the
+    /// referral commit together, so a failure leaves neither behind and the
+    /// registration can be retried. Returns the inviter, or `None` if the code
+    /// doesn't earn anyone a bonus.
+    #[tracing::instrument(skip(self))]
+    pub async fn register_referred_user(
+        &self,
+        telegram_id: i64,
+        username: &str,
+        referral_code: &str,
+    ) -> Result<(User, Option<ModeratedUser>)> {
+        let username = Username::new(username)?;
+
+        let (user, inviter) = self
+            .referral_repo
+            .create_referred_user(telegram_id, username.as_str(), referral_code, self.bonus_clicks)
+            .await?;
+
+        let Some(inviter) = inviter else {
+            tracing::info!(invitee = %user.id, "Referral code not applied");
+            return Ok((user, None));
+        };
+
+        tracing::info!(invitee = %user.id, inviter = %inviter.user_id, bonus = self.bonus_clicks, "Referral recorded");
+        shared::record_counter("game_service.referrals.recorded", 1);
+
+        // The referral is committed either way; an unpublished bonus is back-filled
+        if let Err(e) = self.publish_bonus(&user.id, &inviter, self.bonus_clicks).await {
+            tracing::warn!(
+                error = %e,
+                inviter = %inviter.user_id,
+                "Failed to publish referral bonus, leaving it for back-fill"
+            );
+            shared::record_counter("game_service.referrals.publish_failed", 1);
+        }
+
+        Ok((user, Some(inviter)))
+    }
+
+    /// Publishes up to `limit` committed bonuses that never reached the click
+    /// stream, returning how many were published.
+    pub async fn republish_pending_bonuses(&self, limit: i64) -> Result<usize> {
+        let pending = self.referral_repo.claim_unpublished(BACKFILL_LEASE_SECS, limit).await?;
+
+        let mut published = 0;
+        for bonus in pending {
+            match self.publish_bonus(&bonus.invitee, &bonus.inviter, bonus.bonus_clicks).await {
+                Ok(()) => published += 1,
+                Err(e) => {
+                    tracing::warn!(error = %e, invitee = %bonus.invitee, "Failed to back-fill referral bonus");
+                }
+            }
+        }
+
+        if published > 0 {
+            shared::record_counter("game_service.referrals.backfilled", published as u64);
+        }
+        Ok(published)
+    }
+
+    async fn publish_bonus(&self, invitee: &UserId, inviter: &ModeratedUser, bonus_clicks: i64) -> Result<()> {
+        self.publisher
+            .publish_click_event(
+                &inviter.user_id.to_string(),
+                &inviter.username,
+                inviter.total(),
+                bonus_clicks,
+                None,
+            )
+            .await?;
+
+        self.referral_repo.mark_published(invitee).await
+    }
+}
@@ -1,4 +1,4 @@
-use shared::{Result, ServiceError, Session, SessionId, SessionStats, UserId};
+use shared::{Result, Session, SessionId, SessionStats, UserId};
 use crate::repository::SessionRepository;
 
 
@@ -147,6 +147,7 @@ impl SessionService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use shared::ServiceError;
 
     #[test]
     fn test_session_timeout_configuration() {
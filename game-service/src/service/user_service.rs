@@ -16,7 +16,7 @@ impl UserService {
     pub async fn register_user(&self, telegram_id: i64, username: &str) -> Result<User> {
         let validated_username = Username::new(username)?;
 
-        if let Ok(_) = self.user_repo.get_by_telegram_id(telegram_id).await {
+        if self.user_repo.get_by_telegram_id(telegram_id).await.is_ok() {
             return Err(ServiceError::UserAlreadyExists(telegram_id.to_string()));
         }
 
@@ -81,7 +81,7 @@ mod tests {
     #[test]
     fn test_username_validation_rejects_invalid() {
         assert!(Username::new("ab").is_err(), "Too short");
-        assert!(Username::new(&"a".repeat(21)).is_err(), "Too long");
+        assert!(Username::new("a".repeat(21)).is_err(), "Too long");
         assert!(Username::new("user@name").is_err(), "Invalid char @");
         assert!(Username::new("user name").is_err(), "Invalid char space");
         assert!(Username::new("user!").is_err(), "Invalid char !");
@@ -91,7 +91,7 @@ mod tests {
     #[test]
     fn test_username_validation_accepts_valid() {
         assert!(Username::new("abc").is_ok(), "Minimum length");
-        assert!(Username::new(&"a".repeat(20)).is_ok(), "Maximum length");
+        assert!(Username::new("a".repeat(20)).is_ok(), "Maximum length");
         assert!(Username::new("user123").is_ok(), "Alphanumeric");
         assert!(Username::new("user_name").is_ok(), "With underscore");
         assert!(Username::new("user-name").is_ok(), "With hyphen");
@@ -1,51 +1,78 @@
 
 
 use redis::aio::MultiplexedConnection;
-use redis::{AsyncCommands, RedisError};
+use redis::{RedisError, Script};
 use shared::errors::{Result, ServiceError};
+use shared::events::{CLICK_SEQ_KEY, CLICK_STREAM_KEY};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, error};
 
-const STREAM_KEY: &str = "clicks:stream";
+use crate::repository::UserTotal;
+
+/// Issues the user's next sequence number and appends the event in one step, so
+/// stream order always matches sequence order.
+const PUBLISH_SCRIPT: &str = r"
+local seq = redis.call('HINCRBY', KEYS[2], ARGV[1], 1)
+return redis.call('XADD', KEYS[1], '*',
+    'user_id', ARGV[1],
+    'username', ARGV[2],
+    'total_clicks', ARGV[3],
+    'delta', ARGV[4],
+    'session_id', ARGV[5],
+    'seq', seq,
+    'timestamp', ARGV[6],
+    'reached_at', ARGV[7],
+    'score_version', ARGV[8])
+";
 
 #[derive(Clone)]
 pub struct ClickEventPublisher {
     redis: Arc<Mutex<MultiplexedConnection>>,
+    publish_script: Script,
 }
 
 impl ClickEventPublisher {
     pub fn new(redis: MultiplexedConnection) -> Self {
         Self {
             redis: Arc::new(Mutex::new(redis)),
+            publish_script: Script::new(PUBLISH_SCRIPT),
         }
     }
 
+    /// Publishes a committed change to the user's score. `total` is as returned
+    /// by the change, so the leaderboard breaks ties on the time Postgres
+    /// recorded rather than the time of publishing, and keeps the newest total
+    /// by its version even if events reach the stream out of order.
     pub async fn publish_click_event(
         &self,
         user_id: &str,
         username: &str,
-        total_clicks: i64,
+        total: UserTotal,
+        delta: i64,
+        session_id: Option<&str>,
     ) -> Result<String> {
         let mut conn = self.redis.lock().await;
         let timestamp = chrono::Utc::now().timestamp();
 
         debug!(
-            "Publishing click event: user_id={}, username={}, total_clicks={}",
-            user_id, username, total_clicks
+            "Publishing click event: user_id={}, username={}, total_clicks={}, delta={}",
+            user_id, username, total.total_clicks, delta
         );
 
-        let message_id: String = conn
-            .xadd(
-                STREAM_KEY,
-                "*",
-                &[
-                    ("user_id", user_id),
-                    ("username", username),
-                    ("total_clicks", &total_clicks.to_string()),
-                    ("timestamp", &timestamp.to_string()),
-                ],
-            )
+        let message_id: String = self
+            .publish_script
+            .key(CLICK_STREAM_KEY)
+            .key(CLICK_SEQ_KEY)
+            .arg(user_id)
+            .arg(username)
+            .arg(total.total_clicks)
+            .arg(delta)
+            .arg(session_id.unwrap_or_default())
+            .arg(timestamp)
+            .arg(total.score_reached_at.timestamp())
+            .arg(total.score_version)
+            .invoke_async(&mut *conn)
             .await
             .map_err(|e: RedisError| {
                 error!("Failed to publish click event: {}", e);
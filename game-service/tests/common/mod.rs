@@ -1,4 +1,6 @@
 
+#![allow(dead_code)]
+
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use std::env;
 
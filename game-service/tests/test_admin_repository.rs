@@ -0,0 +1,213 @@
+mod common;
+
+use common::create_test_user_data;
+use game_service::domain::{CheatAction, CheatSignal, ScoreAdjustment, SuspicionReport};
+use game_service::repository::{AdminRepository, AuditEntry, CheatRepository, UserRepository};
+use shared::{UserId, Username};
+use sqlx::PgPool;
+use anyhow::Result;
+
+fn audit(reason: &str) -> AuditEntry {
+    AuditEntry {
+        actor: "test-admin".to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+async fn audit_actions(pool: &PgPool, user_id: &UserId) -> Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT action FROM admin_audit_log WHERE user_id = $1 ORDER BY id",
+    )
+    .bind(user_id.0)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|(action,)| action).collect())
+}
+
+async fn is_hidden(pool: &PgPool, user_id: &UserId) -> Result<bool> {
+    let (hidden,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM hidden_users WHERE user_id = $1)")
+        .bind(user_id.0)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(hidden)
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_ban_and_unban_user(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_ban");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let moderated = admin_repo.ban_user(&user.id, &audit("autoclicker")).await?;
+    assert_eq!(moderated.username, username);
+    assert_eq!(admin_repo.get_banned_user_ids().await?, vec![user.id]);
+    assert!(is_hidden(&pool, &user.id).await?);
+
+    admin_repo.unban_user(&user.id, &audit("appeal accepted")).await?;
+    assert!(admin_repo.get_banned_user_ids().await?.is_empty());
+    assert!(!is_hidden(&pool, &user.id).await?);
+
+    assert_eq!(audit_actions(&pool, &user.id).await?, vec!["ban", "unban"]);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_unban_clears_anti_cheat_actions(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+    let cheat_repo = CheatRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_unflag");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let report = SuspicionReport {
+        score: 0.6,
+        signals: vec![CheatSignal::RegularCadence],
+    };
+    cheat_repo.record_detection(&user.id, &report, CheatAction::ShadowBan).await?;
+
+    let (flagged, total) = admin_repo.list_flagged_users(None, 10, 0).await?;
+    assert_eq!(total, 1);
+    assert_eq!(flagged[0].user_id, user.id);
+    assert_eq!(flagged[0].action, CheatAction::ShadowBan);
+    assert!(!flagged[0].banned);
+
+    let (_, frozen) = admin_repo.list_flagged_users(Some(CheatAction::Freeze), 10, 0).await?;
+    assert_eq!(frozen, 0);
+
+    admin_repo.unban_user(&user.id, &audit("false positive")).await?;
+
+    assert_eq!(cheat_repo.get_action(&user.id).await?, None);
+    assert!(!is_hidden(&pool, &user.id).await?);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_remove_from_leaderboard_hides_user(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_remove");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    admin_repo.remove_from_leaderboard(&user.id, &audit("offensive name")).await?;
+
+    assert!(is_hidden(&pool, &user.id).await?);
+    assert!(admin_repo.get_banned_user_ids().await?.is_empty());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_adjust_score(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_adjust");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let adjusted = admin_repo.adjust_score(&user.id, ScoreAdjustment::Delta(150), 0, &audit("compensation")).await?;
+    assert_eq!(adjusted.user.total_clicks, 150);
+    assert_eq!(adjusted.clicks_added, 150);
+
+    let adjusted = admin_repo.adjust_score(&user.id, ScoreAdjustment::Delta(-500), 0, &audit("exploit")).await?;
+    assert_eq!(adjusted.user.total_clicks, 0);
+    assert_eq!(adjusted.clicks_added, -150);
+
+    let adjusted = admin_repo.adjust_score(&user.id, ScoreAdjustment::SetTo(42), 0, &audit("restore")).await?;
+    assert_eq!(adjusted.user.total_clicks, 42);
+    assert_eq!(user_repo.get_by_id(&user.id).await?.total_clicks, 42);
+
+    let (reason, details): (String, String) = sqlx::query_as(
+        "SELECT reason, details FROM admin_audit_log WHERE user_id = $1 ORDER BY id DESC LIMIT 1",
+    )
+    .bind(user.id.0)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(reason, "restore");
+    assert!(details.contains("0 -> 42"), "Unexpected details: {}", details);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_adjust_score_with_pending_clicks(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_adjust_pending");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    // A delta counts the unflushed clicks, which also reach the period boards
+    let adjusted = admin_repo.adjust_score(&user.id, ScoreAdjustment::Delta(100), 30, &audit("compensation")).await?;
+    assert_eq!(adjusted.user.total_clicks, 130);
+    assert_eq!(adjusted.clicks_added, 130);
+
+    // Setting a total discards them
+    let adjusted = admin_repo.adjust_score(&user.id, ScoreAdjustment::SetTo(50), 20, &audit("exploit")).await?;
+    assert_eq!(adjusted.user.total_clicks, 50);
+    assert_eq!(adjusted.clicks_added, -80);
+    assert_eq!(user_repo.get_by_id(&user.id).await?.total_clicks, 50);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_rename_user(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("admin_rename");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let renamed = admin_repo
+        .rename_user(&user.id, &Username::new("renamed_user")?, &audit("impersonation"))
+        .await?;
+
+    assert_eq!(renamed.username, "renamed_user");
+    assert_eq!(user_repo.get_by_id(&user.id).await?.username.as_str(), "renamed_user");
+    assert_eq!(audit_actions(&pool, &user.id).await?, vec!["rename"]);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_list_telegram_ids_pages_and_skips_banned(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let admin_repo = AdminRepository::new(pool.clone());
+
+    let mut users = Vec::new();
+    for (telegram_id, name) in [(101, "page_one"), (102, "page_two"), (103, "page_three")] {
+        users.push(user_repo.create_user(telegram_id, name).await?);
+    }
+    admin_repo.ban_user(&users[1].id, &audit("spam")).await?;
+
+    let all = admin_repo.list_telegram_ids(0, 100).await?;
+    assert!(all.contains(&101) && all.contains(&103));
+    assert!(!all.contains(&102));
+
+    let page = admin_repo.list_telegram_ids(0, 1).await?;
+    let next = admin_repo.list_telegram_ids(page[0], 100).await?;
+    assert!(!next.contains(&page[0]));
+    assert_eq!(page.len() + next.len(), all.len());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_admin_actions_on_missing_user(pool: PgPool) -> Result<()> {
+    let admin_repo = AdminRepository::new(pool.clone());
+    let user_id = UserId::new();
+
+    let result = admin_repo.ban_user(&user_id, &audit("missing")).await;
+    assert!(matches!(result, Err(shared::ServiceError::UserNotFound(_))));
+    assert!(audit_actions(&pool, &user_id).await?.is_empty());
+
+    Ok(())
+}
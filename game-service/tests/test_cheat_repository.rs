@@ -0,0 +1,108 @@
+mod common;
+
+use common::create_test_user_data;
+use game_service::domain::{CheatAction, CheatSignal, SuspicionReport};
+use game_service::repository::{CheatRepository, UserRepository};
+use sqlx::PgPool;
+use anyhow::Result;
+
+fn report(score: f64, signals: Vec<CheatSignal>) -> SuspicionReport {
+    SuspicionReport { score, signals }
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_record_detection_creates_row(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let cheat_repo = CheatRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("cheat_create");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    assert_eq!(cheat_repo.get_action(&user.id).await?, None);
+
+    let action = cheat_repo
+        .record_detection(&user.id, &report(0.3, vec![CheatSignal::RegularCadence]), CheatAction::Flag)
+        .await?;
+    assert_eq!(action, CheatAction::Flag);
+    assert_eq!(cheat_repo.get_action(&user.id).await?, Some(CheatAction::Flag));
+
+    let (signals,): (Vec<String>,) = sqlx::query_as("SELECT signals FROM suspicious_users WHERE user_id = $1")
+        .bind(user.id.0)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(signals, vec!["regular_cadence".to_string()]);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_record_detection_never_downgrades(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let cheat_repo = CheatRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("cheat_escalate");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let signals = vec![CheatSignal::LowTimingVariance, CheatSignal::RegularCadence];
+    let action = cheat_repo
+        .record_detection(&user.id, &report(0.6, signals), CheatAction::ShadowBan)
+        .await?;
+    assert_eq!(action, CheatAction::ShadowBan);
+
+    let action = cheat_repo
+        .record_detection(&user.id, &report(0.3, vec![CheatSignal::RegularCadence]), CheatAction::Flag)
+        .await?;
+    assert_eq!(action, CheatAction::ShadowBan);
+
+    let (score,): (f64,) = sqlx::query_as("SELECT score FROM suspicious_users WHERE user_id = $1")
+        .bind(user.id.0)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(score, 0.3);
+
+    let action = cheat_repo
+        .record_detection(&user.id, &report(1.0, vec![CheatSignal::BatchTimestampMismatch]), CheatAction::Freeze)
+        .await?;
+    assert_eq!(action, CheatAction::Freeze);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_hidden_users_leave_the_leaderboard_view(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let cheat_repo = CheatRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("cheat_flagged");
+    let flagged = user_repo.create_user(telegram_id, &username).await?;
+    let (telegram_id, username) = create_test_user_data("cheat_banned");
+    let banned = user_repo.create_user(telegram_id, &username).await?;
+
+    sqlx::query("UPDATE users SET total_clicks = 100 WHERE id = ANY($1)")
+        .bind(vec![flagged.id.0, banned.id.0])
+        .execute(&pool)
+        .await?;
+
+    cheat_repo
+        .record_detection(&flagged.id, &report(0.3, vec![CheatSignal::RegularCadence]), CheatAction::Flag)
+        .await?;
+    cheat_repo
+        .record_detection(&banned.id, &report(0.6, vec![CheatSignal::RegularCadence]), CheatAction::ShadowBan)
+        .await?;
+
+    assert_eq!(cheat_repo.get_hidden_user_ids().await?, vec![banned.id]);
+
+    sqlx::query("REFRESH MATERIALIZED VIEW leaderboard_top_1000")
+        .execute(&pool)
+        .await?;
+
+    let ranked: Vec<(String,)> = sqlx::query_as("SELECT user_id FROM leaderboard_top_1000")
+        .fetch_all(&pool)
+        .await?;
+    let ranked: Vec<String> = ranked.into_iter().map(|(user_id,)| user_id).collect();
+
+    assert!(ranked.contains(&flagged.id.to_string()));
+    assert!(!ranked.contains(&banned.id.to_string()));
+
+    Ok(())
+}
@@ -60,8 +60,8 @@ async fn test_record_clicks_batch(pool: PgPool) -> Result<()> {
     // Create batch of click events
     let events: Vec<ClickEvent> = (0..10)
         .map(|_| ClickEvent {
-            user_id: user.id.clone(),
-            session_id: session.id.clone(),
+            user_id: user.id,
+            session_id: session.id,
             timestamp: Utc::now(),
             count: 1,
         })
@@ -192,7 +192,7 @@ async fn test_cleanup_does_not_affect_recent_clicks(pool: PgPool) -> Result<()>
     let user = user_repo.create_user(telegram_id, &username).await?;
     let session = session_repo.create_session(&user.id, 123456, None).await?;
 
-     Record some clicks
+    // Record some clicks
     for _ in 0..3 {
         click_repo.record_click(&user.id, &session.id).await?;
     }
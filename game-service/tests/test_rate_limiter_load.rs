@@ -0,0 +1,134 @@
+use futures::future::join_all;
+use game_service::domain::RateLimiter;
+use shared::UserId;
+use std::time::{Duration, Instant};
+
+const CONCURRENT_USERS: usize = 200;
+const CHECKS_PER_USER: usize = 5;
+
+fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+
+#[test]
+fn test_rate_limiter_is_shareable_without_a_lock() {
+    assert_shareable::<RateLimiter>();
+}
+
+const BURST_SECS: u32 = 2;
+
+async fn connect_rate_limiter(max_clicks_per_second: u32) -> RateLimiter {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6380".to_string());
+    let client = redis::Client::open(redis_url).expect("Invalid REDIS_URL");
+    let conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect to Redis. Make sure Redis is running.");
+
+    RateLimiter::new(conn, max_clicks_per_second, BURST_SECS)
+}
+
+/// Times each user's checks alone, then all users at once. If checks queued
+/// behind each other, the concurrent run would take about as long as the
+/// sequential one; sharing the limiter lets them overlap on the connection.
+#[tokio::test]
+#[ignore] // Requires Redis
+async fn test_concurrent_users_do_not_queue() {
+    let rate_limiter = connect_rate_limiter(1_000).await;
+    let users: Vec<UserId> = (0..CONCURRENT_USERS).map(|_| UserId::new()).collect();
+
+    let sequential_start = Instant::now();
+    for user_id in &users {
+        for _ in 0..CHECKS_PER_USER {
+            rate_limiter.check_rate_limit(user_id, 1).await.unwrap();
+        }
+    }
+    let sequential = sequential_start.elapsed();
+
+    for user_id in &users {
+        rate_limiter.reset(user_id).await.unwrap();
+    }
+
+    let concurrent_start = Instant::now();
+    let tasks = users.iter().map(|user_id| {
+        let rate_limiter = rate_limiter.clone();
+        let user_id = *user_id;
+        tokio::spawn(async move {
+            for _ in 0..CHECKS_PER_USER {
+                rate_limiter.check_rate_limit(&user_id, 1).await.unwrap();
+            }
+        })
+    });
+    for result in join_all(tasks).await {
+        result.unwrap();
+    }
+    let concurrent = concurrent_start.elapsed();
+
+    assert!(
+        concurrent * 3 < sequential,
+        "Concurrent checks took {:?}, sequential {:?}; checks are still serialized",
+        concurrent,
+        sequential
+    );
+
+    for user_id in &users {
+        rate_limiter.reset(user_id).await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires Redis
+async fn test_limit_still_enforced_under_concurrency() {
+    let rate_limiter = connect_rate_limiter(10).await;
+    let user_id = UserId::new();
+
+    let tasks = (0..50).map(|_| {
+        let rate_limiter = rate_limiter.clone();
+        tokio::spawn(async move { rate_limiter.check_rate_limit(&user_id, 1).await.is_ok() })
+    });
+
+    let accepted = join_all(tasks)
+        .await
+        .into_iter()
+        .filter(|result| *result.as_ref().unwrap())
+        .count();
+
+    // Two seconds of burst; the bucket refills while the checks run, so allow a
+    // little headroom
+    assert!((20..=22).contains(&accepted), "Accepted {} clicks", accepted);
+
+    tokio::time::sleep(Duration::from_millis(1_100)).await;
+    assert!(rate_limiter.check_rate_limit(&user_id, 10).await.is_ok());
+
+    rate_limiter.reset(&user_id).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires Redis
+async fn test_batch_larger_than_bucket_is_not_retryable() {
+    let rate_limiter = connect_rate_limiter(10).await;
+    let user_id = UserId::new();
+
+    let result = rate_limiter.check_rate_limit(&user_id, 21).await;
+    assert!(matches!(result, Err(shared::ServiceError::Validation(_))), "Got {:?}", result);
+
+    // Rejecting it leaves the bucket untouched
+    assert!(rate_limiter.check_rate_limit(&user_id, 20).await.is_ok());
+
+    rate_limiter.reset(&user_id).await.unwrap();
+}
+
+/// A player at the full rate whose next batch went out a second late, after a
+/// reconnect or a retry-after wait, sends two seconds of clicks at once.
+#[tokio::test]
+#[ignore] // Requires Redis
+async fn test_late_batch_over_the_rate_is_not_dropped() {
+    let rate_limiter = connect_rate_limiter(10).await;
+    let user_id = UserId::new();
+
+    assert!(rate_limiter.check_rate_limit(&user_id, 10).await.is_ok());
+
+    tokio::time::sleep(Duration::from_millis(2_000)).await;
+    let result = rate_limiter.check_rate_limit(&user_id, 20).await;
+    assert!(result.is_ok(), "Got {:?}", result);
+
+    rate_limiter.reset(&user_id).await.unwrap();
+}
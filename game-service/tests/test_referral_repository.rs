@@ -0,0 +1,146 @@
+mod common;
+
+use common::create_test_user_data;
+use game_service::repository::{ReferralRepository, UserRepository};
+use shared::UserId;
+use sqlx::PgPool;
+use anyhow::Result;
+
+async fn referral_code(pool: &PgPool, user_id: &UserId) -> Result<String> {
+    let (code,): (String,) = sqlx::query_as("SELECT referral_code FROM users WHERE id = $1")
+        .bind(user_id.0)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(code)
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_record_referral_credits_the_inviter_once(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let referral_repo = ReferralRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("inviter");
+    let inviter = user_repo.create_user(telegram_id, &username).await?;
+    let (telegram_id, username) = create_test_user_data("invitee");
+    let invitee = user_repo.create_user(telegram_id, &username).await?;
+
+    let code = referral_code(&pool, &inviter.id).await?;
+
+    let credited = referral_repo.record_referral(&invitee.id, &code, 1000).await?.unwrap();
+    assert_eq!(credited.user_id, inviter.id);
+    assert_eq!(credited.total_clicks, 1000);
+
+    // A second invite for the same player earns nothing
+    assert!(referral_repo.record_referral(&invitee.id, &code, 1000).await?.is_none());
+    assert_eq!(user_repo.get_by_id(&inviter.id).await?.total_clicks, 1000);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_record_referral_ignores_unknown_and_own_codes(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let referral_repo = ReferralRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("self_referral");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    assert!(referral_repo.record_referral(&user.id, "nosuchcode00", 1000).await?.is_none());
+
+    let own_code = referral_code(&pool, &user.id).await?;
+    assert!(referral_repo.record_referral(&user.id, &own_code, 1000).await?.is_none());
+
+    assert_eq!(user_repo.get_by_id(&user.id).await?.total_clicks, 0);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_create_referred_user_records_the_referral(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let referral_repo = ReferralRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("ref_inviter");
+    let inviter = user_repo.create_user(telegram_id, &username).await?;
+    let code = referral_code(&pool, &inviter.id).await?;
+
+    let (telegram_id, username) = create_test_user_data("ref_invitee");
+    let (invitee, credited) = referral_repo
+        .create_referred_user(telegram_id, &username, &code, 1000)
+        .await?;
+    assert_eq!(credited.unwrap().user_id, inviter.id);
+    assert_eq!(user_repo.get_by_id(&invitee.id).await?.telegram_id, telegram_id);
+
+    // A bad code still creates the user
+    let (telegram_id, username) = create_test_user_data("ref_badcode");
+    let (user, credited) = referral_repo
+        .create_referred_user(telegram_id, &username, "nosuchcode00", 1000)
+        .await?;
+    assert!(credited.is_none());
+    assert_eq!(user_repo.get_by_id(&user.id).await?.total_clicks, 0);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_failed_registration_leaves_no_referral(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let referral_repo = ReferralRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("dup_inviter");
+    let inviter = user_repo.create_user(telegram_id, &username).await?;
+    let code = referral_code(&pool, &inviter.id).await?;
+
+    let (telegram_id, username) = create_test_user_data("dup_invitee");
+    user_repo.create_user(telegram_id, &username).await?;
+
+    let result = referral_repo
+        .create_referred_user(telegram_id, &username, &code, 1000)
+        .await;
+    assert!(matches!(result, Err(shared::ServiceError::UserAlreadyExists(_))), "Got {:?}", result);
+    assert_eq!(user_repo.get_by_id(&inviter.id).await?.total_clicks, 0);
+
+    Ok(())
+}
+
+async fn make_due(pool: &PgPool) -> Result<()> {
+    sqlx::query("UPDATE referrals SET publish_after = NOW() - INTERVAL '1 second'")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_unpublished_bonus_is_claimed_until_published(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let referral_repo = ReferralRepository::new(pool.clone());
+
+    let (telegram_id, username) = create_test_user_data("outbox_inviter");
+    let inviter = user_repo.create_user(telegram_id, &username).await?;
+    let (telegram_id, username) = create_test_user_data("outbox_invitee");
+    let invitee = user_repo.create_user(telegram_id, &username).await?;
+
+    let code = referral_code(&pool, &inviter.id).await?;
+    referral_repo.record_referral(&invitee.id, &code, 1000).await?.unwrap();
+
+    // The inline publish gets a head start before the back-fill steps in
+    assert!(referral_repo.claim_unpublished(60, 10).await?.is_empty());
+
+    make_due(&pool).await?;
+    let claimed = referral_repo.claim_unpublished(60, 10).await?;
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].invitee, invitee.id);
+    assert_eq!(claimed[0].bonus_clicks, 1000);
+    assert_eq!(claimed[0].inviter.total_clicks, 1000);
+
+    // Leased to the first claimant
+    assert!(referral_repo.claim_unpublished(60, 10).await?.is_empty());
+
+    referral_repo.mark_published(&invitee.id).await?;
+    make_due(&pool).await?;
+    assert!(referral_repo.claim_unpublished(60, 10).await?.is_empty());
+
+    Ok(())
+}
@@ -199,7 +199,7 @@ async fn test_cleanup_does_not_affect_recent_sessions(pool: PgPool) -> Result<()
     let user_repo = UserRepository::new(pool.clone());
     let session_repo = SessionRepository::new(pool);
 
-$    let (telegram_id, username) = create_test_user_data("cleanup_recent");
+    let (telegram_id, username) = create_test_user_data("cleanup_recent");
     let user = user_repo.create_user(telegram_id, &username).await?;
     let session = session_repo.create_session(&user.id, 123456, None).await?;
 
@@ -211,3 +211,26 @@ $    let (telegram_id, username) = create_test_user_data("cleanup_recent");
 
     Ok(())
 }
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_end_sessions_only_ends_given_sessions(pool: PgPool) -> Result<()> {
+    let user_repo = UserRepository::new(pool.clone());
+    let session_repo = SessionRepository::new(pool);
+
+    let (telegram_id, username) = create_test_user_data("session_end_many");
+    let user = user_repo.create_user(telegram_id, &username).await?;
+
+    let ended = session_repo.create_session(&user.id, 1, None).await?;
+    let kept = session_repo.create_session(&user.id, 2, None).await?;
+
+    let owners = session_repo.get_active_session_owners().await?;
+    assert!(owners.contains(&(ended.id, user.id)));
+
+    let count = session_repo.end_sessions(&[ended.id]).await?;
+    assert_eq!(count, 1);
+
+    assert!(!session_repo.get_by_id(&ended.id).await?.is_active);
+    assert!(session_repo.get_by_id(&kept.id).await?.is_active);
+
+    Ok(())
+}
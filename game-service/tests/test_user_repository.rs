@@ -3,9 +3,12 @@ mod common;
 
 use common::create_test_user_data;
 use game_service::repository::UserRepository;
+use game_service::service::UserClickBatch;
 use shared::Username;
 use sqlx::PgPool;
 use anyhow::Result;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 #[sqlx::test(migrations = "../migrations")]
 async fn test_create_user_success(pool: PgPool) -> Result<()> {
@@ -115,3 +118,50 @@ async fn test_count_total_users(pool: PgPool) -> Result<()> {
 
     Ok(())
 }
+
+fn click_batch(username: &str, clicks: u32) -> UserClickBatch {
+    UserClickBatch {
+        username: username.to_string(),
+        session_id: None,
+        accumulated_clicks: clicks,
+        last_click_time: chrono::Utc::now(),
+    }
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_apply_click_flush_once(pool: PgPool) -> Result<()> {
+    let repo = UserRepository::new(pool);
+    let (telegram_id, username) = create_test_user_data("flush");
+
+    let user = repo.create_user(telegram_id, &username).await?;
+    let batches = HashMap::from([(user.id.to_string(), click_batch(&username, 7))]);
+    let flush_id = Uuid::new_v4();
+
+    let first = repo.apply_click_flush(flush_id, 0, &batches).await?;
+    assert_eq!(first.unwrap().get(&user.id.to_string()).map(|t| t.total_clicks), Some(7));
+
+    let retried = repo.apply_click_flush(flush_id, 0, &batches).await?;
+    assert!(retried.is_none(), "Replayed flush should not be applied again");
+
+    let final_user = repo.get_by_id(&user.id).await?;
+    assert_eq!(final_user.total_clicks, 7);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_apply_click_flush_distinct_ids(pool: PgPool) -> Result<()> {
+    let repo = UserRepository::new(pool);
+    let (telegram_id, username) = create_test_user_data("flush_twice");
+
+    let user = repo.create_user(telegram_id, &username).await?;
+    let batches = HashMap::from([(user.id.to_string(), click_batch(&username, 3))]);
+
+    let first = repo.apply_click_flush(Uuid::new_v4(), 0, &batches).await?.unwrap();
+    let second = repo.apply_click_flush(Uuid::new_v4(), 0, &batches).await?.unwrap();
+    let (first, second) = (first[&user.id.to_string()], second[&user.id.to_string()]);
+    assert_eq!(second.total_clicks, 6);
+    assert!(second.score_version > first.score_version, "Each flush should bump the score version");
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=../proto/game.proto");
     tonic_prost_build::compile_protos("../proto/game.proto")?;
     Ok(())
 }
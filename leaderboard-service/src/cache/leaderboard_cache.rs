@@ -1,12 +1,293 @@
+use chrono::{DateTime, Utc};
 use redis::aio::ConnectionManager;
-use redis::{AsyncCommands, RedisError};
+use redis::{AsyncCommands, RedisError, Script};
 use shared::errors::{Result, ServiceError};
+use shared::events::{ClickStreamEvent, HIDDEN_USERS_KEY};
 use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 
-const LEADERBOARD_KEY: &str = "leaderboard:global";
-const USER_MEMBER_MAP_KEY: &str = "leaderboard:user_members";
+use super::period::LeaderboardPeriod;
+use super::stats_cache::TOTAL_CLICKS_KEY;
+use crate::ranking::{self, UserStanding};
+
+pub(crate) const LEADERBOARD_KEY: &str = "leaderboard:global";
+/// Hash of user_id -> username, joined onto board pages. Board members are bare
+/// user ids, so a rename only touches this hash.
+const USERNAMES_KEY: &str = "leaderboard:usernames";
+/// Hash of user_id -> Postgres `score_version` of the total on the all-time
+/// board, so an older total delivered late can't overwrite a newer one.
+const SCORE_VERSION_KEY: &str = "leaderboard:score_version";
+/// As `SCORE_VERSION_KEY`, by stream sequence number, for events published
+/// before they carried the score version.
+const APPLIED_SEQ_KEY: &str = "leaderboard:applied_seq";
+/// The `user_id -> "user_id:username"` map used by the old member format; only
+/// read by the migration.
+const LEGACY_MEMBER_MAP_KEY: &str = "leaderboard:user_members";
+/// Version of the board format: 2 once members are bare user ids, 3 once
+/// scores carry the tie-break (see `ranking::board_score`).
+const MEMBER_FORMAT_KEY: &str = "leaderboard:member_format";
+const MEMBER_FORMAT_VERSION: i64 = 3;
+/// A rebuild writes here, then swaps it in, so readers never see a half-built board.
+const REBUILD_KEY: &str = "leaderboard:global:rebuild";
+/// Set while a rebuild stages users. Click events applied meanwhile are staged
+/// too, so the swap doesn't drop them.
+const REBUILD_ACTIVE_KEY: &str = "leaderboard:rebuild_active";
+/// Hash of user_id -> `score_version` of totals staged by click events during a
+/// rebuild, so a page read from Postgres before the event can't overwrite it.
+const REBUILD_VERSIONS_KEY: &str = "leaderboard:global:rebuild:versions";
+/// Held while a rebuild runs so only one instance does it.
+const REBUILD_LOCK_KEY: &str = "leaderboard:rebuild_lock";
 const DEFAULT_LEADERBOARD_LIMIT: i32 = 20;
+const MIGRATION_SCAN_COUNT: usize = 500;
+
+/// Replaces the all-time board with the staged rebuild, or empties it if
+/// nothing was staged, and ends the rebuild.
+///
+/// KEYS[1] = staged board, KEYS[2] = all-time board, KEYS[3] = staged
+/// versions, KEYS[4] = rebuild marker
+const SWAP_REBUILD_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    redis.call('RENAME', KEYS[1], KEYS[2])
+else
+    redis.call('DEL', KEYS[2])
+end
+redis.call('DEL', KEYS[3], KEYS[4])
+return redis.call('ZCARD', KEYS[2])
+";
+
+/// Stages users read from Postgres, skipping anyone hidden meanwhile or whose
+/// newer total a click event has already staged.
+///
+/// KEYS[1] = staged board, KEYS[2] = staged versions, KEYS[3] = usernames hash,
+/// KEYS[4] = hidden users
+/// ARGV = user id, username, board score, score version, repeated
+const STAGE_REBUILD_SCRIPT: &str = r"
+local staged = 0
+for i = 1, #ARGV, 4 do
+    local user = ARGV[i]
+    if redis.call('SISMEMBER', KEYS[4], user) == 0
+        and tonumber(ARGV[i + 3]) >= tonumber(redis.call('HGET', KEYS[2], user) or '0') then
+        redis.call('ZADD', KEYS[1], ARGV[i + 2], user)
+        redis.call('HSET', KEYS[3], user, ARGV[i + 1])
+        staged = staged + 1
+    end
+end
+return staged
+";
+
+/// Releases the rebuild lock only if this instance still holds it.
+///
+/// KEYS[1] = lock, ARGV[1] = owner token
+const RELEASE_LOCK_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// Sets a user's all-time clicks and username, returning their 0-based rank.
+/// The tie-break is only restamped when the click count changes, so a rename
+/// or a replayed total doesn't cost the user their place among equals.
+///
+/// KEYS[1] = all-time board, KEYS[2] = usernames hash
+/// ARGV[1] = user id, ARGV[2] = username, ARGV[3] = clicks, ARGV[4] = tie-break
+/// Scores are `clicks * 2^30 + tie-break`, see `ranking::board_score`.
+const UPDATE_SCORE_SCRIPT: &str = r"
+local scale = 1073741824
+local clicks = tonumber(ARGV[3])
+local current = redis.call('ZSCORE', KEYS[1], ARGV[1])
+if not current or math.floor(tonumber(current) / scale) ~= clicks then
+    redis.call('ZADD', KEYS[1], string.format('%.0f', clicks * scale + tonumber(ARGV[4])), ARGV[1])
+end
+redis.call('HSET', KEYS[2], ARGV[1], ARGV[2])
+return redis.call('ZREVRANK', KEYS[1], ARGV[1])
+";
+
+/// Adds a click delta to each windowed board, restamping the tie-break, and
+/// (re)arms the board's expiry.
+///
+/// KEYS = windowed boards
+/// ARGV[1] = user id, ARGV[2] = delta, ARGV[3] = tie-break,
+/// ARGV[3 + i] = expiry of KEYS[i] as a unix timestamp, or empty for none
+const INCREMENT_PERIOD_SCRIPT: &str = r"
+local scale = 1073741824
+for i, key in ipairs(KEYS) do
+    local current = redis.call('ZSCORE', key, ARGV[1])
+    local clicks = tonumber(ARGV[2])
+    if current then
+        clicks = clicks + math.floor(tonumber(current) / scale)
+    end
+    redis.call('ZADD', key, string.format('%.0f', clicks * scale + tonumber(ARGV[3])), ARGV[1])
+    if ARGV[3 + i] ~= '' then
+        redis.call('EXPIREAT', key, ARGV[3 + i])
+    end
+end
+return 1
+";
+
+/// Applies one click stream entry and acks it in a single step, so a crash or a
+/// second consumer can never apply it twice or apply it without acking. An
+/// entry that is no longer pending was already applied and changes nothing.
+/// Deltas always land on the windowed boards, where a negative one (an admin
+/// taking clicks away) removes at most what the user has, and positive ones
+/// count towards the click counter; the total only replaces the all-time score if its version is at least as new. Events
+/// from before deltas were added count the rise over the board's score, or
+/// nothing if the user isn't on it, as their total isn't one batch. Hidden
+/// users are taken off the boards instead, though their clicks still count.
+///
+/// While a rebuild runs, a total that makes it onto the all-time board is
+/// staged as well, if it carries a score version.
+///
+/// KEYS[1] = stream, KEYS[2] = all-time board, KEYS[3] = usernames hash,
+/// KEYS[4] = hidden users, KEYS[5] = click counter, KEYS[6] = applied versions,
+/// KEYS[7] = rebuild marker, KEYS[8] = staged board, KEYS[9] = staged versions,
+/// KEYS[9 + i] = windowed boards
+/// ARGV[1] = consumer group, ARGV[2] = entry id, ARGV[3] = user id,
+/// ARGV[4] = username, ARGV[5] = total clicks, ARGV[6] = delta or empty,
+/// ARGV[7] = version or empty, ARGV[8] = all-time tie-break,
+/// ARGV[9] = windowed tie-break, ARGV[10] = score version or empty,
+/// ARGV[10 + i] = expiry of KEYS[9 + i] or empty
+/// Returns the 1-based rank (0 if unranked), -1 if hidden, -2 if already
+/// applied, -3 if the total was older than the one on the board.
+const APPLY_CLICK_EVENT_SCRIPT: &str = r"
+local scale = 1073741824
+if #redis.call('XPENDING', KEYS[1], ARGV[1], ARGV[2], ARGV[2], 1) == 0 then
+    return -2
+end
+local user = ARGV[3]
+local total = tonumber(ARGV[5])
+local current = redis.call('ZSCORE', KEYS[2], user)
+local delta = tonumber(ARGV[6])
+local result
+if redis.call('SISMEMBER', KEYS[4], user) == 1 then
+    redis.call('ZREM', KEYS[2], user)
+    redis.call('ZREM', KEYS[8], user)
+    for i = 10, #KEYS do
+        redis.call('ZREM', KEYS[i], user)
+    end
+    delta = delta or 0
+    result = -1
+else
+    if not delta then
+        delta = 0
+        if current then
+            delta = math.max(total - math.floor(tonumber(current) / scale), 0)
+        end
+    end
+    local version = tonumber(ARGV[7])
+    if not version or version >= tonumber(redis.call('HGET', KEYS[6], user) or '0') then
+        if not current or math.floor(tonumber(current) / scale) ~= total then
+            redis.call('ZADD', KEYS[2], string.format('%.0f', total * scale + tonumber(ARGV[8])), user)
+        end
+        redis.call('HSET', KEYS[3], user, ARGV[4])
+        if version then
+            redis.call('HSET', KEYS[6], user, version)
+        end
+        local score_version = tonumber(ARGV[10])
+        if score_version and redis.call('EXISTS', KEYS[7]) == 1
+            and score_version >= tonumber(redis.call('HGET', KEYS[9], user) or '0') then
+            redis.call('ZADD', KEYS[8], redis.call('ZSCORE', KEYS[2], user), user)
+            redis.call('HSET', KEYS[9], user, score_version)
+        end
+        local rank = redis.call('ZREVRANK', KEYS[2], user)
+        result = rank and rank + 1 or 0
+    else
+        result = -3
+    end
+    if delta ~= 0 then
+        for i = 10, #KEYS do
+            local clicks = delta
+            local score = redis.call('ZSCORE', KEYS[i], user)
+            if score then
+                clicks = clicks + math.floor(tonumber(score) / scale)
+            end
+            if clicks > 0 then
+                redis.call('ZADD', KEYS[i], string.format('%.0f', clicks * scale + tonumber(ARGV[9])), user)
+                if ARGV[1 + i] ~= '' then
+                    redis.call('EXPIREAT', KEYS[i], ARGV[1 + i])
+                end
+            else
+                redis.call('ZREM', KEYS[i], user)
+            end
+        end
+    end
+end
+if delta > 0 then
+    redis.call('INCRBY', KEYS[5], delta)
+end
+redis.call('XACK', KEYS[1], ARGV[1], ARGV[2])
+return result
+";
+
+/// Rewrites plain click-count scores from before the tie-break as board scores
+/// with the given tie-break. Scores of 2^28 or more already carry one.
+///
+/// KEYS[1] = board, ARGV[1] = tie-break, ARGV[2..] = members
+const CONVERT_SCORES_SCRIPT: &str = r"
+local scale = 1073741824
+local converted = 0
+for i = 2, #ARGV do
+    local score = redis.call('ZSCORE', KEYS[1], ARGV[i])
+    if score and tonumber(score) < 268435456 then
+        redis.call('ZADD', KEYS[1], string.format('%.0f', tonumber(score) * scale + tonumber(ARGV[1])), ARGV[i])
+        converted = converted + 1
+    end
+end
+return converted
+";
+
+/// Rewrites legacy `user_id:username` members of one board to bare user ids.
+/// All-time scores are absolute, so the highest wins; windowed scores are
+/// partial sums from before a rename, so they are added. The username is only
+/// kept if the hash doesn't already have a newer one.
+///
+/// KEYS[1] = board, KEYS[2] = usernames hash
+/// ARGV[1] = "max" or "sum", ARGV[2..] = legacy members
+const MIGRATE_MEMBERS_SCRIPT: &str = r"
+local moved = 0
+for i = 2, #ARGV do
+    local member = ARGV[i]
+    local score = redis.call('ZSCORE', KEYS[1], member)
+    local sep = string.find(member, ':', 1, true)
+    if score and sep then
+        local user_id = string.sub(member, 1, sep - 1)
+        if ARGV[1] == 'max' then
+            redis.call('ZADD', KEYS[1], 'GT', score, user_id)
+        else
+            redis.call('ZINCRBY', KEYS[1], score, user_id)
+        end
+        redis.call('ZREM', KEYS[1], member)
+        redis.call('HSETNX', KEYS[2], user_id, string.sub(member, sep + 1))
+        moved = moved + 1
+    end
+end
+return moved
+";
+
+/// Where a click stream entry came from, so applying it can ack it too.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamEntry<'a> {
+    pub stream: &'a str,
+    pub group: &'a str,
+    pub id: &'a str,
+}
+
+/// What applying a click stream entry did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppliedClickEvent {
+    /// The total is on the all-time board at this 1-based rank.
+    Ranked(i32),
+    /// Only the delta was applied; the board already has a newer total.
+    Superseded,
+    /// The user is kept off the boards; only the click counter moved.
+    Hidden,
+    /// The entry had been applied and acked before; nothing changed.
+    AlreadyApplied,
+}
+
+/// `(user_id, username, total_clicks, score_reached_at, score_version)` of a
+/// user staged by a rebuild.
+pub type RebuildRow = (String, String, i64, DateTime<Utc>, i64);
 
 #[derive(Debug, Clone)]
 pub struct LeaderboardEntry {
@@ -19,109 +300,222 @@ pub struct LeaderboardEntry {
 #[derive(Clone)]
 pub struct LeaderboardCache {
     redis: Arc<ConnectionManager>,
+    update_score_script: Script,
+    increment_period_script: Script,
+    apply_click_event_script: Script,
+    migrate_members_script: Script,
+    convert_scores_script: Script,
+    swap_rebuild_script: Script,
+    stage_rebuild_script: Script,
+    release_lock_script: Script,
 }
 
 impl LeaderboardCache {
     pub fn new(redis: ConnectionManager) -> Self {
         Self {
             redis: Arc::new(redis),
+            update_score_script: Script::new(UPDATE_SCORE_SCRIPT),
+            increment_period_script: Script::new(INCREMENT_PERIOD_SCRIPT),
+            apply_click_event_script: Script::new(APPLY_CLICK_EVENT_SCRIPT),
+            migrate_members_script: Script::new(MIGRATE_MEMBERS_SCRIPT),
+            convert_scores_script: Script::new(CONVERT_SCORES_SCRIPT),
+            swap_rebuild_script: Script::new(SWAP_REBUILD_SCRIPT),
+            stage_rebuild_script: Script::new(STAGE_REBUILD_SCRIPT),
+            release_lock_script: Script::new(RELEASE_LOCK_SCRIPT),
         }
     }
 
+    /// Sets a user's all-time score and current username, returning their
+    /// 1-based rank. `reached_at` breaks ties if the score changed.
     pub async fn update_score(
         &self,
         user_id: &str,
         username: &str,
         score: i64,
+        reached_at: DateTime<Utc>,
     ) -> Result<i32> {
-        let member = format!("{}:{}", user_id, username);
-
         let mut conn = self.redis.as_ref().clone();
 
-        conn.zadd(LEADERBOARD_KEY, &member, score)
+        let rank: Option<i64> = self
+            .update_score_script
+            .key(LEADERBOARD_KEY)
+            .key(USERNAMES_KEY)
+            .arg(user_id)
+            .arg(username)
+            .arg(score)
+            .arg(ranking::tie_break(reached_at))
+            .invoke_async(&mut conn)
             .await
             .map_err(|e: RedisError| {
                 error!("Failed to update score for user {}: {}", user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        conn.hset(USER_MEMBER_MAP_KEY, user_id, &member)
+        debug!("Updated score for user {} to {}", user_id, score);
+        Ok(rank.map(|r| (r + 1) as i32).unwrap_or(0))
+    }
+
+    pub async fn get_user_rank(&self, user_id: &str) -> Result<i32> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let rank: Option<i64> = conn
+            .zrevrank(LEADERBOARD_KEY, user_id)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to update user member map for user {}: {}", user_id, e);
+                error!("Failed to get rank for user {}: {}", user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        debug!("Updated score for user {} to {}", user_id, score);
-
-        self.get_user_rank(user_id).await
+        Ok(rank.map(|r| (r + 1) as i32).unwrap_or(0))
     }
 
-
-    pub async fn get_user_rank(&self, user_id: &str) -> Result<i32> {
+    pub async fn get_user_score(&self, user_id: &str) -> Result<Option<i64>> {
         let mut conn = self.redis.as_ref().clone();
 
-        let member_name: Option<String> = conn
-            .hget(USER_MEMBER_MAP_KEY, user_id)
+        let score: Option<f64> = conn
+            .zscore(LEADERBOARD_KEY, user_id)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to get member name for user {}: {}", user_id, e);
+                error!("Failed to get score for user {}: {}", user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        let member = match member_name {
-            Some(m) => m,
-            None => {
-                debug!("User {} not found in leaderboard", user_id);
-                return Ok(0);
-            }
-        };
+        Ok(score.map(ranking::clicks_from_board_score))
+    }
 
-        let rank: Option<i64> = conn
-            .zrevrank(LEADERBOARD_KEY, &member)
+    /// Adds a click delta to every windowed board for the window containing `now`,
+    /// (re)arming each set's expiry so finished windows clean themselves up.
+    pub async fn increment_period_scores(
+        &self,
+        user_id: &str,
+        delta: i64,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut invocation = self.increment_period_script.prepare_invoke();
+        invocation.arg(user_id).arg(delta).arg(ranking::tie_break(now));
+
+        for period in LeaderboardPeriod::WINDOWED {
+            invocation.key(period.key_at(now));
+            let expire_at = period.retain_until(now).map(|at| at.timestamp().to_string());
+            invocation.arg(expire_at.unwrap_or_default());
+        }
+
+        let mut conn = self.redis.as_ref().clone();
+        invocation
+            .invoke_async::<()>(&mut conn)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to get rank for user {}: {}", user_id, e);
+                error!("Failed to update period scores for user {}: {}", user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        Ok(rank.map(|r| (r + 1) as i32).unwrap_or(0))
+        debug!("Added {} clicks to period boards for user {}", delta, user_id);
+        Ok(())
     }
 
-    pub async fn get_user_score(&self, user_id: &str) -> Result<Option<i64>> {
-        let mut conn = self.redis.as_ref().clone();
+    /// Applies a click stream entry to every board and the click counter, and
+    /// acks it, atomically. `reached_at` breaks ties on the all-time board and
+    /// `now` picks the windows the delta counts towards.
+    pub async fn apply_click_event(
+        &self,
+        entry: StreamEntry<'_>,
+        event: &ClickStreamEvent,
+        reached_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<AppliedClickEvent> {
+        // Postgres versions follow commit order; stream seqs only publish order
+        let (versions_key, version) = match event.score_version {
+            Some(version) => (SCORE_VERSION_KEY, Some(version)),
+            None => (APPLIED_SEQ_KEY, event.seq.map(|seq| seq as i64)),
+        };
+
+        let mut invocation = self.apply_click_event_script.prepare_invoke();
+        invocation
+            .key(entry.stream)
+            .key(LEADERBOARD_KEY)
+            .key(USERNAMES_KEY)
+            .key(HIDDEN_USERS_KEY)
+            .key(TOTAL_CLICKS_KEY)
+            .key(versions_key)
+            .key(REBUILD_ACTIVE_KEY)
+            .key(REBUILD_KEY)
+            .key(REBUILD_VERSIONS_KEY)
+            .arg(entry.group)
+            .arg(entry.id)
+            .arg(&event.user_id)
+            .arg(&event.username)
+            .arg(event.total_clicks)
+            .arg(event.delta.map(|delta| delta.to_string()).unwrap_or_default())
+            .arg(version.map(|version| version.to_string()).unwrap_or_default())
+            .arg(ranking::tie_break(reached_at))
+            .arg(ranking::tie_break(now))
+            .arg(event.score_version.map(|version| version.to_string()).unwrap_or_default());
+
+        for period in LeaderboardPeriod::WINDOWED {
+            invocation.key(period.key_at(now));
+            let expire_at = period.retain_until(now).map(|at| at.timestamp().to_string());
+            invocation.arg(expire_at.unwrap_or_default());
+        }
 
-        let member_name: Option<String> = conn
-            .hget(USER_MEMBER_MAP_KEY, user_id)
+        let mut conn = self.redis.as_ref().clone();
+        let result: i64 = invocation
+            .invoke_async(&mut conn)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to get member name for user {}: {}", user_id, e);
+                error!("Failed to apply click event {} for user {}: {}", entry.id, event.user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        let member = match member_name {
-            Some(m) => m,
-            None => {
-                debug!("User {} not found in leaderboard", user_id);
-                return Ok(None);
-            }
-        };
+        Ok(match result {
+            -1 => AppliedClickEvent::Hidden,
+            -2 => AppliedClickEvent::AlreadyApplied,
+            -3 => AppliedClickEvent::Superseded,
+            rank => AppliedClickEvent::Ranked(rank as i32),
+        })
+    }
 
-        let score: Option<i64> = conn
-            .zscore(LEADERBOARD_KEY, &member)
+    /// Rank (1-based) and score of a user on a period board, `None` if the user
+    /// has no clicks in the current window.
+    /// ZREVRANK is O(log N), so this is as cheap at rank 1,000,000 as at rank 1.
+    pub async fn get_period_user_rank(
+        &self,
+        period: LeaderboardPeriod,
+        user_id: &str,
+    ) -> Result<Option<UserStanding>> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let key = period.key_at(Utc::now());
+        let (rank, score, ranked_users): (Option<i64>, Option<f64>, i64) = redis::pipe()
+            .zrevrank(&key, user_id)
+            .zscore(&key, user_id)
+            .zcard(&key)
+            .query_async(&mut conn)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to get score for user {}: {}", user_id, e);
+                error!("Failed to get {:?} rank for user {}: {}", period, user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        Ok(score)
+        Ok(rank.zip(score).map(|(rank, score)| UserStanding {
+            rank: (rank + 1) as i32,
+            total_clicks: ranking::clicks_from_board_score(score),
+            ranked_users,
+        }))
     }
 
     pub async fn get_leaderboard(
         &self,
         limit: Option<i32>,
         offset: Option<i32>,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        self.get_period_leaderboard(LeaderboardPeriod::AllTime, limit, offset).await
+    }
+
+    pub async fn get_period_leaderboard(
+        &self,
+        period: LeaderboardPeriod,
+        limit: Option<i32>,
+        offset: Option<i32>,
     ) -> Result<Vec<LeaderboardEntry>> {
         let limit = limit.unwrap_or(DEFAULT_LEADERBOARD_LIMIT);
         let offset = offset.unwrap_or(0);
@@ -131,38 +525,86 @@ impl LeaderboardCache {
         let start = offset as isize;
         let end = (offset + limit - 1) as isize;
 
-        let entries: Vec<(String, i64)> = conn
-            .zrevrange_withscores(LEADERBOARD_KEY, start, end)
+        let entries: Vec<(String, f64)> = conn
+            .zrevrange_withscores(period.key_at(Utc::now()), start, end)
             .await
             .map_err(|e: RedisError| {
                 error!("Failed to get leaderboard: {}", e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        let mut result = Vec::with_capacity(entries.len());
-        for (rank_idx, (member, score)) in entries.iter().enumerate() {
-            let parts: Vec<&str> = member.splitn(2, ':').collect();
-            if parts.len() == 2 {
-                result.push(LeaderboardEntry {
-                    rank: (offset + rank_idx as i32 + 1),
-                    user_id: parts[0].to_string(),
-                    username: parts[1].to_string(),
-                    total_clicks: *score,
-                });
-            } else {
-                warn!("Invalid member format in leaderboard: {}", member);
-            }
+        if entries.is_empty() {
+            return Ok(Vec::new());
         }
 
+        let user_ids: Vec<&str> = entries.iter().map(|(user_id, _)| user_id.as_str()).collect();
+        let usernames: Vec<Option<String>> = redis::cmd("HMGET")
+            .arg(USERNAMES_KEY)
+            .arg(&user_ids)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to get leaderboard usernames: {}", e);
+                ServiceError::Redis(e.to_string())
+            })?;
+
+        let result: Vec<LeaderboardEntry> = entries
+            .into_iter()
+            .zip(usernames)
+            .enumerate()
+            .map(|(rank_idx, ((user_id, score), username))| {
+                let username = username.unwrap_or_else(|| {
+                    warn!("No username cached for leaderboard user {}", user_id);
+                    String::new()
+                });
+
+                LeaderboardEntry {
+                    rank: offset + rank_idx as i32 + 1,
+                    user_id,
+                    username,
+                    total_clicks: ranking::clicks_from_board_score(score),
+                }
+            })
+            .collect();
+
         debug!("Retrieved {} leaderboard entries", result.len());
         Ok(result)
     }
 
+    /// The user's entry with up to `radius` entries on either side, or `None`
+    /// if the user isn't on the board.
+    pub async fn get_period_leaderboard_around(
+        &self,
+        period: LeaderboardPeriod,
+        user_id: &str,
+        radius: i32,
+    ) -> Result<Option<Vec<LeaderboardEntry>>> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let rank: Option<i64> = conn
+            .zrevrank(period.key_at(Utc::now()), user_id)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to get {:?} rank for user {}: {}", period, user_id, e);
+                ServiceError::Redis(e.to_string())
+            })?;
+        let Some(rank) = rank else {
+            return Ok(None);
+        };
+
+        let (offset, limit) = window_around(rank as i32, radius);
+        self.get_period_leaderboard(period, Some(limit), Some(offset)).await.map(Some)
+    }
+
     pub async fn get_total_count(&self) -> Result<i64> {
+        self.get_period_total_count(LeaderboardPeriod::AllTime).await
+    }
+
+    pub async fn get_period_total_count(&self, period: LeaderboardPeriod) -> Result<i64> {
         let mut conn = self.redis.as_ref().clone();
 
         let count: i64 = conn
-            .zcard(LEADERBOARD_KEY)
+            .zcard(period.key_at(Utc::now()))
             .await
             .map_err(|e: RedisError| {
                 error!("Failed to get leaderboard count: {}", e);
@@ -172,39 +614,361 @@ impl LeaderboardCache {
         Ok(count)
     }
 
+    /// Takes a user off every board and forgets their username.
     pub async fn remove_user(&self, user_id: &str) -> Result<bool> {
+        let mut pipe = redis::pipe();
+        pipe.atomic().zrem(LEADERBOARD_KEY, user_id);
+        for key in windowed_keys(Utc::now()) {
+            pipe.zrem(key, user_id).ignore();
+        }
+        pipe.hdel(USERNAMES_KEY, user_id).ignore();
+
+        let mut conn = self.redis.as_ref().clone();
+        let (removed,): (i32,) = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to remove user {}: {}", user_id, e);
+                ServiceError::Redis(e.to_string())
+            })?;
+
+        if removed > 0 {
+            info!("Removed user {} from leaderboard", user_id);
+        }
+        Ok(removed > 0)
+    }
+
+    /// Whether game-service has shadow-banned or frozen the user.
+    pub async fn is_hidden(&self, user_id: &str) -> Result<bool> {
         let mut conn = self.redis.as_ref().clone();
 
-        let members: Vec<String> = conn
-            .zrevrange(LEADERBOARD_KEY, 0, -1)
+        conn.sismember(HIDDEN_USERS_KEY, user_id)
             .await
             .map_err(|e: RedisError| {
-                error!("Failed to get members for removal: {}", e);
+                error!("Failed to check hidden users for {}: {}", user_id, e);
+                ServiceError::Redis(e.to_string())
+            })
+    }
+
+    /// Takes a user off the all-time board and the current windowed boards.
+    pub async fn hide_user(&self, user_id: &str, now: DateTime<Utc>) -> Result<()> {
+        let mut pipe = redis::pipe();
+        pipe.zrem(LEADERBOARD_KEY, user_id).ignore();
+        for key in windowed_keys(now) {
+            pipe.zrem(key, user_id).ignore();
+        }
+
+        let mut conn = self.redis.as_ref().clone();
+        pipe.query_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to hide user {} from leaderboard: {}", user_id, e);
                 ServiceError::Redis(e.to_string())
             })?;
 
-        for member in members {
-            if member.starts_with(&format!("{}:", user_id)) {
-                let removed: i32 = conn
-                    .zrem(LEADERBOARD_KEY, &member)
+        debug!("Hid user {} from leaderboard", user_id);
+        Ok(())
+    }
+
+    /// Brings the boards up to the current format: first from the old
+    /// `user_id:username` members to bare user ids, with usernames moved into
+    /// their own hash, then from plain click counts to tie-breaking board
+    /// scores. Safe to rerun and to run from several instances at once;
+    /// returns the number of members rewritten.
+    pub async fn migrate_member_format(&self) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let version: Option<i64> = conn
+            .get(MEMBER_FORMAT_KEY)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+        let version = version.unwrap_or(0);
+        if version >= MEMBER_FORMAT_VERSION {
+            return Ok(0);
+        }
+
+        let mut migrated = 0;
+        if version < 2 {
+            migrated += self.migrate_to_user_id_members().await?;
+        }
+        migrated += self.convert_scores().await?;
+
+        redis::pipe()
+            .del(LEGACY_MEMBER_MAP_KEY)
+            .ignore()
+            .set(MEMBER_FORMAT_KEY, MEMBER_FORMAT_VERSION)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+        info!("Migrated {} leaderboard members", migrated);
+        Ok(migrated)
+    }
+
+    async fn migrate_to_user_id_members(&self) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+
+        info!("Migrating leaderboard members to user id format...");
+
+        // The member map has each user's latest username, so it goes in first and
+        // stale names from leftover rename members can't overwrite it
+        let mut cursor = 0u64;
+        loop {
+            let (next, members): (u64, Vec<(String, String)>) = redis::cmd("HSCAN")
+                .arg(LEGACY_MEMBER_MAP_KEY)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(MIGRATION_SCAN_COUNT)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            let mut pipe = redis::pipe();
+            for (user_id, member) in &members {
+                if let Some((_, username)) = member.split_once(':') {
+                    pipe.hset_nx(USERNAMES_KEY, user_id, username).ignore();
+                }
+            }
+            pipe.query_async::<()>(&mut conn)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        let mut migrated = 0;
+        migrated += self.migrate_board(LEADERBOARD_KEY, "max").await?;
+        for key in windowed_keys(Utc::now()) {
+            migrated += self.migrate_board(&key, "sum").await?;
+        }
+
+        Ok(migrated)
+    }
+
+    /// Scores from before the tie-break have no record of when they were
+    /// reached, so they all get the migration time; a rebuild from Postgres
+    /// restores the real order on the all-time board.
+    async fn convert_scores(&self) -> Result<usize> {
+        info!("Converting leaderboard scores to tie-breaking board scores...");
+
+        let tie_break = ranking::tie_break(Utc::now());
+        let mut converted = self.convert_board_scores(LEADERBOARD_KEY, tie_break).await?;
+        for key in windowed_keys(Utc::now()) {
+            converted += self.convert_board_scores(&key, tie_break).await?;
+        }
+
+        Ok(converted)
+    }
+
+    async fn convert_board_scores(&self, key: &str, tie_break: i64) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+        let mut converted = 0;
+        let mut cursor = 0u64;
+
+        loop {
+            let (next, entries): (u64, Vec<(String, String)>) = redis::cmd("ZSCAN")
+                .arg(key)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(MIGRATION_SCAN_COUNT)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            if !entries.is_empty() {
+                let members: Vec<&str> = entries.iter().map(|(member, _)| member.as_str()).collect();
+                let count: usize = self
+                    .convert_scores_script
+                    .key(key)
+                    .arg(tie_break)
+                    .arg(&members)
+                    .invoke_async(&mut conn)
+                    .await
+                    .map_err(|e: RedisError| {
+                        error!("Failed to convert scores of {}: {}", key, e);
+                        ServiceError::Redis(e.to_string())
+                    })?;
+                converted += count;
+            }
+
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        Ok(converted)
+    }
+
+    async fn migrate_board(&self, key: &str, merge: &str) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+        let mut migrated = 0;
+        let mut cursor = 0u64;
+
+        loop {
+            let (next, entries): (u64, Vec<(String, String)>) = redis::cmd("ZSCAN")
+                .arg(key)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(MIGRATION_SCAN_COUNT)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            let legacy: Vec<&str> = entries
+                .iter()
+                .map(|(member, _)| member.as_str())
+                .filter(|member| member.contains(':'))
+                .collect();
+
+            if !legacy.is_empty() {
+                let moved: usize = self
+                    .migrate_members_script
+                    .key(key)
+                    .key(USERNAMES_KEY)
+                    .arg(merge)
+                    .arg(&legacy)
+                    .invoke_async(&mut conn)
                     .await
                     .map_err(|e: RedisError| {
-                        error!("Failed to remove user {}: {}", user_id, e);
+                        error!("Failed to migrate members of {}: {}", key, e);
                         ServiceError::Redis(e.to_string())
                     })?;
+                migrated += moved;
+            }
 
-                info!("Removed user {} from leaderboard", user_id);
-                return Ok(removed > 0);
+            cursor = next;
+            if cursor == 0 {
+                break;
             }
         }
 
-        Ok(false)
+        Ok(migrated)
+    }
+
+    /// Takes the rebuild lock for `ttl` unless another instance holds it.
+    pub async fn try_lock_rebuild(&self, owner: &str, ttl: std::time::Duration) -> Result<bool> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(REBUILD_LOCK_KEY)
+            .arg(owner)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+        Ok(acquired.is_some())
+    }
+
+    /// Extends the rebuild lock, and the marker that has click events staged.
+    pub async fn extend_rebuild_lock(&self, ttl: std::time::Duration) -> Result<()> {
+        let mut conn = self.redis.as_ref().clone();
+
+        redis::pipe()
+            .pexpire(REBUILD_LOCK_KEY, ttl.as_millis() as i64)
+            .ignore()
+            .pexpire(REBUILD_ACTIVE_KEY, ttl.as_millis() as i64)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))
+    }
+
+    pub async fn release_rebuild_lock(&self, owner: &str) -> Result<()> {
+        let mut conn = self.redis.as_ref().clone();
+
+        self.release_lock_script
+            .key(REBUILD_LOCK_KEY)
+            .arg(owner)
+            .invoke_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))
+    }
+
+    /// Drops anything left staged by an interrupted rebuild and starts staging
+    /// click events for `ttl`, renewed by `extend_rebuild_lock`.
+    pub async fn begin_rebuild(&self, ttl: std::time::Duration) -> Result<()> {
+        let mut conn = self.redis.as_ref().clone();
+
+        redis::pipe()
+            .del(&[REBUILD_KEY, REBUILD_VERSIONS_KEY])
+            .ignore()
+            .pset_ex(REBUILD_ACTIVE_KEY, 1, ttl.as_millis() as u64)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))
+    }
+
+    /// Stops staging and drops what was staged, after a rebuild failed.
+    pub async fn abort_rebuild(&self) -> Result<()> {
+        let mut conn = self.redis.as_ref().clone();
+
+        conn.del::<_, ()>(&[REBUILD_ACTIVE_KEY, REBUILD_KEY, REBUILD_VERSIONS_KEY])
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))
+    }
+
+    /// Stages `(user_id, username, total_clicks, score_reached_at, score_version)`
+    /// rows for the next swap, returning how many were staged. Usernames go
+    /// straight to the live hash; they are the same either way.
+    pub async fn stage_rebuild_page(&self, users: &[RebuildRow]) -> Result<i64> {
+        if users.is_empty() {
+            return Ok(0);
+        }
+
+        let mut invocation = self.stage_rebuild_script.prepare_invoke();
+        invocation
+            .key(REBUILD_KEY)
+            .key(REBUILD_VERSIONS_KEY)
+            .key(USERNAMES_KEY)
+            .key(HIDDEN_USERS_KEY);
+        for (user_id, username, total_clicks, reached_at, score_version) in users {
+            invocation
+                .arg(user_id)
+                .arg(username)
+                .arg(ranking::board_score(*total_clicks, *reached_at))
+                .arg(score_version);
+        }
+
+        let mut conn = self.redis.as_ref().clone();
+        invocation
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to stage leaderboard rebuild page: {}", e);
+                ServiceError::Redis(e.to_string())
+            })
+    }
+
+    /// Swaps the staged board in as the all-time board, returning its size.
+    pub async fn finish_rebuild(&self) -> Result<i64> {
+        let mut conn = self.redis.as_ref().clone();
+
+        self.swap_rebuild_script
+            .key(REBUILD_KEY)
+            .key(LEADERBOARD_KEY)
+            .key(REBUILD_VERSIONS_KEY)
+            .key(REBUILD_ACTIVE_KEY)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to swap in rebuilt leaderboard: {}", e);
+                ServiceError::Redis(e.to_string())
+            })
     }
 
     pub async fn clear(&self) -> Result<()> {
         let mut conn = self.redis.as_ref().clone();
 
-        conn.del(LEADERBOARD_KEY)
+        conn.del::<_, ()>(LEADERBOARD_KEY)
             .await
             .map_err(|e: RedisError| {
                 error!("Failed to clear leaderboard: {}", e);
@@ -215,3 +979,174 @@ impl LeaderboardCache {
         Ok(())
     }
 }
+
+/// `(offset, limit)` of the page holding `radius` entries either side of the
+/// 0-based `position`, cut short at the top of the board.
+pub(crate) fn window_around(position: i32, radius: i32) -> (i32, i32) {
+    let offset = (position - radius).max(0);
+    (offset, position - offset + radius + 1)
+}
+
+fn windowed_keys(now: DateTime<Utc>) -> impl Iterator<Item = String> {
+    LeaderboardPeriod::WINDOWED.iter().map(move |period| period.key_at(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_around() {
+        assert_eq!(window_around(10, 2), (8, 5));
+        assert_eq!(window_around(1, 2), (0, 4));
+        assert_eq!(window_around(0, 0), (0, 1));
+    }
+
+    #[test]
+    fn test_scripts_use_the_board_score_scale() {
+        let scale = format!("local scale = {}", ranking::CLICK_SCALE as u64);
+        for script in [
+            UPDATE_SCORE_SCRIPT,
+            INCREMENT_PERIOD_SCRIPT,
+            APPLY_CLICK_EVENT_SCRIPT,
+            CONVERT_SCORES_SCRIPT,
+        ] {
+            assert!(script.contains(&scale));
+        }
+    }
+
+    async fn test_cache() -> LeaderboardCache {
+        let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6380".to_string());
+        let client = redis::Client::open(redis_url).expect("Invalid REDIS_URL");
+        LeaderboardCache::new(ConnectionManager::new(client).await.unwrap())
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_rename_keeps_single_entry() {
+        let cache = test_cache().await;
+        let user_id = "rename-test-user";
+        cache.remove_user(user_id).await.unwrap();
+
+        cache.update_score(user_id, "OldName", 10, Utc::now()).await.unwrap();
+        cache.increment_period_scores(user_id, 10, Utc::now()).await.unwrap();
+        cache.update_score(user_id, "NewName", 15, Utc::now()).await.unwrap();
+
+        let entries: Vec<LeaderboardEntry> = cache
+            .get_leaderboard(Some(1000), None)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.user_id == user_id)
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].username, "NewName");
+        assert_eq!(entries[0].total_clicks, 15);
+        assert_eq!(
+            cache.get_period_user_rank(LeaderboardPeriod::Daily, user_id).await.unwrap().map(|s| s.total_clicks),
+            Some(10)
+        );
+
+        assert!(cache.remove_user(user_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_remove_user_clears_username() {
+        let cache = test_cache().await;
+        let user_id = "remove-test-user";
+
+        cache.update_score(user_id, "Gone", 5, Utc::now()).await.unwrap();
+        assert!(cache.remove_user(user_id).await.unwrap());
+
+        assert_eq!(cache.get_user_rank(user_id).await.unwrap(), 0);
+        assert_eq!(cache.get_user_score(user_id).await.unwrap(), None);
+        assert!(!cache.remove_user(user_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_migrate_member_format() {
+        let cache = test_cache().await;
+        let mut conn = cache.redis.as_ref().clone();
+        let user_id = "migrate-test-user";
+        cache.remove_user(user_id).await.unwrap();
+
+        // A user renamed under the old format: a stale member plus the current one
+        redis::pipe()
+            .del(MEMBER_FORMAT_KEY)
+            .zadd(LEADERBOARD_KEY, format!("{}:Before", user_id), 7)
+            .zadd(LEADERBOARD_KEY, format!("{}:After", user_id), 12)
+            .hset(LEGACY_MEMBER_MAP_KEY, user_id, format!("{}:After", user_id))
+            .query_async::<()>(&mut conn)
+            .await
+            .unwrap();
+
+        assert!(cache.migrate_member_format().await.unwrap() >= 2);
+        assert_eq!(cache.migrate_member_format().await.unwrap(), 0);
+
+        let entries: Vec<LeaderboardEntry> = cache
+            .get_leaderboard(Some(1000), None)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.user_id == user_id)
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].username, "After");
+        assert_eq!(entries[0].total_clicks, 12);
+
+        cache.remove_user(user_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_rebuild_swaps_in_staged_board() {
+        let cache = test_cache().await;
+        let owner = "rebuild-test";
+
+        assert!(cache.try_lock_rebuild(owner, std::time::Duration::from_secs(5)).await.unwrap());
+        assert!(!cache.try_lock_rebuild("other", std::time::Duration::from_secs(5)).await.unwrap());
+
+        cache.begin_rebuild(std::time::Duration::from_secs(5)).await.unwrap();
+        cache
+            .stage_rebuild_page(&[
+                ("rebuild-a".to_string(), "Alice".to_string(), 30, Utc::now(), 1),
+                ("rebuild-b".to_string(), "Bob".to_string(), 20, Utc::now(), 1),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(cache.finish_rebuild().await.unwrap(), 2);
+
+        let entries = cache.get_leaderboard(Some(10), None).await.unwrap();
+        assert_eq!(entries[0].username, "Alice");
+        assert_eq!(entries[1].total_clicks, 20);
+
+        cache.release_rebuild_lock(owner).await.unwrap();
+        assert!(cache.try_lock_rebuild("other", std::time::Duration::from_secs(5)).await.unwrap());
+        cache.release_rebuild_lock("other").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_ties_go_to_earliest_and_survive_unchanged_updates() {
+        let cache = test_cache().await;
+        let (early, late) = ("tie-test-early", "tie-test-late");
+        let start = Utc::now() - chrono::Duration::minutes(5);
+
+        cache.update_score(late, "Late", 50, start + chrono::Duration::seconds(30)).await.unwrap();
+        cache.update_score(early, "Early", 50, start).await.unwrap();
+        assert_eq!(cache.get_user_rank(early).await.unwrap() + 1, cache.get_user_rank(late).await.unwrap());
+
+        // Same total reported again, as on a rename or a replay, keeps the old time
+        cache.update_score(early, "Renamed", 50, Utc::now()).await.unwrap();
+        assert_eq!(cache.get_user_rank(early).await.unwrap() + 1, cache.get_user_rank(late).await.unwrap());
+        assert_eq!(cache.get_user_score(early).await.unwrap(), Some(50));
+
+        cache.update_score(late, "Late", 51, Utc::now()).await.unwrap();
+        assert_eq!(cache.get_user_rank(late).await.unwrap() + 1, cache.get_user_rank(early).await.unwrap());
+
+        cache.remove_user(early).await.unwrap();
+        cache.remove_user(late).await.unwrap();
+    }
+}
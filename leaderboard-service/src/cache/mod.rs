@@ -1,5 +1,7 @@
 pub mod leaderboard_cache;
+pub mod period;
 pub mod stats_cache;
 
-pub use leaderboard_cache::LeaderboardCache;
+pub use leaderboard_cache::{AppliedClickEvent, LeaderboardCache, StreamEntry};
+pub use period::LeaderboardPeriod;
 pub use stats_cache::StatsCache;
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+
+use super::leaderboard_cache::LEADERBOARD_KEY;
+
+const PERIOD_KEY_PREFIX: &str = "leaderboard:";
+
+/// Time window a leaderboard covers. Windowed boards live in their own sorted
+/// set per window (e.g. `leaderboard:weekly:2025-W07`) and are fed click deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaderboardPeriod {
+    AllTime,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl LeaderboardPeriod {
+    pub const WINDOWED: [LeaderboardPeriod; 3] = [Self::Daily, Self::Weekly, Self::Monthly];
+
+    pub fn is_windowed(&self) -> bool {
+        *self != Self::AllTime
+    }
+
+    pub fn key_at(&self, now: DateTime<Utc>) -> String {
+        match self {
+            Self::AllTime => LEADERBOARD_KEY.to_string(),
+            Self::Daily => format!("{}daily:{}", PERIOD_KEY_PREFIX, now.format("%Y-%m-%d")),
+            Self::Weekly => {
+                let week = now.iso_week();
+                format!("{}weekly:{}-W{:02}", PERIOD_KEY_PREFIX, week.year(), week.week())
+            }
+            Self::Monthly => format!("{}monthly:{}", PERIOD_KEY_PREFIX, now.format("%Y-%m")),
+        }
+    }
+
+    /// Start of the window following the one containing `now`.
+    pub fn window_end(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.date_naive();
+
+        let end = match self {
+            Self::AllTime => return None,
+            Self::Daily => today + Duration::days(1),
+            Self::Weekly => today + Duration::days(7 - today.weekday().num_days_from_monday() as i64),
+            Self::Monthly => first_of_next_month(today),
+        };
+
+        Some(Utc.from_utc_datetime(&end.and_hms_opt(0, 0, 0)?))
+    }
+
+    /// When the window's sorted set should expire. A finished window stays readable
+    /// for one more period so last week's results can still be announced.
+    pub fn retain_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let end = self.window_end(now)?;
+        self.window_end(end)
+    }
+}
+
+fn first_of_next_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+
+    NaiveDate::from_ymd_opt(year, month, 1).expect("first day of month is always valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_period_keys() {
+        let now = at("2025-02-13T15:30:00Z");
+
+        assert_eq!(LeaderboardPeriod::AllTime.key_at(now), "leaderboard:global");
+        assert_eq!(LeaderboardPeriod::Daily.key_at(now), "leaderboard:daily:2025-02-13");
+        assert_eq!(LeaderboardPeriod::Weekly.key_at(now), "leaderboard:weekly:2025-W07");
+        assert_eq!(LeaderboardPeriod::Monthly.key_at(now), "leaderboard:monthly:2025-02");
+    }
+
+    #[test]
+    fn test_weekly_key_uses_iso_year() {
+        let now = at("2024-12-30T08:00:00Z");
+        assert_eq!(LeaderboardPeriod::Weekly.key_at(now), "leaderboard:weekly:2025-W01");
+    }
+
+    #[test]
+    fn test_window_end() {
+        let now = at("2025-02-13T15:30:00Z"); // Thursday
+
+        assert_eq!(LeaderboardPeriod::AllTime.window_end(now), None);
+        assert_eq!(LeaderboardPeriod::Daily.window_end(now), Some(at("2025-02-14T00:00:00Z")));
+        assert_eq!(LeaderboardPeriod::Weekly.window_end(now), Some(at("2025-02-17T00:00:00Z")));
+        assert_eq!(LeaderboardPeriod::Monthly.window_end(now), Some(at("2025-03-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_window_end_on_boundaries() {
+        let monday = at("2025-02-17T00:00:00Z");
+        assert_eq!(LeaderboardPeriod::Weekly.window_end(monday), Some(at("2025-02-24T00:00:00Z")));
+
+        let december = at("2025-12-31T23:59:59Z");
+        assert_eq!(LeaderboardPeriod::Monthly.window_end(december), Some(at("2026-01-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_retain_until_keeps_one_extra_window() {
+        let now = at("2025-02-13T15:30:00Z");
+
+        assert_eq!(LeaderboardPeriod::Daily.retain_until(now), Some(at("2025-02-15T00:00:00Z")));
+        assert_eq!(LeaderboardPeriod::Monthly.retain_until(now), Some(at("2025-04-01T00:00:00Z")));
+    }
+}
@@ -4,7 +4,7 @@ use shared::errors::{Result, ServiceError};
 use std::sync::Arc;
 use tracing::{debug, error};
 
-const TOTAL_CLICKS_KEY: &str = "stats:total_clicks";
+pub(crate) const TOTAL_CLICKS_KEY: &str = "stats:total_clicks";
 const TOTAL_USERS_KEY: &str = "stats:total_users";
 const ACTIVE_SESSIONS_KEY: &str = "stats:active_sessions";
 
@@ -42,7 +42,7 @@ impl StatsCache {
             })?;
 
         let stats = GlobalStats {
-            total_clicks: values.get(0).and_then(|v| *v).unwrap_or(0),
+            total_clicks: values.first().and_then(|v| *v).unwrap_or(0),
             total_users: values.get(1).and_then(|v| *v).unwrap_or(0),
             active_sessions: values.get(2).and_then(|v| *v).unwrap_or(0),
         };
@@ -137,6 +137,28 @@ impl StatsCache {
         Ok(())
     }
 
+    /// Overwrites every stat, as when rebuilding them from Postgres.
+    pub async fn set_global_stats(&self, stats: &GlobalStats) -> Result<()> {
+        let mut conn = self.redis.as_ref().clone();
+
+        redis::pipe()
+            .set(TOTAL_CLICKS_KEY, stats.total_clicks)
+            .ignore()
+            .set(TOTAL_USERS_KEY, stats.total_users)
+            .ignore()
+            .set(ACTIVE_SESSIONS_KEY, stats.active_sessions)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .map_err(|e: RedisError| {
+                error!("Failed to set global stats: {}", e);
+                ServiceError::Redis(e.to_string())
+            })?;
+
+        debug!("Set global stats to {:?}", stats);
+        Ok(())
+    }
+
     pub async fn reset_all(&self) -> Result<()> {
         let mut conn = self.redis.as_ref().clone();
 
@@ -0,0 +1,44 @@
+use crate::rebuild::LeaderboardRebuilder;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use super::leaderboard_server::game::leaderboard_admin_service_server::LeaderboardAdminService;
+use super::leaderboard_server::game::{RebuildLeaderboardRequest, RebuildLeaderboardResponse};
+
+#[derive(Clone)]
+pub struct LeaderboardAdminServerImpl {
+    rebuilder: LeaderboardRebuilder,
+}
+
+impl LeaderboardAdminServerImpl {
+    pub fn new(rebuilder: LeaderboardRebuilder) -> Self {
+        Self { rebuilder }
+    }
+}
+
+#[tonic::async_trait]
+impl LeaderboardAdminService for LeaderboardAdminServerImpl {
+    async fn rebuild_leaderboard(
+        &self,
+        request: Request<RebuildLeaderboardRequest>,
+    ) -> Result<Response<RebuildLeaderboardResponse>, Status> {
+        let actor = request.into_inner().actor;
+        if actor.trim().is_empty() {
+            return Err(Status::invalid_argument("actor is required"));
+        }
+
+        info!(actor = %actor, "RebuildLeaderboard requested");
+        let started = self.rebuilder.start(&actor).await?;
+
+        let message = if started {
+            "Rebuild started"
+        } else {
+            "A rebuild is already running"
+        };
+
+        Ok(Response::new(RebuildLeaderboardResponse {
+            started,
+            message: message.to_string(),
+        }))
+    }
+}
@@ -1,7 +1,16 @@
+use crate::cache::leaderboard_cache::window_around;
+use crate::cache::{LeaderboardCache, LeaderboardPeriod};
+use crate::ranking::UserStanding;
 use crate::repository::LeaderboardRepository;
+use crate::stream_consumer::RankUpdateHub;
+use futures::{SinkExt, Stream};
+use shared::errors::Result as ServiceResult;
+use std::collections::HashSet;
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::{Request, Response, Status};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 pub mod game {
     tonic::include_proto!("game");
@@ -9,22 +18,241 @@ pub mod game {
 
 use game::leaderboard_service_server::LeaderboardService;
 use game::{
-    GetGlobalStatsRequest, GetGlobalStatsResponse, GetLeaderboardRequest, GetLeaderboardResponse,
-    GetUserRankRequest, GetUserRankResponse, LeaderboardEntry, UpdateUserScoreRequest,
-    UpdateUserScoreResponse,
+    GetFriendsLeaderboardRequest, GetFriendsLeaderboardResponse, GetGlobalStatsRequest, GetGlobalStatsResponse,
+    GetLeaderboardAroundUserRequest, GetLeaderboardAroundUserResponse, GetLeaderboardRequest,
+    GetLeaderboardResponse, GetUserRankRequest, GetUserRankResponse, LeaderboardEntry, SubscribeUserRankRequest,
+    UpdateUserScoreRequest, UpdateUserScoreResponse, UserRankUpdate,
 };
 
+const RANK_UPDATE_BUFFER: usize = 256;
+const DEFAULT_AROUND_RADIUS: i32 = 2;
+const MAX_AROUND_RADIUS: i32 = 25;
+const DEFAULT_FRIENDS_LIMIT: i32 = 20;
+const MAX_FRIENDS_LIMIT: i32 = 100;
+
+/// Where a read was answered from, counted so dashboards show when the
+/// repository fallback is carrying traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadSource {
+    Redis,
+    Repository,
+}
+
+fn record_read_source(source: ReadSource) {
+    shared::record_counter(
+        match source {
+            ReadSource::Redis => "leaderboard.reads.redis",
+            ReadSource::Repository => "leaderboard.reads.repository",
+        },
+        1,
+    );
+}
+
 #[derive(Clone)]
 pub struct LeaderboardServerImpl {
     repository: Arc<LeaderboardRepository>,
+    cache: LeaderboardCache,
+    rank_updates: RankUpdateHub,
+    repository_fallback: bool,
 }
 
 impl LeaderboardServerImpl {
-    pub fn new(repository: LeaderboardRepository) -> Self {
+    /// Ranks are read from the Redis boards the stream consumer maintains. With
+    /// `repository_fallback`, all-time reads go to Postgres while Redis is
+    /// failing or its all-time board is empty though Postgres isn't; windowed
+    /// boards only exist in Redis either way.
+    pub fn new(
+        repository: LeaderboardRepository,
+        cache: LeaderboardCache,
+        rank_updates: RankUpdateHub,
+        repository_fallback: bool,
+    ) -> Self {
         Self {
             repository: Arc::new(repository),
+            cache,
+            rank_updates,
+            repository_fallback,
+        }
+    }
+
+    async fn read_leaderboard(
+        &self,
+        period: LeaderboardPeriod,
+        limit: i32,
+        offset: i32,
+    ) -> ServiceResult<(GetLeaderboardResponse, ReadSource)> {
+        let cached = tokio::try_join!(
+            self.cache.get_period_leaderboard(period, Some(limit), Some(offset)),
+            self.cache.get_period_total_count(period)
+        );
+
+        match cached {
+            Ok((_, 0)) if !period.is_windowed() && self.all_time_board_missing().await && self.repository_fallback => {
+                let response = self.read_repository_leaderboard(limit, offset).await?;
+                Ok((response, ReadSource::Repository))
+            }
+            Ok((entries, total_count)) => {
+                let entries = entries.into_iter().map(LeaderboardEntry::from).collect();
+                let response = GetLeaderboardResponse {
+                    entries,
+                    total_count: total_count as i32,
+                };
+                Ok((response, ReadSource::Redis))
+            }
+            Err(e) if self.repository_fallback && !period.is_windowed() => {
+                warn!("Redis leaderboard read failed, falling back to repository: {}", e);
+                let response = self.read_repository_leaderboard(limit, offset).await?;
+                Ok((response, ReadSource::Repository))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_repository_leaderboard(&self, limit: i32, offset: i32) -> ServiceResult<GetLeaderboardResponse> {
+        let (entries, total_count) = tokio::try_join!(
+            self.repository.get_leaderboard_cached(limit, offset),
+            self.repository.get_total_count_cached()
+        )?;
+
+        let entries = entries.into_iter().map(LeaderboardEntry::from).collect();
+
+        Ok(GetLeaderboardResponse {
+            entries,
+            total_count: total_count as i32,
+        })
+    }
+
+    async fn read_user_rank(
+        &self,
+        period: LeaderboardPeriod,
+        user_id: &str,
+    ) -> ServiceResult<(Option<UserStanding>, ReadSource)> {
+        match self.cache.get_period_user_rank(period, user_id).await {
+            Ok(None) if !period.is_windowed() && self.all_time_board_empty().await && self.repository_fallback => {
+                let standing = self.repository.get_user_standing(user_id).await?;
+                Ok((standing, ReadSource::Repository))
+            }
+            Ok(rank) => Ok((rank, ReadSource::Redis)),
+            Err(e) if self.repository_fallback && !period.is_windowed() => {
+                warn!("Redis rank read failed, falling back to repository: {}", e);
+                let standing = self.repository.get_user_standing(user_id).await?;
+                Ok((standing, ReadSource::Repository))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_leaderboard_around(
+        &self,
+        period: LeaderboardPeriod,
+        user_id: &str,
+        radius: i32,
+    ) -> ServiceResult<(GetLeaderboardAroundUserResponse, ReadSource)> {
+        let cached = tokio::try_join!(
+            self.cache.get_period_leaderboard_around(period, user_id, radius),
+            self.cache.get_period_total_count(period)
+        );
+
+        match cached {
+            Ok((_, 0)) if !period.is_windowed() && self.all_time_board_missing().await && self.repository_fallback => {
+                let response = self.read_repository_leaderboard_around(user_id, radius).await?;
+                Ok((response, ReadSource::Repository))
+            }
+            Ok((entries, total_count)) => {
+                let response = GetLeaderboardAroundUserResponse {
+                    found: entries.is_some(),
+                    entries: entries.unwrap_or_default().into_iter().map(LeaderboardEntry::from).collect(),
+                    total_count: total_count as i32,
+                };
+                Ok((response, ReadSource::Redis))
+            }
+            Err(e) if self.repository_fallback && !period.is_windowed() => {
+                warn!("Redis leaderboard read failed, falling back to repository: {}", e);
+                let response = self.read_repository_leaderboard_around(user_id, radius).await?;
+                Ok((response, ReadSource::Repository))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// For reads that found nothing on the all-time board: whether the board
+    /// is empty as a whole while Postgres has ranked users.
+    async fn all_time_board_empty(&self) -> bool {
+        match self.cache.get_total_count().await {
+            Ok(0) => self.all_time_board_missing().await,
+            _ => false,
         }
     }
+
+    /// Called with the all-time board empty: true if Postgres has ranked users,
+    /// as after Redis lost its data, until a rebuild repopulates the board.
+    async fn all_time_board_missing(&self) -> bool {
+        match self.repository.get_total_count_cached().await {
+            Ok(0) => false,
+            Ok(ranked_users) => {
+                warn!(
+                    "Redis all-time leaderboard is empty but Postgres has {} ranked users; rebuild it",
+                    ranked_users
+                );
+                shared::record_counter("leaderboard.reads.board_missing", 1);
+                true
+            }
+            Err(e) => {
+                error!("Failed to check whether the all-time leaderboard is missing: {}", e);
+                false
+            }
+        }
+    }
+
+    async fn read_repository_leaderboard_around(
+        &self,
+        user_id: &str,
+        radius: i32,
+    ) -> ServiceResult<GetLeaderboardAroundUserResponse> {
+        let Some((rank, _)) = self.repository.get_user_rank(user_id).await? else {
+            return Ok(GetLeaderboardAroundUserResponse::default());
+        };
+
+        let (offset, limit) = window_around(rank - 1, radius);
+        let response = self.read_repository_leaderboard(limit, offset).await?;
+
+        Ok(GetLeaderboardAroundUserResponse {
+            entries: response.entries,
+            found: true,
+            total_count: response.total_count,
+        })
+    }
+}
+
+impl From<crate::cache::leaderboard_cache::LeaderboardEntry> for LeaderboardEntry {
+    fn from(entry: crate::cache::leaderboard_cache::LeaderboardEntry) -> Self {
+        Self {
+            rank: entry.rank,
+            username: entry.username,
+            total_clicks: entry.total_clicks,
+            user_id: entry.user_id,
+        }
+    }
+}
+
+impl From<crate::repository::LeaderboardEntry> for LeaderboardEntry {
+    fn from(entry: crate::repository::LeaderboardEntry) -> Self {
+        Self {
+            rank: entry.rank as i32,
+            username: entry.username,
+            total_clicks: entry.total_clicks,
+            user_id: entry.user_id,
+        }
+    }
+}
+
+fn period_from_proto(period: i32) -> LeaderboardPeriod {
+    match game::LeaderboardPeriod::try_from(period).unwrap_or_default() {
+        game::LeaderboardPeriod::AllTime => LeaderboardPeriod::AllTime,
+        game::LeaderboardPeriod::Daily => LeaderboardPeriod::Daily,
+        game::LeaderboardPeriod::Weekly => LeaderboardPeriod::Weekly,
+        game::LeaderboardPeriod::Monthly => LeaderboardPeriod::Monthly,
+    }
 }
 
 #[tonic::async_trait]
@@ -37,49 +265,29 @@ impl LeaderboardService for LeaderboardServerImpl {
         let req = request.into_inner();
         let limit = if req.limit > 0 { req.limit } else { 20 };
         let offset = if req.offset > 0 { req.offset } else { 0 };
+        let period = period_from_proto(req.period);
 
         debug!(
-            "⏱️ GetLeaderboard BEGIN (CACHED): limit={}, offset={}",
-            limit, offset
-        );
-
-        let repo_clone = self.repository.clone();
-        let (entries_result, count_result) = tokio::join!(
-            self.repository.get_leaderboard_cached(limit, offset),
-            repo_clone.get_total_count()
+            "⏱️ GetLeaderboard BEGIN ({:?}): limit={}, offset={}",
+            period, limit, offset
         );
 
-        let entries = entries_result.map_err(|e| {
-            error!("Failed to get cached leaderboard: {}", e);
+        let (response, source) = self.read_leaderboard(period, limit, offset).await.map_err(|e| {
+            error!("Failed to get {:?} leaderboard: {}", period, e);
             Status::from(e)
         })?;
-
-        let total_count = count_result.map_err(|e| {
-            error!("Failed to get total count: {}", e);
-            Status::from(e)
-        })? as i32;
-
-        let pb_entries: Vec<LeaderboardEntry> = entries
-            .into_iter()
-            .map(|e| LeaderboardEntry {
-                rank: e.rank as i32,
-                username: e.username,
-                total_clicks: e.total_clicks,
-                user_id: e.user_id,
-            })
-            .collect();
+        record_read_source(source);
 
         info!(
-            "⏱️ GetLeaderboard TOTAL: {:?} - Returning {} entries (total: {})",
+            "⏱️ GetLeaderboard ({:?}, {:?}) TOTAL: {:?} - Returning {} entries (total: {})",
+            period,
+            source,
             start.elapsed(),
-            pb_entries.len(),
-            total_count
+            response.entries.len(),
+            response.total_count
         );
 
-        Ok(Response::new(GetLeaderboardResponse {
-            entries: pb_entries,
-            total_count,
-        }))
+        Ok(Response::new(response))
     }
 
     async fn get_user_rank(
@@ -89,37 +297,125 @@ impl LeaderboardService for LeaderboardServerImpl {
         let start = std::time::Instant::now();
         let req = request.into_inner();
         let user_id = req.user_id;
+        let period = period_from_proto(req.period);
+
+        debug!("⏱️ GetUserRank BEGIN ({:?}) for user: {}", period, user_id);
+
+        let (result, source) = self.read_user_rank(period, &user_id).await.map_err(|e| {
+            error!("Failed to get {:?} user rank for {}: {}", period, user_id, e);
+            Status::from(e)
+        })?;
+        record_read_source(source);
 
-        debug!("⏱️ GetUserRank BEGIN (CACHED) for user: {}", user_id);
+        let response = match result {
+            Some(standing) => GetUserRankResponse {
+                rank: standing.rank,
+                total_clicks: standing.total_clicks,
+                found: true,
+                percentile: standing.percentile(),
+            },
+            None => GetUserRankResponse::default(),
+        };
 
-        let result = self
+        info!(
+            "⏱️ GetUserRank ({:?}) TOTAL: {:?} - User {} rank: {} (top {:.2}%), clicks: {}, found: {}",
+            source,
+            start.elapsed(),
+            user_id,
+            response.rank,
+            response.percentile,
+            response.total_clicks,
+            response.found
+        );
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_leaderboard_around_user(
+        &self,
+        request: Request<GetLeaderboardAroundUserRequest>,
+    ) -> Result<Response<GetLeaderboardAroundUserResponse>, Status> {
+        let start = std::time::Instant::now();
+        let req = request.into_inner();
+        let period = period_from_proto(req.period);
+        let radius = if req.radius > 0 {
+            req.radius.min(MAX_AROUND_RADIUS)
+        } else {
+            DEFAULT_AROUND_RADIUS
+        };
+
+        debug!(
+            "⏱️ GetLeaderboardAroundUser BEGIN ({:?}) for user: {}, radius={}",
+            period, req.user_id, radius
+        );
+
+        let (response, source) = self
+            .read_leaderboard_around(period, &req.user_id, radius)
+            .await
+            .map_err(|e| {
+                error!("Failed to get {:?} leaderboard around {}: {}", period, req.user_id, e);
+                Status::from(e)
+            })?;
+        record_read_source(source);
+
+        info!(
+            "⏱️ GetLeaderboardAroundUser ({:?}, {:?}) TOTAL: {:?} - User {} found: {}, returning {} entries",
+            period,
+            source,
+            start.elapsed(),
+            req.user_id,
+            response.found,
+            response.entries.len()
+        );
+
+        Ok(Response::new(response))
+    }
+
+    async fn get_friends_leaderboard(
+        &self,
+        request: Request<GetFriendsLeaderboardRequest>,
+    ) -> Result<Response<GetFriendsLeaderboardResponse>, Status> {
+        let start = std::time::Instant::now();
+        let req = request.into_inner();
+        let limit = if req.limit > 0 {
+            req.limit.min(MAX_FRIENDS_LIMIT)
+        } else {
+            DEFAULT_FRIENDS_LIMIT
+        };
+
+        debug!("⏱️ GetFriendsLeaderboard BEGIN for user: {}, limit={}", req.user_id, limit);
+
+        // Friends come from the referrals table, so this always reads Postgres
+        let board = self
             .repository
-            .get_user_rank_cached(&user_id)
+            .get_friends_leaderboard(&req.user_id, limit)
             .await
             .map_err(|e| {
-                error!("Failed to get cached user rank for {}: {}", user_id, e);
+                error!("Failed to get friends leaderboard for {}: {}", req.user_id, e);
                 Status::from(e)
             })?;
 
-        let (rank, total_clicks, found) = match result {
-            Some((r, clicks)) => (r, clicks, true),
-            None => (0, 0, false),
+        let response = match board {
+            Some(board) => GetFriendsLeaderboardResponse {
+                entries: board.entries.into_iter().map(LeaderboardEntry::from).collect(),
+                found: true,
+                user_rank: board.user_rank,
+                total_count: board.total_count as i32,
+                referral_code: board.referral_code,
+            },
+            None => GetFriendsLeaderboardResponse::default(),
         };
 
         info!(
-            "⏱️ GetUserRank TOTAL: {:?} - User {} rank: {}, clicks: {}, found: {}",
+            "⏱️ GetFriendsLeaderboard TOTAL: {:?} - User {} found: {}, rank {} of {}",
             start.elapsed(),
-            user_id,
-            rank,
-            total_clicks,
-            found
+            req.user_id,
+            response.found,
+            response.user_rank,
+            response.total_count
         );
 
-        Ok(Response::new(GetUserRankResponse {
-            rank,
-            total_clicks,
-            found,
-        }))
+        Ok(Response::new(response))
     }
 
     async fn get_global_stats(
@@ -182,4 +478,52 @@ impl LeaderboardService for LeaderboardServerImpl {
             new_rank,
         }))
     }
+
+    type SubscribeUserRankStream =
+        Pin<Box<dyn Stream<Item = Result<UserRankUpdate, Status>> + Send + 'static>>;
+
+    async fn subscribe_user_rank(
+        &self,
+        request: Request<SubscribeUserRankRequest>,
+    ) -> Result<Response<Self::SubscribeUserRankStream>, Status> {
+        let filter: HashSet<String> = request.into_inner().user_ids.into_iter().collect();
+        let mut updates = self.rank_updates.subscribe();
+
+        info!("SubscribeUserRank opened (filtered users: {})", filter.len());
+        shared::record_counter("leaderboard.rank_subscriptions", 1);
+
+        let (mut tx, rx) = futures::channel::mpsc::channel(RANK_UPDATE_BUFFER);
+
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(update) => {
+                        if !filter.is_empty() && !filter.contains(&update.user_id) {
+                            continue;
+                        }
+
+                        let message = UserRankUpdate {
+                            user_id: update.user_id,
+                            username: update.username,
+                            rank: update.rank,
+                            total_clicks: update.total_clicks,
+                        };
+
+                        if tx.send(Ok(message)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Rank subscriber lagged, skipped {} updates", skipped);
+                        shared::record_counter("leaderboard.rank_updates_skipped", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+
+            info!("SubscribeUserRank closed");
+        });
+
+        Ok(Response::new(Box::pin(rx)))
+    }
 }
@@ -1,3 +1,5 @@
+pub mod admin_server;
 pub mod leaderboard_server;
 
+pub use admin_server::LeaderboardAdminServerImpl;
 pub use leaderboard_server::LeaderboardServerImpl;
@@ -1,5 +1,15 @@
+pub mod cache;
 pub mod grpc_server;
+pub mod ranking;
+pub mod rebuild;
+pub mod refresh;
 pub mod repository;
+pub mod stream_consumer;
 
-pub use grpc_server::LeaderboardServerImpl;
+pub use cache::{LeaderboardCache, StatsCache};
+pub use grpc_server::{LeaderboardAdminServerImpl, LeaderboardServerImpl};
+pub use ranking::UserStanding;
+pub use rebuild::LeaderboardRebuilder;
+pub use refresh::ViewRefresher;
 pub use repository::{GlobalStats, LeaderboardEntry, LeaderboardRepository};
+pub use stream_consumer::ClickStreamConsumer;
@@ -0,0 +1,127 @@
+//! The one ranking policy every rank source follows, Redis boards and
+//! Postgres queries alike:
+//!
+//! 1. Most clicks first.
+//! 2. On equal clicks, whoever reached the score first, to the second.
+//! 3. On equal clicks and time, higher user id first, which is the order
+//!    ZREVRANK gives members with equal scores.
+//!
+//! Ranks are ordinal: no two players share one. The time comes from one
+//! place, `users.score_reached_at`, stamped by the flush that wrote the
+//! score. The click event carries it as `reached_at`, and Redis folds it into
+//! the member's score, see [`board_score`]; a rebuild reads it from Postgres
+//! directly. Both sources therefore break ties on the same instant.
+
+use chrono::{DateTime, Utc};
+
+/// Tie-break times are counted from 2024-01-01T00:00:00Z.
+const TIE_BREAK_EPOCH: i64 = 1_704_067_200;
+/// Seconds of tie-break the encoding covers (about 17 years). Later times all
+/// tie with each other and fall through to the user id.
+const TIE_BREAK_SPAN: i64 = 1 << 29;
+/// Keeps the tie-break a quarter of a click away from either neighbouring
+/// click count, so float rounding on huge scores can't carry into the count.
+const TIE_BREAK_BIAS: i64 = 1 << 28;
+/// Board score units per click, 2^30. `LeaderboardCache`'s scripts hard-code it.
+pub(crate) const CLICK_SCALE: f64 = (1u64 << 30) as f64;
+
+/// Redis sorted-set score for a player: clicks in the high bits, and in the
+/// low bits a value that shrinks the later the score was reached. Sorting
+/// scores descending gives the policy's order. Scores stay exact up to 2^23
+/// clicks; past that the tie-break gets coarser but clicks still order first.
+pub fn board_score(total_clicks: i64, reached_at: DateTime<Utc>) -> f64 {
+    total_clicks as f64 * CLICK_SCALE + tie_break(reached_at) as f64
+}
+
+/// Low bits of [`board_score`], for scripts that set the click count themselves.
+pub fn tie_break(reached_at: DateTime<Utc>) -> i64 {
+    let elapsed = (reached_at.timestamp() - TIE_BREAK_EPOCH).clamp(0, TIE_BREAK_SPAN - 1);
+    TIE_BREAK_BIAS + (TIE_BREAK_SPAN - 1 - elapsed)
+}
+
+/// Click count a [`board_score`] was built from.
+pub fn clicks_from_board_score(score: f64) -> i64 {
+    (score / CLICK_SCALE).floor() as i64
+}
+
+/// Where a user stands on a board, as answered by Redis or Postgres.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserStanding {
+    pub rank: i32,
+    pub total_clicks: i64,
+    /// Everyone on the board, the user included.
+    pub ranked_users: i64,
+}
+
+impl UserStanding {
+    /// Share of the board ranked at or above the user, so 3.0 reads "top 3%".
+    pub fn percentile(&self) -> f64 {
+        if self.ranked_users <= 0 || self.rank <= 0 {
+            return 0.0;
+        }
+
+        (self.rank as f64 / self.ranked_users as f64 * 100.0).min(100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(TIE_BREAK_EPOCH + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn test_board_score_orders_by_clicks_then_earliest() {
+        let mut players = [
+            ("late", board_score(100, at(50))),
+            ("most", board_score(101, at(900))),
+            ("early", board_score(100, at(10))),
+            ("few", board_score(3, at(0))),
+        ];
+        players.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let order: Vec<&str> = players.iter().map(|(name, _)| *name).collect();
+        assert_eq!(order, ["most", "early", "late", "few"]);
+    }
+
+    #[test]
+    fn test_board_score_round_trips_clicks() {
+        for clicks in [0, 1, 42, (1 << 23) - 1, 1 << 23, 987_654_321_012, 1 << 50] {
+            for secs in [-5, 0, 1, 86_400, TIE_BREAK_SPAN + 100] {
+                assert_eq!(clicks_from_board_score(board_score(clicks, at(secs))), clicks);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tie_break_clamps_outside_span() {
+        assert_eq!(tie_break(at(-100)), tie_break(at(0)));
+        assert_eq!(tie_break(at(TIE_BREAK_SPAN + 100)), tie_break(at(TIE_BREAK_SPAN - 1)));
+        assert!(tie_break(at(0)) < CLICK_SCALE as i64);
+        assert!(tie_break(at(TIE_BREAK_SPAN)) >= TIE_BREAK_BIAS);
+    }
+
+    fn standing(rank: i32, ranked_users: i64) -> UserStanding {
+        UserStanding {
+            rank,
+            total_clicks: 0,
+            ranked_users,
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(standing(1, 1).percentile(), 100.0);
+        assert_eq!(standing(3, 100).percentile(), 3.0);
+        assert_eq!(standing(50, 200_000).percentile(), 0.025);
+    }
+
+    #[test]
+    fn test_percentile_of_empty_board() {
+        assert_eq!(standing(0, 0).percentile(), 0.0);
+        assert_eq!(standing(4, 0).percentile(), 0.0);
+    }
+}
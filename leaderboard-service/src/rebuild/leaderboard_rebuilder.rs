@@ -0,0 +1,146 @@
+use crate::cache::stats_cache::GlobalStats as CachedStats;
+use crate::cache::{LeaderboardCache, StatsCache};
+use crate::repository::LeaderboardRepository;
+use shared::errors::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+const PAGE_SIZE: i64 = 1_000;
+/// Renewed after every page, so it only lapses if the rebuilding instance dies.
+const LOCK_TTL: Duration = Duration::from_secs(60);
+
+/// Repopulates the Redis all-time board, usernames and global stats from
+/// Postgres. Users are paged into a staging key that is swapped in at the end,
+/// so reads keep being served from the old board the whole time. Windowed
+/// boards have no Postgres source and are left alone.
+///
+/// Totals the stream consumer applies while a rebuild runs are staged as well,
+/// and a page read from Postgres before such an update doesn't overwrite it, so
+/// the swap keeps them.
+#[derive(Clone)]
+pub struct LeaderboardRebuilder {
+    repository: LeaderboardRepository,
+    cache: LeaderboardCache,
+    stats_cache: StatsCache,
+    running: Arc<AtomicBool>,
+}
+
+impl LeaderboardRebuilder {
+    pub fn new(repository: LeaderboardRepository, cache: LeaderboardCache, stats_cache: StatsCache) -> Self {
+        Self {
+            repository,
+            cache,
+            stats_cache,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Starts a rebuild in the background if the all-time board is empty, as
+    /// after Redis lost its data.
+    pub async fn rebuild_if_empty(&self) -> Result<bool> {
+        if self.cache.get_total_count().await? > 0 {
+            return Ok(false);
+        }
+
+        info!("Redis leaderboard is empty, rebuilding from Postgres");
+        self.start("startup").await
+    }
+
+    /// Starts a rebuild in the background. Returns false if one is already
+    /// running on this or another instance.
+    pub async fn start(&self, requested_by: &str) -> Result<bool> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Ok(false);
+        }
+
+        let owner = uuid::Uuid::new_v4().to_string();
+        match self.cache.try_lock_rebuild(&owner, LOCK_TTL).await {
+            Ok(true) => {}
+            Ok(false) => {
+                self.running.store(false, Ordering::SeqCst);
+                return Ok(false);
+            }
+            Err(e) => {
+                self.running.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+
+        info!(requested_by, "Leaderboard rebuild started");
+        shared::record_counter("leaderboard.rebuild.started", 1);
+
+        let rebuilder = self.clone();
+        tokio::spawn(async move {
+            let start = Instant::now();
+
+            match rebuilder.run().await {
+                Ok(users) => {
+                    info!(users, elapsed = ?start.elapsed(), "Leaderboard rebuild finished");
+                    shared::record_counter("leaderboard.rebuild.completed", 1);
+                    shared::record_timing("leaderboard.rebuild.duration", start.elapsed().as_secs_f64());
+                }
+                Err(e) => {
+                    error!("Leaderboard rebuild failed: {}", e);
+                    shared::record_counter("leaderboard.rebuild.failed", 1);
+                    if let Err(e) = rebuilder.cache.abort_rebuild().await {
+                        warn!("Failed to clear aborted leaderboard rebuild: {}", e);
+                    }
+                }
+            }
+
+            if let Err(e) = rebuilder.cache.release_rebuild_lock(&owner).await {
+                warn!("Failed to release leaderboard rebuild lock: {}", e);
+            }
+            rebuilder.running.store(false, Ordering::SeqCst);
+        });
+
+        Ok(true)
+    }
+
+    async fn run(&self) -> Result<i64> {
+        self.cache.begin_rebuild(LOCK_TTL).await?;
+
+        let mut staged = 0usize;
+        let mut after = None;
+        loop {
+            let page = self.repository.get_user_scores_page(after, PAGE_SIZE).await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            after = Some(last.user_id);
+
+            let rows: Vec<_> = page
+                .into_iter()
+                .map(|user| {
+                    (
+                        user.user_id.to_string(),
+                        user.username,
+                        user.total_clicks,
+                        user.score_reached_at,
+                        user.score_version,
+                    )
+                })
+                .collect();
+            staged += self.cache.stage_rebuild_page(&rows).await? as usize;
+            self.cache.extend_rebuild_lock(LOCK_TTL).await?;
+
+            shared::record_gauge("leaderboard.rebuild.users_staged", staged as f64);
+            info!(staged, "Leaderboard rebuild progress");
+        }
+
+        let users = self.cache.finish_rebuild().await?;
+
+        let stats = self.repository.get_global_stats().await?;
+        self.stats_cache
+            .set_global_stats(&CachedStats {
+                total_clicks: stats.total_clicks,
+                total_users: stats.total_users,
+                active_sessions: stats.active_sessions,
+            })
+            .await?;
+
+        Ok(users)
+    }
+}
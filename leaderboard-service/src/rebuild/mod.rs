@@ -0,0 +1,3 @@
+pub mod leaderboard_rebuilder;
+
+pub use leaderboard_rebuilder::LeaderboardRebuilder;
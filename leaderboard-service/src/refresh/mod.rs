@@ -0,0 +1,3 @@
+pub mod view_refresher;
+
+pub use view_refresher::ViewRefresher;
@@ -0,0 +1,192 @@
+use crate::repository::LeaderboardRepository;
+use shared::errors::{Result, ServiceError};
+use sqlx::postgres::PgListener;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// Notified by triggers on users and on the restriction tables.
+pub const LEADERBOARD_CHANGED_CHANNEL: &str = "leaderboard_changed";
+const LISTEN_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// When `leaderboard_top_1000` first fell behind Postgres, if it has.
+#[derive(Debug, Default)]
+struct DirtyState {
+    since: Option<Instant>,
+}
+
+impl DirtyState {
+    /// Keeps the earliest time, so staleness counts from the first missed change.
+    fn mark(&mut self, at: Instant) {
+        self.since = Some(self.since.map_or(at, |since| since.min(at)));
+    }
+
+    fn take(&mut self) -> Option<Instant> {
+        self.since.take()
+    }
+
+    fn staleness(&self, now: Instant) -> Duration {
+        self.since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since))
+    }
+}
+
+/// Refreshes the `leaderboard_top_1000` materialized view when Postgres reports
+/// a change, rather than on a fixed timer. Notifications only mark the view
+/// dirty; refreshes run at most once per `min_interval`, so a burst of flushes
+/// costs a single refresh. A refresh also runs every `max_interval` in case a
+/// notification was lost.
+///
+/// LISTEN needs a session of its own, so `listen_url` must reach Postgres
+/// directly rather than through a transaction-pooling PgBouncer.
+///
+/// Replicas share an advisory lock: one that finds it taken stays dirty and
+/// tries again on its next tick, as the running refresh may predate its change.
+#[derive(Clone)]
+pub struct ViewRefresher {
+    repository: LeaderboardRepository,
+    listen_url: Arc<str>,
+    min_interval: Duration,
+    max_interval: Duration,
+    dirty: Arc<Mutex<DirtyState>>,
+}
+
+impl ViewRefresher {
+    pub fn new(
+        repository: LeaderboardRepository,
+        listen_url: &str,
+        min_interval: Duration,
+        max_interval: Duration,
+    ) -> Self {
+        Self {
+            repository,
+            listen_url: Arc::from(listen_url),
+            min_interval,
+            max_interval,
+            dirty: Arc::new(Mutex::new(DirtyState::default())),
+        }
+    }
+
+    /// Starts listening for changes and runs the refresh loop. Never returns.
+    pub async fn start(self) {
+        let listener = self.clone();
+        tokio::spawn(async move { listener.listen().await });
+
+        self.run().await;
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.lock().unwrap().mark(Instant::now());
+    }
+
+    async fn connect_listener(&self) -> Result<PgListener> {
+        let mut listener = PgListener::connect(&self.listen_url)
+            .await
+            .map_err(|e| ServiceError::Database(e.to_string()))?;
+
+        listener
+            .listen(LEADERBOARD_CHANGED_CHANNEL)
+            .await
+            .map_err(|e| ServiceError::Database(e.to_string()))?;
+
+        Ok(listener)
+    }
+
+    async fn listen(&self) {
+        loop {
+            match self.connect_listener().await {
+                Ok(mut listener) => {
+                    info!("Listening for leaderboard changes");
+                    // Anything changed while we were not listening went unseen
+                    self.mark_dirty();
+
+                    loop {
+                        match listener.try_recv().await {
+                            Ok(Some(_)) => self.mark_dirty(),
+                            // The listener reconnects on the next call, but
+                            // notifications sent in between are lost
+                            Ok(None) => {
+                                warn!("Leaderboard change listener lost its connection, reconnecting");
+                                self.mark_dirty();
+                            }
+                            Err(e) => {
+                                error!("Leaderboard change listener failed: {}", e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to listen for leaderboard changes: {}", e),
+            }
+
+            self.mark_dirty();
+            tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+        }
+    }
+
+    async fn run(&self) {
+        let mut interval = tokio::time::interval(self.min_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_refresh = Instant::now();
+
+        loop {
+            interval.tick().await;
+
+            let now = Instant::now();
+            let dirty_since = {
+                let mut dirty = self.dirty.lock().unwrap();
+                shared::record_gauge("leaderboard.mv.staleness_seconds", dirty.staleness(now).as_secs_f64());
+
+                if dirty.since.is_none() && now.duration_since(last_refresh) < self.max_interval {
+                    continue;
+                }
+                dirty.take()
+            };
+
+            match self.repository.try_refresh_leaderboard_cache().await {
+                Ok(true) => {
+                    last_refresh = Instant::now();
+                    shared::record_counter("leaderboard.mv.refreshed", 1);
+                    shared::record_timing("leaderboard.mv.refresh_seconds", now.elapsed().as_secs_f64());
+                    debug!("Leaderboard view refreshed in {:?}", now.elapsed());
+                }
+                Ok(false) => {
+                    shared::record_counter("leaderboard.mv.refresh_skipped", 1);
+                    debug!("Leaderboard view refresh already running on another instance");
+                    self.restore_dirty(dirty_since, now);
+                }
+                Err(e) => {
+                    shared::record_counter("leaderboard.mv.refresh_failed", 1);
+                    error!("Failed to refresh leaderboard view: {}", e);
+                    self.restore_dirty(dirty_since, now);
+                }
+            }
+        }
+    }
+
+    /// Puts back the dirty mark a refresh that did not happen had cleared. A
+    /// safety refresh counts as dirty from when it was due.
+    fn restore_dirty(&self, dirty_since: Option<Instant>, attempted_at: Instant) {
+        self.dirty.lock().unwrap().mark(dirty_since.unwrap_or(attempted_at));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_state_keeps_earliest_change() {
+        let start = Instant::now();
+        let mut dirty = DirtyState::default();
+        assert_eq!(dirty.staleness(start), Duration::ZERO);
+
+        dirty.mark(start + Duration::from_secs(2));
+        dirty.mark(start);
+        dirty.mark(start + Duration::from_secs(5));
+        assert_eq!(dirty.staleness(start + Duration::from_secs(3)), Duration::from_secs(3));
+
+        assert_eq!(dirty.take(), Some(start));
+        assert_eq!(dirty.take(), None);
+        assert_eq!(dirty.staleness(start + Duration::from_secs(3)), Duration::ZERO);
+    }
+}
@@ -1,5 +1,9 @@
+use crate::ranking::UserStanding;
 use shared::errors::{Result, ServiceError};
 use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 use tracing::{debug, error};
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -17,14 +21,50 @@ pub struct GlobalStats {
     pub active_sessions: i64,
 }
 
+/// A user ranked against their referral graph: whoever invited them and
+/// everyone they invited.
+#[derive(Debug, Clone)]
+pub struct FriendsLeaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+    pub user_rank: i32,
+    pub total_count: i64,
+    pub referral_code: String,
+}
+
+/// Rows kept in the `leaderboard_top_1000` materialized view. Must match the
+/// view's `LIMIT` in `migrations/013_leaderboard_tie_breaking.sql`; changing the
+/// depth means a migration that recreates the view.
+const LEADERBOARD_VIEW_DEPTH: i32 = 1000;
+
+/// How long `get_total_count_cached` reuses a count. Counting scans every user,
+/// and "rank N of M" doesn't need M to the second.
+const TOTAL_COUNT_TTL: Duration = Duration::from_secs(30);
+
+/// A user's all-time score as stored in Postgres, for rebuilding the Redis board.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserScore {
+    pub user_id: uuid::Uuid,
+    pub username: String,
+    pub total_clicks: i64,
+    pub score_reached_at: chrono::DateTime<chrono::Utc>,
+    pub score_version: i64,
+}
+
+/// Users in the `hidden_users` view (shadow-banned, frozen, banned or removed by
+/// an admin) are left out of every ranking query. Ranks follow the policy in
+/// `crate::ranking`, via `users.score_reached_at`.
 #[derive(Clone)]
 pub struct LeaderboardRepository {
     pool: PgPool,
+    total_count: Arc<Mutex<Option<(Instant, i64)>>>,
 }
 
 impl LeaderboardRepository {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            total_count: Arc::new(Mutex::new(None)),
+        }
     }
 
     pub async fn get_leaderboard(
@@ -41,12 +81,13 @@ impl LeaderboardRepository {
                 total_clicks
             FROM (
                 SELECT
-                    DENSE_RANK() OVER (ORDER BY total_clicks DESC) as rank,
+                    ROW_NUMBER() OVER (ORDER BY total_clicks DESC, score_reached_at, id DESC) as rank,
                     id::text as user_id,
                     username,
                     total_clicks
                 FROM users
                 WHERE total_clicks > 0
+                AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = users.id)
             ) ranked
             ORDER BY rank
             LIMIT $1 OFFSET $2
@@ -65,6 +106,9 @@ impl LeaderboardRepository {
         Ok(entries)
     }
 
+    /// Ranks by counting the users ahead over the leaderboard order index,
+    /// rather than ranking the whole table, so the cost grows with the rank
+    /// instead of with the number of users.
     pub async fn get_user_rank(&self, user_id: &str) -> Result<Option<(i32, i64)>> {
         let user_uuid = uuid::Uuid::parse_str(user_id).map_err(|e| {
             error!("Invalid UUID: {}", e);
@@ -74,17 +118,25 @@ impl LeaderboardRepository {
         let result = sqlx::query_as::<_, (i64, i64)>(
             r#"
             SELECT
-                rank,
-                total_clicks
-            FROM (
-                SELECT
-                    DENSE_RANK() OVER (ORDER BY total_clicks DESC) as rank,
-                    id,
-                    total_clicks
-                FROM users
-                WHERE total_clicks > 0
-            ) ranked
-            WHERE id = $1
+                (
+                    SELECT COUNT(*)
+                    FROM users ahead
+                    WHERE (
+                        ahead.total_clicks > me.total_clicks
+                        OR (ahead.total_clicks = me.total_clicks AND ahead.score_reached_at < me.score_reached_at)
+                        OR (
+                            ahead.total_clicks = me.total_clicks
+                            AND ahead.score_reached_at = me.score_reached_at
+                            AND ahead.id > me.id
+                        )
+                    )
+                    AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = ahead.id)
+                ) + 1 AS rank,
+                me.total_clicks
+            FROM users me
+            WHERE me.id = $1
+            AND me.total_clicks > 0
+            AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = me.id)
             "#,
         )
         .bind(user_uuid)
@@ -98,12 +150,106 @@ impl LeaderboardRepository {
         Ok(result.map(|(rank, clicks)| (rank as i32, clicks)))
     }
 
+    pub async fn get_user_standing(&self, user_id: &str) -> Result<Option<UserStanding>> {
+        let (rank, ranked_users) = tokio::try_join!(self.get_user_rank(user_id), self.get_total_count_cached())?;
+
+        // The count can lag behind users who ranked since it was taken
+        Ok(rank.map(|(rank, total_clicks)| UserStanding {
+            rank,
+            total_clicks,
+            ranked_users: ranked_users.max(rank as i64),
+        }))
+    }
+
+    /// The top `limit` of the user's friends, ranked among themselves. Friends
+    /// with no clicks yet still count; hidden friends don't, though the user
+    /// always sees themselves.
+    pub async fn get_friends_leaderboard(&self, user_id: &str, limit: i32) -> Result<Option<FriendsLeaderboard>> {
+        let user_uuid = uuid::Uuid::parse_str(user_id).map_err(|e| {
+            error!("Invalid UUID: {}", e);
+            ServiceError::Validation(format!("Invalid user_id: {}", e))
+        })?;
+
+        let db_err = |e: sqlx::Error| {
+            error!("Failed to get friends leaderboard: {}", e);
+            ServiceError::Database(e.to_string())
+        };
+
+        let referral_code: Option<String> = sqlx::query_scalar("SELECT referral_code FROM users WHERE id = $1")
+            .bind(user_uuid)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_err)?;
+        let Some(referral_code) = referral_code else {
+            return Ok(None);
+        };
+
+        // The user's own row comes back even when it falls outside the limit
+        let rows = sqlx::query_as::<_, (i64, String, String, i64, i64)>(
+            r#"
+            WITH friends AS (
+                SELECT $1::UUID AS id
+                UNION
+                SELECT inviter_id FROM referrals WHERE invitee_id = $1
+                UNION
+                SELECT invitee_id FROM referrals WHERE inviter_id = $1
+            ),
+            ranked AS (
+                SELECT
+                    ROW_NUMBER() OVER (ORDER BY u.total_clicks DESC, u.score_reached_at, u.id DESC) AS rank,
+                    COUNT(*) OVER () AS total_count,
+                    u.id,
+                    u.username,
+                    u.total_clicks
+                FROM friends f
+                JOIN users u ON u.id = f.id
+                WHERE u.id = $1
+                OR NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = u.id)
+            )
+            SELECT rank, id::text, username, total_clicks, total_count
+            FROM ranked
+            WHERE rank <= $2 OR id = $1
+            ORDER BY rank
+            "#,
+        )
+        .bind(user_uuid)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_err)?;
+
+        let user_rank = rows
+            .iter()
+            .find(|(_, id, _, _, _)| *id == user_id)
+            .map(|(rank, _, _, _, _)| *rank as i32)
+            .unwrap_or(0);
+        let total_count = rows.first().map(|row| row.4).unwrap_or(0);
+        let entries = rows
+            .into_iter()
+            .filter(|(rank, _, _, _, _)| *rank <= limit as i64)
+            .map(|(rank, user_id, username, total_clicks, _)| LeaderboardEntry {
+                rank,
+                user_id,
+                username,
+                total_clicks,
+            })
+            .collect();
+
+        Ok(Some(FriendsLeaderboard {
+            entries,
+            user_rank,
+            total_count,
+            referral_code,
+        }))
+    }
+
     pub async fn get_total_count(&self) -> Result<i64> {
         let (count,): (i64,) = sqlx::query_as(
             r#"
             SELECT COUNT(*)
             FROM users
             WHERE total_clicks > 0
+            AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = users.id)
             "#,
         )
         .fetch_one(&self.pool)
@@ -116,6 +262,21 @@ impl LeaderboardRepository {
         Ok(count)
     }
 
+    /// `get_total_count`, reused for `TOTAL_COUNT_TTL`. Concurrent callers wait
+    /// for one count instead of each running their own.
+    pub async fn get_total_count_cached(&self) -> Result<i64> {
+        let mut cached = self.total_count.lock().await;
+        if let Some((counted_at, count)) = *cached {
+            if counted_at.elapsed() < TOTAL_COUNT_TTL {
+                return Ok(count);
+            }
+        }
+
+        let count = self.get_total_count().await?;
+        *cached = Some((Instant::now(), count));
+        Ok(count)
+    }
+
     pub async fn update_score(&self, user_id: &str, username: &str, score: i64) -> Result<i32> {
         let user_uuid = uuid::Uuid::parse_str(user_id).map_err(|e| {
             error!("Invalid UUID: {}", e);
@@ -152,6 +313,30 @@ impl LeaderboardRepository {
         Ok(rank)
     }
 
+    /// One page of ranked users ordered by id, starting after `after`. Keyset
+    /// paging keeps each page cheap however far into the table it is.
+    pub async fn get_user_scores_page(&self, after: Option<uuid::Uuid>, limit: i64) -> Result<Vec<UserScore>> {
+        sqlx::query_as::<_, UserScore>(
+            r#"
+            SELECT id AS user_id, username, total_clicks, score_reached_at, score_version
+            FROM users
+            WHERE total_clicks > 0
+            AND ($1::UUID IS NULL OR id > $1)
+            AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = users.id)
+            ORDER BY id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch user scores page: {}", e);
+            ServiceError::Database(e.to_string())
+        })
+    }
+
     pub async fn get_global_stats(&self) -> Result<GlobalStats> {
         let stats = sqlx::query_as::<_, GlobalStats>(
             r#"
@@ -172,11 +357,18 @@ impl LeaderboardRepository {
         Ok(stats)
     }
 
+    /// Pages within `leaderboard_top_1000` come from the view, deeper pages
+    /// from the live ranking.
     pub async fn get_leaderboard_cached(
         &self,
         limit: i32,
         offset: i32,
     ) -> Result<Vec<LeaderboardEntry>> {
+        if offset.saturating_add(limit) > LEADERBOARD_VIEW_DEPTH {
+            debug!("Leaderboard page at offset {} is past the cached view, using real-time query", offset);
+            return self.get_leaderboard(limit, offset).await;
+        }
+
         let entries = sqlx::query_as::<_, LeaderboardEntry>(
             r#"
             SELECT
@@ -227,11 +419,13 @@ impl LeaderboardRepository {
         self.get_user_rank(user_id).await
     }
 
-    pub async fn refresh_leaderboard_cache(&self) -> Result<()> {
+    /// Refreshes `leaderboard_top_1000` unless another replica holds the
+    /// refresh lock, in which case it returns false without waiting.
+    pub async fn try_refresh_leaderboard_cache(&self) -> Result<bool> {
         let start = std::time::Instant::now();
 
-        sqlx::query("SELECT refresh_leaderboard()")
-            .execute(&self.pool)
+        let refreshed: bool = sqlx::query_scalar("SELECT try_refresh_leaderboard()")
+            .fetch_one(&self.pool)
             .await
             .map_err(|e| {
                 error!("Failed to refresh leaderboard cache: {}", e);
@@ -239,8 +433,8 @@ impl LeaderboardRepository {
             })?;
 
         let duration = start.elapsed();
-        debug!("Refreshed leaderboard cache in {:?}", duration);
+        debug!("Leaderboard cache refresh (refreshed: {}) took {:?}", refreshed, duration);
 
-        Ok(())
+        Ok(refreshed)
     }
 }
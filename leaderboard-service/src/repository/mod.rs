@@ -1,3 +1,5 @@
 mod leaderboard_repository;
 
-pub use leaderboard_repository::{GlobalStats, LeaderboardEntry, LeaderboardRepository};
+pub use leaderboard_repository::{
+    FriendsLeaderboard, GlobalStats, LeaderboardEntry, LeaderboardRepository, UserScore,
+};
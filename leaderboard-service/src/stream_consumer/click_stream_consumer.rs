@@ -1,43 +1,47 @@
-use crate::cache::{LeaderboardCache, StatsCache};
+use crate::cache::{AppliedClickEvent, LeaderboardCache, StreamEntry};
+use crate::stream_consumer::rank_updates::{RankUpdate, RANK_UPDATES_CHANNEL};
 use redis::aio::ConnectionManager;
-use redis::RedisError;
+use redis::streams::{StreamInfoGroupsReply, StreamMaxlen, StreamPendingReply, StreamRangeReply};
+use redis::{AsyncCommands, RedisError};
+use shared::config::StreamConsumerConfig;
 use shared::errors::{Result, ServiceError};
+use shared::events::{ClickStreamEvent, CLICK_DEAD_LETTER_STREAM_KEY, CLICK_STREAM_KEY};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
-const STREAM_KEY: &str = "clicks:stream";
+const STREAM_KEY: &str = CLICK_STREAM_KEY;
 const CONSUMER_GROUP: &str = "leaderboard-service";
-const CONSUMER_NAME: &str = "leaderboard-consumer-1";
 const BATCH_SIZE: usize = 100;
 const BLOCK_MS: usize = 5000;
-#[derive(Debug, Clone)]
-pub struct ClickEvent {
-    pub user_id: String,
-    pub username: String,
-    pub total_clicks: i64,
-    pub timestamp: i64,
-}
+/// Upper bound on claim rounds per maintenance tick, so a large backlog is
+/// worked through over several ticks instead of starving the tick's other jobs.
+const MAX_CLAIM_ROUNDS: usize = 10;
+const DEAD_LETTER_MAX_LEN: usize = 10_000;
 
 #[derive(Clone)]
 pub struct ClickStreamConsumer {
     redis: Arc<ConnectionManager>,
     leaderboard_cache: Arc<LeaderboardCache>,
-    stats_cache: Arc<StatsCache>,
+    /// Must be unique per replica; entries are pending against the consumer that read them.
+    consumer_name: Arc<str>,
+    config: StreamConsumerConfig,
 }
 
 impl ClickStreamConsumer {
     pub fn new(
         redis: ConnectionManager,
         leaderboard_cache: LeaderboardCache,
-        stats_cache: StatsCache,
+        consumer_name: impl Into<String>,
+        config: StreamConsumerConfig,
     ) -> Self {
         Self {
             redis: Arc::new(redis),
             leaderboard_cache: Arc::new(leaderboard_cache),
-            stats_cache: Arc::new(stats_cache),
+            consumer_name: consumer_name.into().into(),
+            config,
         }
     }
 
@@ -72,7 +76,7 @@ impl ClickStreamConsumer {
     }
 
     pub async fn start_consuming(&self) -> Result<()> {
-        info!("Starting click stream consumer");
+        info!("Starting click stream consumer {}", self.consumer_name);
 
         loop {
             match self.consume_batch().await {
@@ -96,7 +100,7 @@ impl ClickStreamConsumer {
             redis::cmd("XREADGROUP")
                 .arg("GROUP")
                 .arg(CONSUMER_GROUP)
-                .arg(CONSUMER_NAME)
+                .arg(self.consumer_name.as_ref())
                 .arg("COUNT")
                 .arg(BATCH_SIZE)
                 .arg("BLOCK")
@@ -116,43 +120,8 @@ impl ClickStreamConsumer {
 
                 for stream in streams {
                     if let redis::Value::Array(stream_data) = stream {
-                        if stream_data.len() >= 2 {
-                            if let redis::Value::Array(entries) = &stream_data[1] {
-                                for entry in entries {
-                                    if let redis::Value::Array(entry_data) = entry {
-                                        if entry_data.len() >= 2 {
-                                            let message_id = match &entry_data[0] {
-                                                redis::Value::BulkString(bytes) => {
-                                                    String::from_utf8_lossy(&bytes).to_string()
-                                                }
-                                                _ => continue,
-                                            };
-
-                                            if let redis::Value::Array(fields_array) = &entry_data[1] {
-                                                match self.parse_and_process_event(fields_array).await {
-                                                    Ok(_) => {
-                                                        let _: std::result::Result<i32, RedisError> =
-                                                            redis::cmd("XACK")
-                                                                .arg(STREAM_KEY)
-                                                                .arg(CONSUMER_GROUP)
-                                                                .arg(&message_id)
-                                                                .query_async(&mut conn)
-                                                                .await;
-
-                                                        processed += 1;
-                                                    }
-                                                    Err(e) => {
-                                                        error!(
-                                                            "Failed to process event {}: {}",
-                                                            message_id, e
-                                                        );
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                        if let Some(redis::Value::Array(entries)) = stream_data.get(1) {
+                            processed += self.process_entries(entries).await;
                         }
                     }
                 }
@@ -167,18 +136,229 @@ impl ClickStreamConsumer {
         }
     }
 
-    async fn parse_and_process_event(&self, fields_array: &[redis::Value]) -> Result<()> {
+    /// Applies and acks each `[id, [field, value, ...]]` entry. Failed entries
+    /// stay pending, to be claimed again once idle.
+    async fn process_entries(&self, entries: &[redis::Value]) -> usize {
+        let mut processed = 0;
+
+        for entry in entries {
+            let redis::Value::Array(entry_data) = entry else {
+                continue;
+            };
+            let (Some(redis::Value::BulkString(id)), Some(redis::Value::Array(fields_array))) =
+                (entry_data.first(), entry_data.get(1))
+            else {
+                continue;
+            };
+            let message_id = String::from_utf8_lossy(id).to_string();
+
+            match self.parse_and_process_event(&message_id, fields_array).await {
+                Ok(_) => processed += 1,
+                Err(e) => {
+                    error!("Failed to process event {}: {}", message_id, e);
+                    shared::record_counter("leaderboard.click_events.failed", 1);
+                }
+            }
+        }
+
+        processed
+    }
+
+    /// Periodically recovers entries left pending by crashed or failing
+    /// consumers and trims what every consumer is done with.
+    pub async fn start_maintenance(&self) {
+        let mut interval = tokio::time::interval(Duration::from_millis(self.config.maintenance_interval_ms));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.run_maintenance().await {
+                error!("Click stream maintenance failed: {}", e);
+            }
+        }
+    }
+
+    async fn run_maintenance(&self) -> Result<()> {
+        let dead_lettered = self.dead_letter_exhausted_entries().await?;
+        let claimed = self.claim_idle_entries().await?;
+        let trimmed = self.trim_stream().await?;
+        let pending = self.get_pending_count().await?;
+
+        shared::record_gauge("leaderboard.stream.pending", pending as f64);
+        if dead_lettered + claimed + trimmed > 0 {
+            info!(
+                "Click stream maintenance: {} claimed, {} dead-lettered, {} trimmed, {} pending",
+                claimed, dead_lettered, trimmed, pending
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Claims entries idle past `claim_idle_ms` from any consumer, including
+    /// this one, and processes them again. A claimed entry's delta still counts
+    /// when newer entries for the same user were applied in the meantime; only
+    /// its total yields to theirs.
+    async fn claim_idle_entries(&self) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+        let mut cursor = "0-0".to_string();
+        let mut processed = 0;
+
+        for _ in 0..MAX_CLAIM_ROUNDS {
+            let reply: Vec<redis::Value> = redis::cmd("XAUTOCLAIM")
+                .arg(STREAM_KEY)
+                .arg(CONSUMER_GROUP)
+                .arg(self.consumer_name.as_ref())
+                .arg(self.config.claim_idle_ms)
+                .arg(&cursor)
+                .arg("COUNT")
+                .arg(BATCH_SIZE)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            if let Some(redis::Value::Array(entries)) = reply.get(1) {
+                if !entries.is_empty() {
+                    shared::record_counter("leaderboard.click_events.claimed", entries.len() as u64);
+                }
+                processed += self.process_entries(entries).await;
+            }
+
+            cursor = match reply.first() {
+                Some(redis::Value::BulkString(next)) => String::from_utf8_lossy(next).to_string(),
+                _ => break,
+            };
+            if cursor == "0-0" {
+                break;
+            }
+        }
+
+        Ok(processed)
+    }
+
+    /// Moves idle entries that have been delivered `max_deliveries` times to the
+    /// dead-letter stream and acks them, so one bad event can't be retried forever.
+    async fn dead_letter_exhausted_entries(&self) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let pending: Vec<(String, String, u64, u64)> = redis::cmd("XPENDING")
+            .arg(STREAM_KEY)
+            .arg(CONSUMER_GROUP)
+            .arg("IDLE")
+            .arg(self.config.claim_idle_ms)
+            .arg("-")
+            .arg("+")
+            .arg(BATCH_SIZE)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+        let mut dead_lettered = 0;
+        for (message_id, consumer, _idle, deliveries) in pending {
+            if deliveries < self.config.max_deliveries {
+                continue;
+            }
+
+            let range: StreamRangeReply = conn
+                .xrange(STREAM_KEY, &message_id, &message_id)
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            // A trimmed entry has nothing left to keep, it only needs acking
+            if let Some(entry) = range.ids.first() {
+                let mut fields: Vec<(String, String)> = entry
+                    .map
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        redis::from_redis_value::<String>(value)
+                            .ok()
+                            .map(|value| (key.clone(), value))
+                    })
+                    .collect();
+                fields.push(("original_id".to_string(), message_id.clone()));
+                fields.push(("consumer".to_string(), consumer.clone()));
+                fields.push(("deliveries".to_string(), deliveries.to_string()));
+
+                conn.xadd_maxlen::<_, _, _, _, ()>(
+                    CLICK_DEAD_LETTER_STREAM_KEY,
+                    StreamMaxlen::Approx(DEAD_LETTER_MAX_LEN),
+                    "*",
+                    &fields,
+                )
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+            }
+
+            conn.xack::<_, _, _, ()>(STREAM_KEY, CONSUMER_GROUP, &[&message_id])
+                .await
+                .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+            warn!(
+                "Dead-lettered click event {} after {} deliveries (last consumer {})",
+                message_id, deliveries, consumer
+            );
+            dead_lettered += 1;
+        }
+
+        if dead_lettered > 0 {
+            shared::record_counter("leaderboard.click_events.dead_lettered", dead_lettered as u64);
+        }
+        Ok(dead_lettered)
+    }
+
+    /// Trims entries older than anything the group still needs: the oldest
+    /// pending entry, or the last delivered one if nothing is pending. Assumes
+    /// this group is the stream's only reader.
+    async fn trim_stream(&self) -> Result<usize> {
+        let mut conn = self.redis.as_ref().clone();
+
+        let pending: StreamPendingReply = conn
+            .xpending(STREAM_KEY, CONSUMER_GROUP)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+        let min_id = match pending {
+            StreamPendingReply::Data(data) => data.start_id,
+            StreamPendingReply::Empty => {
+                let groups: StreamInfoGroupsReply = conn
+                    .xinfo_groups(STREAM_KEY)
+                    .await
+                    .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+                match groups.groups.into_iter().find(|group| group.name == CONSUMER_GROUP) {
+                    Some(group) => group.last_delivered_id,
+                    None => return Ok(0),
+                }
+            }
+        };
+
+        let trimmed: usize = redis::cmd("XTRIM")
+            .arg(STREAM_KEY)
+            .arg("MINID")
+            .arg("~")
+            .arg(&min_id)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: RedisError| ServiceError::Redis(e.to_string()))?;
+
+        if trimmed > 0 {
+            shared::record_counter("leaderboard.stream.trimmed", trimmed as u64);
+        }
+        Ok(trimmed)
+    }
+
+    async fn parse_and_process_event(&self, message_id: &str, fields_array: &[redis::Value]) -> Result<()> {
         let mut fields = HashMap::new();
 
         for chunk in fields_array.chunks(2) {
             if chunk.len() == 2 {
                 let key = match &chunk[0] {
-                    redis::Value::BulkString(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                    redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).to_string(),
                     _ => continue,
                 };
 
                 let value = match &chunk[1] {
-                    redis::Value::BulkString(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                    redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).to_string(),
                     _ => continue,
                 };
 
@@ -186,46 +366,84 @@ impl ClickStreamConsumer {
             }
         }
 
-        self.process_event(&fields).await
+        self.process_event(message_id, &fields).await
     }
 
-    async fn process_event(&self, fields: &HashMap<String, String>) -> Result<()> {
-        let user_id = fields
-            .get("user_id")
-            .ok_or_else(|| ServiceError::Validation("Missing user_id field".to_string()))?;
-
-        let username = fields
-            .get("username")
-            .ok_or_else(|| ServiceError::Validation("Missing username field".to_string()))?;
-
-        let total_clicks = fields
-            .get("total_clicks")
-            .and_then(|s| s.parse::<i64>().ok())
-            .ok_or_else(|| ServiceError::Validation("Invalid total_clicks field".to_string()))?;
-
-        let _timestamp = fields
-            .get("timestamp")
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or(0);
+    /// Applies the event and acks its entry in one step, so neither a crash
+    /// between the two nor another consumer claiming the entry can count it twice.
+    async fn process_event(&self, message_id: &str, fields: &HashMap<String, String>) -> Result<()> {
+        let event = ClickStreamEvent::from_fields(fields)?;
 
         debug!(
-            "Processing click event: user={}, username={}, clicks={}",
-            user_id, username, total_clicks
+            "Processing click event: user={}, username={}, clicks={}, delta={:?}, seq={:?}",
+            event.user_id, event.username, event.total_clicks, event.delta, event.seq
         );
 
-        let new_rank = self
+        let entry = StreamEntry {
+            stream: STREAM_KEY,
+            group: CONSUMER_GROUP,
+            id: message_id,
+        };
+        // Ties on the board go to whoever reached the score first, by the same
+        // clock Postgres ranks on; older events only have their publish time
+        let reached_at = chrono::DateTime::from_timestamp(event.reached_at.unwrap_or(event.timestamp), 0)
+            .unwrap_or_else(chrono::Utc::now);
+
+        match self
             .leaderboard_cache
-            .update_score(user_id, username, total_clicks)
-            .await?;
+            .apply_click_event(entry, &event, reached_at, chrono::Utc::now())
+            .await?
+        {
+            AppliedClickEvent::Ranked(new_rank) => {
+                debug!("Updated leaderboard: user={}, new_rank={}", event.user_id, new_rank);
+
+                self.publish_rank_update(RankUpdate {
+                    user_id: event.user_id,
+                    username: event.username,
+                    rank: new_rank,
+                    total_clicks: event.total_clicks,
+                })
+                .await;
+            }
+            AppliedClickEvent::Superseded => {
+                debug!("Kept newer total on the board: user={}, seq={:?}", event.user_id, event.seq);
+                shared::record_counter("leaderboard.click_events.out_of_order", 1);
+            }
+            // Shadow-banned and frozen users keep their clicks but never reach a board
+            AppliedClickEvent::Hidden => {
+                debug!("Keeping hidden user off the leaderboard: user={}", event.user_id);
+                shared::record_counter("leaderboard.click_events.hidden", 1);
+            }
+            AppliedClickEvent::AlreadyApplied => {
+                debug!("Skipping replayed click event: user={}, entry={}", event.user_id, message_id);
+                shared::record_counter("leaderboard.click_events.replayed", 1);
+            }
+        }
 
-        debug!(
-            "Updated leaderboard: user={}, new_rank={}",
-            user_id, new_rank
-        );
+        Ok(())
+    }
+
+    /// Best effort: a missed rank update is corrected by the next one, so it
+    /// must not fail the event.
+    async fn publish_rank_update(&self, update: RankUpdate) {
+        let payload = match serde_json::to_string(&update) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize rank update for {}: {}", update.user_id, e);
+                return;
+            }
+        };
 
-        self.stats_cache.increment_total_clicks(1).await?;
+        let mut conn = self.redis.as_ref().clone();
+        let result: std::result::Result<i64, RedisError> = redis::cmd("PUBLISH")
+            .arg(RANK_UPDATES_CHANNEL)
+            .arg(payload)
+            .query_async(&mut conn)
+            .await;
 
-        Ok(())
+        if let Err(e) = result {
+            warn!("Failed to publish rank update for {}: {}", update.user_id, e);
+        }
     }
 
     pub async fn get_pending_count(&self) -> Result<usize> {
@@ -249,45 +467,214 @@ impl ClickStreamConsumer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::{LeaderboardPeriod, StatsCache};
+
+    async fn test_connection() -> ConnectionManager {
+        let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6380".to_string());
+        let client = redis::Client::open(redis_url).expect("Invalid REDIS_URL");
+        ConnectionManager::new(client).await.unwrap()
+    }
+
+    async fn test_consumer(conn: &ConnectionManager) -> ClickStreamConsumer {
+        let consumer = ClickStreamConsumer::new(
+            conn.clone(),
+            LeaderboardCache::new(conn.clone()),
+            "test-consumer",
+            StreamConsumerConfig::default(),
+        );
+        consumer.init_consumer_group().await.unwrap();
+        consumer
+    }
+
+    async fn publish(conn: &mut ConnectionManager, user_id: &str, total: i64, delta: i64, seq: u64) {
+        let fields = [
+            ("user_id", user_id.to_string()),
+            ("username", "StreamUser".to_string()),
+            ("total_clicks", total.to_string()),
+            ("delta", delta.to_string()),
+            ("seq", seq.to_string()),
+            ("timestamp", chrono::Utc::now().timestamp().to_string()),
+        ];
+        conn.xadd::<_, _, _, _, String>(STREAM_KEY, "*", &fields).await.unwrap();
+    }
+
+    async fn daily_clicks(consumer: &ClickStreamConsumer, user_id: &str) -> Option<i64> {
+        consumer
+            .leaderboard_cache
+            .get_period_user_rank(LeaderboardPeriod::Daily, user_id)
+            .await
+            .unwrap()
+            .map(|standing| standing.total_clicks)
+    }
 
     #[tokio::test]
-    #[ignore]
+    #[ignore] // Requires Redis
     async fn test_init_consumer_group() {
-        let client = redis::Client::open("redis://127.0.0.1:6380").unwrap();
-        let conn = ConnectionManager::new(client).await.unwrap();
-
-        let leaderboard_cache = LeaderboardCache::new(conn.clone());
-        let stats_cache = StatsCache::new(conn.clone());
-        let consumer = ClickStreamConsumer::new(conn, leaderboard_cache, stats_cache);
+        let conn = test_connection().await;
+        let consumer = ClickStreamConsumer::new(
+            conn.clone(),
+            LeaderboardCache::new(conn),
+            "test-consumer",
+            StreamConsumerConfig::default(),
+        );
 
         let result = consumer.init_consumer_group().await;
         assert!(result.is_ok());
     }
 
     #[tokio::test]
-    #[ignore]
+    #[ignore] // Requires Redis
     async fn test_process_event() {
-        let client = redis::Client::open("redis://127.0.0.1:6380").unwrap();
-        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut conn = test_connection().await;
+        let consumer = test_consumer(&conn).await;
+        let user_id = "stream-test-user";
+        consumer.leaderboard_cache.remove_user(user_id).await.unwrap();
 
-        let leaderboard_cache = LeaderboardCache::new(conn.clone());
-        let stats_cache = StatsCache::new(conn.clone());
-        let consumer = ClickStreamConsumer::new(conn, leaderboard_cache, stats_cache);
+        publish(&mut conn, user_id, 42, 42, 1).await;
+        assert!(consumer.consume_batch().await.unwrap() >= 1);
 
-        let mut fields = HashMap::new();
-        fields.insert("user_id".to_string(), "test-user".to_string());
-        fields.insert("username".to_string(), "TestUser".to_string());
-        fields.insert("total_clicks".to_string(), "42".to_string());
-        fields.insert("timestamp".to_string(), "1234567890".to_string());
+        let rank = consumer.leaderboard_cache.get_user_rank(user_id).await.unwrap();
+        assert!(rank > 0);
 
-        let result = consumer.process_event(&fields).await;
-        assert!(result.is_ok());
+        consumer.leaderboard_cache.remove_user(user_id).await.unwrap();
+    }
 
-        let rank = consumer
-            .leaderboard_cache
-            .get_user_rank("test-user")
+    /// Seq 2 delivered before seq 1: both deltas count, and the newer total
+    /// stays on the all-time board. Redelivering either changes nothing.
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_out_of_order_events_all_count_once() {
+        let mut conn = test_connection().await;
+        let consumer = test_consumer(&conn).await;
+        let stats = StatsCache::new(conn.clone());
+        let user_id = format!("stream-order-{}", uuid::Uuid::new_v4());
+        let clicks_before = stats.get_total_clicks().await.unwrap();
+
+        publish(&mut conn, &user_id, 30, 20, 2).await;
+        publish(&mut conn, &user_id, 10, 10, 1).await;
+        assert!(consumer.consume_batch().await.unwrap() >= 2);
+
+        assert_eq!(daily_clicks(&consumer, &user_id).await, Some(30));
+        assert_eq!(consumer.leaderboard_cache.get_user_score(&user_id).await.unwrap(), Some(30));
+        assert!(stats.get_total_clicks().await.unwrap() - clicks_before >= 30);
+
+        let replay: StreamRangeReply = conn.xrange_all(STREAM_KEY).await.unwrap();
+        let user_entries = replay
+            .ids
+            .iter()
+            .filter(|entry| entry.get::<String>("user_id").as_deref() == Some(user_id.as_str()));
+        for entry in user_entries {
+            let fields: HashMap<String, String> = entry
+                .map
+                .iter()
+                .filter_map(|(key, value)| redis::from_redis_value(value).ok().map(|value| (key.clone(), value)))
+                .collect();
+            consumer.process_event(&entry.id, &fields).await.unwrap();
+        }
+        assert_eq!(daily_clicks(&consumer, &user_id).await, Some(30));
+
+        consumer.leaderboard_cache.remove_user(&user_id).await.unwrap();
+    }
+
+    /// An entry left pending by a consumer that died is reclaimed after a newer
+    /// entry for the same user was applied, and its delta still lands.
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_reclaimed_entry_counts_after_newer_ones() {
+        let mut conn = test_connection().await;
+        let config = StreamConsumerConfig {
+            claim_idle_ms: 0,
+            ..StreamConsumerConfig::default()
+        };
+        let consumer = ClickStreamConsumer::new(
+            conn.clone(),
+            LeaderboardCache::new(conn.clone()),
+            "test-consumer",
+            config,
+        );
+        consumer.init_consumer_group().await.unwrap();
+        let user_id = format!("stream-claim-{}", uuid::Uuid::new_v4());
+
+        // Read by a consumer that never gets to apply it
+        publish(&mut conn, &user_id, 10, 10, 1).await;
+        let _: redis::Value = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(CONSUMER_GROUP)
+            .arg("crashed-consumer")
+            .arg("COUNT")
+            .arg(BATCH_SIZE)
+            .arg("STREAMS")
+            .arg(STREAM_KEY)
+            .arg(">")
+            .query_async(&mut conn)
             .await
             .unwrap();
-        assert!(rank > 0);
+
+        publish(&mut conn, &user_id, 30, 20, 2).await;
+        assert!(consumer.consume_batch().await.unwrap() >= 1);
+        assert_eq!(daily_clicks(&consumer, &user_id).await, Some(20));
+
+        assert!(consumer.claim_idle_entries().await.unwrap() >= 1);
+        assert_eq!(daily_clicks(&consumer, &user_id).await, Some(30));
+        assert_eq!(consumer.leaderboard_cache.get_user_score(&user_id).await.unwrap(), Some(30));
+
+        consumer.leaderboard_cache.remove_user(&user_id).await.unwrap();
+    }
+
+    /// An admin taking clicks away lowers the period boards too, but never
+    /// below zero.
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_negative_delta_lowers_period_boards() {
+        let mut conn = test_connection().await;
+        let consumer = test_consumer(&conn).await;
+        let user_id = format!("stream-adjust-{}", uuid::Uuid::new_v4());
+
+        publish(&mut conn, &user_id, 50, 50, 1).await;
+        publish(&mut conn, &user_id, 20, -30, 2).await;
+        assert!(consumer.consume_batch().await.unwrap() >= 2);
+        assert_eq!(daily_clicks(&consumer, &user_id).await, Some(20));
+        assert_eq!(consumer.leaderboard_cache.get_user_score(&user_id).await.unwrap(), Some(20));
+
+        publish(&mut conn, &user_id, 0, -100, 3).await;
+        assert!(consumer.consume_batch().await.unwrap() >= 1);
+        assert_eq!(daily_clicks(&consumer, &user_id).await, None);
+
+        consumer.leaderboard_cache.remove_user(&user_id).await.unwrap();
+    }
+
+    /// A total applied while a rebuild stages users survives the swap, even if
+    /// the user's page was read from Postgres before it and staged after.
+    #[tokio::test]
+    #[ignore] // Requires Redis
+    async fn test_event_during_rebuild_survives_swap() {
+        let mut conn = test_connection().await;
+        let consumer = test_consumer(&conn).await;
+        let cache = &consumer.leaderboard_cache;
+        let user_id = format!("stream-rebuild-{}", uuid::Uuid::new_v4());
+
+        cache.begin_rebuild(std::time::Duration::from_secs(5)).await.unwrap();
+
+        let fields = [
+            ("user_id", user_id.clone()),
+            ("username", "StreamUser".to_string()),
+            ("total_clicks", "80".to_string()),
+            ("delta", "30".to_string()),
+            ("score_version", "5".to_string()),
+            ("timestamp", chrono::Utc::now().timestamp().to_string()),
+        ];
+        conn.xadd::<_, _, _, _, String>(STREAM_KEY, "*", &fields).await.unwrap();
+        assert!(consumer.consume_batch().await.unwrap() >= 1);
+
+        let staged = cache
+            .stage_rebuild_page(&[(user_id.clone(), "StreamUser".to_string(), 50, chrono::Utc::now(), 4)])
+            .await
+            .unwrap();
+        assert_eq!(staged, 0);
+
+        cache.finish_rebuild().await.unwrap();
+        assert_eq!(cache.get_user_score(&user_id).await.unwrap(), Some(80));
+
+        cache.remove_user(&user_id).await.unwrap();
     }
 }
@@ -1,3 +1,5 @@
 pub mod click_stream_consumer;
+pub mod rank_updates;
 
 pub use click_stream_consumer::ClickStreamConsumer;
+pub use rank_updates::{RankUpdate, RankUpdateHub, RANK_UPDATES_CHANNEL};
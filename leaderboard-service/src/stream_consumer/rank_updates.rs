@@ -0,0 +1,118 @@
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+/// Redis pub/sub channel the stream consumer announces rank changes on. Every
+/// leaderboard-service instance relays it, since each consumer in the group only
+/// sees part of the click stream.
+pub const RANK_UPDATES_CHANNEL: &str = "leaderboard:rank_updates";
+
+const RELAY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankUpdate {
+    pub user_id: String,
+    pub username: String,
+    pub rank: i32,
+    pub total_clicks: i64,
+}
+
+/// Fans rank updates received over Redis pub/sub out to local gRPC subscribers.
+#[derive(Clone)]
+pub struct RankUpdateHub {
+    sender: broadcast::Sender<RankUpdate>,
+}
+
+impl RankUpdateHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RankUpdate> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish_local(&self, update: RankUpdate) {
+        // No receivers just means no bot-service is subscribed right now
+        let _ = self.sender.send(update);
+    }
+
+    pub fn start_relay(&self, client: redis::Client) {
+        let hub = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = hub.relay(&client).await {
+                    error!("Rank update relay failed: {}", e);
+                }
+                warn!("Rank update relay disconnected, retrying in {:?}", RELAY_RETRY_DELAY);
+                tokio::time::sleep(RELAY_RETRY_DELAY).await;
+            }
+        });
+    }
+
+    async fn relay(&self, client: &redis::Client) -> redis::RedisResult<()> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(RANK_UPDATES_CHANNEL).await?;
+        info!("Relaying rank updates from {}", RANK_UPDATES_CHANNEL);
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let payload: String = match message.get_payload() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Ignoring non-text rank update: {}", e);
+                    continue;
+                }
+            };
+
+            match serde_json::from_str::<RankUpdate>(&payload) {
+                Ok(update) => {
+                    debug!("Relaying rank update: user={}, rank={}", update.user_id, update.rank);
+                    self.publish_local(update);
+                }
+                Err(e) => warn!("Ignoring malformed rank update: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_update_round_trip() {
+        let update = RankUpdate {
+            user_id: "user-a".to_string(),
+            username: "alice".to_string(),
+            rank: 3,
+            total_clicks: 1200,
+        };
+
+        let json = serde_json::to_string(&update).unwrap();
+        assert_eq!(serde_json::from_str::<RankUpdate>(&json).unwrap(), update);
+    }
+
+    #[tokio::test]
+    async fn test_hub_fans_out_to_subscribers() {
+        let hub = RankUpdateHub::new(16);
+        let mut first = hub.subscribe();
+        let mut second = hub.subscribe();
+
+        hub.publish_local(RankUpdate {
+            user_id: "user-a".to_string(),
+            username: "alice".to_string(),
+            rank: 1,
+            total_clicks: 10,
+        });
+
+        assert_eq!(first.recv().await.unwrap().rank, 1);
+        assert_eq!(second.recv().await.unwrap().user_id, "user-a");
+    }
+}
@@ -0,0 +1,28 @@
+#![allow(dead_code)]
+
+use sqlx::PgPool;
+
+/// Inserts a user with a username derived from `telegram_id`, returning their id.
+pub async fn insert_user(pool: &PgPool, telegram_id: i64, total_clicks: i64) -> anyhow::Result<String> {
+    let (id,): (uuid::Uuid,) = sqlx::query_as(
+        "INSERT INTO users (telegram_id, username, total_clicks) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(telegram_id)
+    .bind(format!("user{}", telegram_id))
+    .bind(total_clicks)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id.to_string())
+}
+
+/// Records that `inviter` invited `invitee`.
+pub async fn refer(pool: &PgPool, inviter: &str, invitee: &str) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO referrals (invitee_id, inviter_id, bonus_clicks) VALUES ($1::UUID, $2::UUID, 0)")
+        .bind(invitee)
+        .bind(inviter)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{insert_user, refer};
+use leaderboard_service::LeaderboardRepository;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_user_standing_skips_hidden_users(pool: PgPool) -> anyhow::Result<()> {
+    let repository = LeaderboardRepository::new(pool.clone());
+
+    let cheater = insert_user(&pool, 1, 900).await?;
+    insert_user(&pool, 2, 500).await?;
+    insert_user(&pool, 3, 300).await?;
+    let user = insert_user(&pool, 4, 100).await?;
+    insert_user(&pool, 5, 0).await?;
+
+    sqlx::query("INSERT INTO user_restrictions (user_id, hidden_from_leaderboard) VALUES ($1::UUID, TRUE)")
+        .bind(&cheater)
+        .execute(&pool)
+        .await?;
+
+    let standing = repository.get_user_standing(&user).await?.unwrap();
+    assert_eq!(standing.rank, 3);
+    assert_eq!(standing.total_clicks, 100);
+    assert_eq!(standing.ranked_users, 3);
+    assert_eq!(standing.percentile(), 100.0);
+
+    assert!(repository.get_user_standing(&cheater).await?.is_none());
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_cached_total_count_is_reused(pool: PgPool) -> anyhow::Result<()> {
+    let repository = LeaderboardRepository::new(pool.clone());
+
+    insert_user(&pool, 1, 500).await?;
+    assert_eq!(repository.get_total_count_cached().await?, 1);
+
+    // Still the earlier count, but a standing never ranks past it
+    let user = insert_user(&pool, 2, 100).await?;
+    assert_eq!(repository.get_total_count_cached().await?, 1);
+    assert_eq!(repository.get_total_count().await?, 2);
+
+    let standing = repository.get_user_standing(&user).await?.unwrap();
+    assert_eq!(standing.rank, 2);
+    assert_eq!(standing.ranked_users, 2);
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_friends_leaderboard_covers_the_referral_graph(pool: PgPool) -> anyhow::Result<()> {
+    let repository = LeaderboardRepository::new(pool.clone());
+
+    let inviter = insert_user(&pool, 1, 500).await?;
+    let user = insert_user(&pool, 2, 100).await?;
+    let invitee = insert_user(&pool, 3, 300).await?;
+    let idle_invitee = insert_user(&pool, 4, 0).await?;
+    let cheater = insert_user(&pool, 5, 900).await?;
+    // Invited by the user's inviter, so not one of the user's friends
+    let sibling = insert_user(&pool, 6, 200).await?;
+
+    refer(&pool, &inviter, &user).await?;
+    refer(&pool, &user, &invitee).await?;
+    refer(&pool, &user, &idle_invitee).await?;
+    refer(&pool, &user, &cheater).await?;
+    refer(&pool, &inviter, &sibling).await?;
+
+    sqlx::query("INSERT INTO user_restrictions (user_id, banned) VALUES ($1::UUID, TRUE)")
+        .bind(&cheater)
+        .execute(&pool)
+        .await?;
+
+    let board = repository.get_friends_leaderboard(&user, 20).await?.unwrap();
+    let order: Vec<&str> = board.entries.iter().map(|e| e.user_id.as_str()).collect();
+    assert_eq!(order, vec![inviter.as_str(), invitee.as_str(), user.as_str(), idle_invitee.as_str()]);
+    assert_eq!(board.user_rank, 3);
+    assert_eq!(board.total_count, 4);
+    assert_eq!(board.referral_code.len(), 12);
+
+    // The user's rank survives a limit that cuts them off
+    let board = repository.get_friends_leaderboard(&user, 2).await?.unwrap();
+    assert_eq!(board.entries.len(), 2);
+    assert_eq!(board.user_rank, 3);
+    assert_eq!(board.total_count, 4);
+
+    let missing = uuid::Uuid::new_v4().to_string();
+    assert!(repository.get_friends_leaderboard(&missing, 20).await?.is_none());
+
+    Ok(())
+}
+
+/// Orders `(user_id, total_clicks, reached_at)` the way Redis does, by
+/// writing them to the all-time board with the live scoring script and
+/// reading their ranks back.
+async fn redis_order(users: &[(String, i64, chrono::DateTime<chrono::Utc>)]) -> anyhow::Result<Vec<String>> {
+    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6380".to_string());
+    let client = redis::Client::open(redis_url)?;
+    let cache = leaderboard_service::LeaderboardCache::new(redis::aio::ConnectionManager::new(client).await?);
+
+    let mut ranked = Vec::new();
+    for (user_id, clicks, reached_at) in users {
+        cache.update_score(user_id, "RankTest", *clicks, *reached_at).await?;
+    }
+    for (user_id, _, _) in users {
+        ranked.push((cache.get_user_rank(user_id).await?, user_id.clone()));
+        cache.remove_user(user_id).await?;
+    }
+
+    ranked.sort();
+    Ok(ranked.into_iter().map(|(_, user_id)| user_id).collect())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+#[ignore] // Requires Redis
+async fn test_sql_ranks_agree_with_redis_board_scores(pool: PgPool) -> anyhow::Result<()> {
+    use chrono::{Duration, TimeZone, Utc};
+
+    let repository = LeaderboardRepository::new(pool.clone());
+    let base = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+
+    // Equal scores reached at different times, and at the very same second
+    let mut users = Vec::new();
+    for (telegram_id, clicks, offset) in [(1, 70, 0), (2, 50, 30), (3, 50, 10), (4, 50, 10), (5, 50, 10), (6, 20, 0)] {
+        let user_id = insert_user(&pool, telegram_id, clicks).await?;
+        let reached_at = base + Duration::seconds(offset);
+        sqlx::query("UPDATE users SET score_reached_at = $1 WHERE id = $2::UUID")
+            .bind(reached_at)
+            .bind(&user_id)
+            .execute(&pool)
+            .await?;
+        users.push((user_id, clicks, reached_at));
+    }
+    let expected = redis_order(&users).await?;
+
+    let live: Vec<String> = repository.get_leaderboard(10, 0).await?.into_iter().map(|e| e.user_id).collect();
+    assert_eq!(live, expected);
+
+    assert!(repository.try_refresh_leaderboard_cache().await?);
+    let cached = repository.get_leaderboard_cached(10, 0).await?;
+    assert_eq!(cached.iter().map(|e| e.user_id.clone()).collect::<Vec<_>>(), expected);
+
+    for (position, user_id) in expected.iter().enumerate() {
+        let rank = position as i32 + 1;
+        assert_eq!(repository.get_user_rank(user_id).await?.map(|(r, _)| r), Some(rank));
+        assert_eq!(cached[position].rank, rank as i64);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_score_reached_at_only_moves_with_the_score(pool: PgPool) -> anyhow::Result<()> {
+    let user_id = insert_user(&pool, 1, 10).await?;
+    sqlx::query("UPDATE users SET score_reached_at = '2025-01-01T00:00:00Z' WHERE id = $1::UUID")
+        .bind(&user_id)
+        .execute(&pool)
+        .await?;
+
+    let reached_at = || async {
+        let (at,): (chrono::DateTime<chrono::Utc>,) =
+            sqlx::query_as("SELECT score_reached_at FROM users WHERE id = $1::UUID")
+                .bind(&user_id)
+                .fetch_one(&pool)
+                .await?;
+        anyhow::Ok(at)
+    };
+    let original = reached_at().await?;
+
+    sqlx::query("UPDATE users SET username = 'renamed', total_clicks = total_clicks WHERE id = $1::UUID")
+        .bind(&user_id)
+        .execute(&pool)
+        .await?;
+    assert_eq!(reached_at().await?, original);
+
+    sqlx::query("UPDATE users SET total_clicks = total_clicks + 1 WHERE id = $1::UUID")
+        .bind(&user_id)
+        .execute(&pool)
+        .await?;
+    assert!(reached_at().await? > original);
+
+    Ok(())
+}
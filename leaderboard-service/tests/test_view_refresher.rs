@@ -0,0 +1,37 @@
+use leaderboard_service::refresh::view_refresher::LEADERBOARD_CHANGED_CHANNEL;
+use leaderboard_service::LeaderboardRepository;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[sqlx::test(migrations = "../migrations")]
+async fn test_score_change_notifies_and_refresh_respects_lock(pool: PgPool) -> anyhow::Result<()> {
+    let mut listener = PgListener::connect_with(&pool).await?;
+    listener.listen(LEADERBOARD_CHANGED_CHANNEL).await?;
+
+    sqlx::query("INSERT INTO users (telegram_id, username, total_clicks) VALUES (1, 'alice', 42)")
+        .execute(&pool)
+        .await?;
+    let notification = tokio::time::timeout(Duration::from_secs(5), listener.recv()).await??;
+    assert_eq!(notification.payload(), "users");
+
+    let repository = LeaderboardRepository::new(pool.clone());
+
+    let mut holder = pool.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock(hashtext('leaderboard_top_1000'))")
+        .execute(&mut *holder)
+        .await?;
+    assert!(!repository.try_refresh_leaderboard_cache().await?);
+
+    sqlx::query("SELECT pg_advisory_unlock(hashtext('leaderboard_top_1000'))")
+        .execute(&mut *holder)
+        .await?;
+    assert!(repository.try_refresh_leaderboard_cache().await?);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM leaderboard_top_1000 WHERE username = 'alice'")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
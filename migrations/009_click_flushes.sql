@@ -0,0 +1,13 @@
+-- Flushes of the Redis click accumulator that have been committed to users.total_clicks.
+-- Inserting the flush id in the same transaction as the increments makes a retried
+-- or recovered flush a no-op instead of counting its clicks twice.
+CREATE TABLE IF NOT EXISTS click_flushes (
+    flush_id UUID PRIMARY KEY,
+    shard_id INTEGER NOT NULL,
+    user_count INTEGER NOT NULL,
+    total_clicks BIGINT NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_click_flushes_applied_at
+ON click_flushes(applied_at);
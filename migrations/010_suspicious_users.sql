@@ -0,0 +1,45 @@
+-- Users the anti-cheat has caught. A row only ever escalates: 'flag' marks the
+-- user for review, 'shadow_ban' keeps them off the leaderboard while their clicks
+-- still count, and 'freeze' also refuses their clicks.
+CREATE TABLE IF NOT EXISTS suspicious_users (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    score DOUBLE PRECISION NOT NULL,
+    signals TEXT[] NOT NULL DEFAULT '{}',
+    action VARCHAR(16) NOT NULL CHECK (action IN ('flag', 'shadow_ban', 'freeze')),
+    first_detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_suspicious_users_action
+ON suspicious_users(action);
+
+
+-- Rebuild the leaderboard view without shadow-banned and frozen users
+DROP MATERIALIZED VIEW IF EXISTS leaderboard_top_1000;
+
+CREATE MATERIALIZED VIEW leaderboard_top_1000 AS
+SELECT
+    DENSE_RANK() OVER (ORDER BY u.total_clicks DESC) as rank,
+    u.id::text as user_id,
+    u.username,
+    u.total_clicks,
+    u.updated_at
+FROM users u
+WHERE u.total_clicks > 0
+AND NOT EXISTS (
+    SELECT 1 FROM suspicious_users s
+    WHERE s.user_id = u.id AND s.action IN ('shadow_ban', 'freeze')
+)
+ORDER BY u.total_clicks DESC
+LIMIT 1000;
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_mv_rank
+ON leaderboard_top_1000(rank);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_leaderboard_mv_user_id
+ON leaderboard_top_1000(user_id);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_mv_rank_username
+ON leaderboard_top_1000(rank, username);
+
+GRANT SELECT ON leaderboard_top_1000 TO postgres;
@@ -0,0 +1,60 @@
+-- Restrictions admins place on users. A banned user's clicks are refused; either
+-- flag keeps the user off the leaderboard.
+CREATE TABLE IF NOT EXISTS user_restrictions (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    banned BOOLEAN NOT NULL DEFAULT FALSE,
+    hidden_from_leaderboard BOOLEAN NOT NULL DEFAULT FALSE,
+    reason TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Every admin action. user_id has no foreign key so entries outlive the user.
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor VARCHAR(255) NOT NULL,
+    action VARCHAR(32) NOT NULL,
+    user_id UUID,
+    reason TEXT,
+    details TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_log_user_id
+ON admin_audit_log(user_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_log_created_at
+ON admin_audit_log(created_at DESC);
+
+
+-- Everyone kept off the leaderboard, by the anti-cheat or by an admin
+CREATE OR REPLACE VIEW hidden_users AS
+SELECT user_id FROM suspicious_users WHERE action IN ('shadow_ban', 'freeze')
+UNION
+SELECT user_id FROM user_restrictions WHERE banned OR hidden_from_leaderboard;
+
+
+DROP MATERIALIZED VIEW IF EXISTS leaderboard_top_1000;
+
+CREATE MATERIALIZED VIEW leaderboard_top_1000 AS
+SELECT
+    DENSE_RANK() OVER (ORDER BY u.total_clicks DESC) as rank,
+    u.id::text as user_id,
+    u.username,
+    u.total_clicks,
+    u.updated_at
+FROM users u
+WHERE u.total_clicks > 0
+AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = u.id)
+ORDER BY u.total_clicks DESC
+LIMIT 1000;
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_mv_rank
+ON leaderboard_top_1000(rank);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_leaderboard_mv_user_id
+ON leaderboard_top_1000(user_id);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_mv_rank_username
+ON leaderboard_top_1000(rank, username);
+
+GRANT SELECT ON leaderboard_top_1000 TO postgres;
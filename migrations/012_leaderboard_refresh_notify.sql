@@ -0,0 +1,44 @@
+-- Tells leaderboard-service that leaderboard_top_1000 is out of date, so it can
+-- refresh on change instead of on a fixed timer. Statement-level, so a flush of
+-- thousands of users sends one notification, delivered when it commits.
+CREATE OR REPLACE FUNCTION notify_leaderboard_changed()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    PERFORM pg_notify('leaderboard_changed', TG_TABLE_NAME);
+    RETURN NULL;
+END;
+$$;
+
+DROP TRIGGER IF EXISTS trg_users_leaderboard_changed ON users;
+CREATE TRIGGER trg_users_leaderboard_changed
+AFTER INSERT OR UPDATE OF total_clicks, username OR DELETE ON users
+FOR EACH STATEMENT EXECUTE FUNCTION notify_leaderboard_changed();
+
+DROP TRIGGER IF EXISTS trg_suspicious_users_leaderboard_changed ON suspicious_users;
+CREATE TRIGGER trg_suspicious_users_leaderboard_changed
+AFTER INSERT OR UPDATE OR DELETE ON suspicious_users
+FOR EACH STATEMENT EXECUTE FUNCTION notify_leaderboard_changed();
+
+DROP TRIGGER IF EXISTS trg_user_restrictions_leaderboard_changed ON user_restrictions;
+CREATE TRIGGER trg_user_restrictions_leaderboard_changed
+AFTER INSERT OR UPDATE OR DELETE ON user_restrictions
+FOR EACH STATEMENT EXECUTE FUNCTION notify_leaderboard_changed();
+
+
+-- Refreshes the view unless another replica already is. The advisory lock is
+-- transaction-scoped, so it is released as soon as the refresh commits.
+CREATE OR REPLACE FUNCTION try_refresh_leaderboard()
+RETURNS boolean
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    IF NOT pg_try_advisory_xact_lock(hashtext('leaderboard_top_1000')) THEN
+        RETURN FALSE;
+    END IF;
+
+    REFRESH MATERIALIZED VIEW CONCURRENTLY leaderboard_top_1000;
+    RETURN TRUE;
+END;
+$$;
@@ -0,0 +1,55 @@
+-- Ranking policy, shared with the Redis boards (see leaderboard-service ranking.rs):
+-- most clicks first, then whoever reached that score first, to the second, then
+-- user id descending. Ranks are ordinal, so no two players share one.
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS score_reached_at TIMESTAMPTZ NOT NULL DEFAULT date_trunc('second', NOW());
+
+UPDATE users SET score_reached_at = date_trunc('second', updated_at);
+
+CREATE OR REPLACE FUNCTION stamp_score_reached_at()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR NEW.total_clicks IS DISTINCT FROM OLD.total_clicks THEN
+        NEW.score_reached_at := date_trunc('second', NOW());
+    END IF;
+    RETURN NEW;
+END;
+$$;
+
+DROP TRIGGER IF EXISTS trg_users_score_reached_at ON users;
+CREATE TRIGGER trg_users_score_reached_at
+BEFORE INSERT OR UPDATE OF total_clicks ON users
+FOR EACH ROW EXECUTE FUNCTION stamp_score_reached_at();
+
+CREATE INDEX IF NOT EXISTS idx_users_leaderboard_order
+ON users (total_clicks DESC, score_reached_at, id DESC)
+WHERE total_clicks > 0;
+
+
+DROP MATERIALIZED VIEW IF EXISTS leaderboard_top_1000;
+
+CREATE MATERIALIZED VIEW leaderboard_top_1000 AS
+SELECT
+    ROW_NUMBER() OVER (ORDER BY u.total_clicks DESC, u.score_reached_at, u.id DESC) as rank,
+    u.id::text as user_id,
+    u.username,
+    u.total_clicks,
+    u.updated_at
+FROM users u
+WHERE u.total_clicks > 0
+AND NOT EXISTS (SELECT 1 FROM hidden_users h WHERE h.user_id = u.id)
+ORDER BY u.total_clicks DESC, u.score_reached_at, u.id DESC
+LIMIT 1000;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_leaderboard_mv_rank
+ON leaderboard_top_1000(rank);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_leaderboard_mv_user_id
+ON leaderboard_top_1000(user_id);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_mv_rank_username
+ON leaderboard_top_1000(rank, username);
+
+GRANT SELECT ON leaderboard_top_1000 TO postgres;
@@ -0,0 +1,19 @@
+-- Code a user shares in their /start ref_<code> invite link. Random rather than
+-- derived from the id, so links don't expose internal ids.
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS referral_code VARCHAR(12) NOT NULL
+DEFAULT substr(md5(uuid_generate_v4()::text), 1, 12);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_referral_code ON users(referral_code);
+
+-- Who invited whom. A user can only be invited once, when they register, and
+-- bonus_clicks records what the inviter was credited for it.
+CREATE TABLE IF NOT EXISTS referrals (
+    invitee_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    inviter_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    bonus_clicks BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (invitee_id <> inviter_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_referrals_inviter_id ON referrals(inviter_id);
@@ -0,0 +1,20 @@
+-- Counts changes to a user's total_clicks. Updates to one row are serialized by
+-- its lock, so the version rises in commit order; the leaderboard keeps the
+-- total with the highest version whatever order the events reach it in.
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS score_version BIGINT NOT NULL DEFAULT 0;
+
+CREATE OR REPLACE FUNCTION stamp_score_reached_at()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        NEW.score_reached_at := date_trunc('second', NOW());
+    ELSIF NEW.total_clicks IS DISTINCT FROM OLD.total_clicks THEN
+        NEW.score_reached_at := date_trunc('second', NOW());
+        NEW.score_version := OLD.score_version + 1;
+    END IF;
+    RETURN NEW;
+END;
+$$;
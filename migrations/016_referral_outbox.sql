@@ -0,0 +1,14 @@
+-- Referral bonuses are published on the click stream after their transaction
+-- commits; published_at records that one was. A bonus still unpublished once
+-- publish_after has passed is republished by the back-fill, which pushes
+-- publish_after out while it works so two instances don't take the same row.
+ALTER TABLE referrals
+ADD COLUMN IF NOT EXISTS published_at TIMESTAMPTZ,
+ADD COLUMN IF NOT EXISTS publish_after TIMESTAMPTZ NOT NULL DEFAULT NOW() + INTERVAL '1 minute';
+
+-- Earlier bonuses were published inline, and can't be told apart from failed ones
+UPDATE referrals SET published_at = created_at WHERE published_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_referrals_unpublished
+ON referrals(publish_after)
+WHERE published_at IS NULL;
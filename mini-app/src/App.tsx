@@ -2,6 +2,8 @@ import { useState, useEffect, Suspense, lazy } from 'react';
 import { useTelegram } from './hooks/useTelegram';
 import { useWebSocket } from './hooks/useWebSocket';
 import { Stats } from './components/Stats';
+import { NearbyRanks } from './components/NearbyRanks';
+import { FriendsLeaderboard } from './components/FriendsLeaderboard';
 import { Loading3D } from './components/Loading3D';
 import { InitialLoading3D } from './components/InitialLoading3D';
 import type { LeaderboardEntry } from './types';
@@ -11,7 +13,7 @@ const Bitcoin3D = lazy(() => import('./components/Bitcoin3D').then(m => ({ defau
 const Leaderboard3D = lazy(() => import('./components/Leaderboard3D').then(m => ({ default: m.Leaderboard3D })));
 
 export function App() {
-  const { user, isReady, hapticFeedback } = useTelegram();
+  const { user, initData, isReady, hapticFeedback } = useTelegram();
   const [totalClicks, setTotalClicks] = useState(0);
   const [isRateLimited, setIsRateLimited] = useState(false);
   const [showInitialLoading, setShowInitialLoading] = useState(true);
@@ -27,6 +29,8 @@ export function App() {
     score,
     rank,
     leaderboard,
+    nearbyRanks,
+    friends,
     error,
     sendClick,
     dbUsername,
@@ -36,7 +40,7 @@ export function App() {
   } = useWebSocket({
     url: wsUrl,
     telegramId: user?.id || 0,
-    username: user?.username || user?.firstName || 'Anonymous',
+    initData,
     enabled: shouldConnect,
   });
 
@@ -141,6 +145,18 @@ export function App() {
           )}
         </div>
 
+        {!leaderboard.some((entry) => entry.rank === rank) && (
+          <NearbyRanks entries={nearbyRanks} rank={rank} />
+        )}
+
+        {friends && (
+          <FriendsLeaderboard
+            entries={friends.entries}
+            userRank={friends.user_rank}
+            totalCount={friends.total_count}
+          />
+        )}
+
         <Suspense fallback={<Loading3D />}>
           <Leaderboard3D entries={leaderboard} />
         </Suspense>
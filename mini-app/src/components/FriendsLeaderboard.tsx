@@ -0,0 +1,42 @@
+import { motion } from 'framer-motion';
+import { HeartHandshake } from 'lucide-react';
+import type { LeaderboardEntry } from '../types';
+import { RankList } from './RankList';
+
+interface FriendsLeaderboardProps {
+  entries: LeaderboardEntry[];
+  userRank: number; // The user's rank among friends, highlighted in the list
+  totalCount: number; // Friends plus the user
+}
+
+export function FriendsLeaderboard({ entries, userRank, totalCount }: FriendsLeaderboardProps) {
+  return (
+    <motion.div
+      initial={{ opacity: 0, y: 20 }}
+      animate={{ opacity: 1, y: 0 }}
+      className="bg-card border border-border rounded-2xl p-5"
+    >
+      <div className="flex items-center justify-between mb-4">
+        <div className="flex items-center gap-3">
+          <div className="inline-flex p-2.5 rounded-xl bg-primary/10">
+            <HeartHandshake className="w-5 h-5 text-primary" strokeWidth={2.5} />
+          </div>
+          <div className="text-sm text-muted-foreground font-medium">Friends</div>
+        </div>
+        {totalCount > 1 && (
+          <div className="text-sm text-muted-foreground tabular-nums">
+            #{userRank} of {totalCount}
+          </div>
+        )}
+      </div>
+
+      {totalCount > 1 ? (
+        <RankList entries={entries} highlightRank={userRank} />
+      ) : (
+        <p className="text-sm text-muted-foreground">
+          No friends here yet. Send /friends to the bot for your invite link, and earn bonus clicks for everyone who joins.
+        </p>
+      )}
+    </motion.div>
+  );
+}
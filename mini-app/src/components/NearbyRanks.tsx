@@ -0,0 +1,32 @@
+import { motion } from 'framer-motion';
+import { Users } from 'lucide-react';
+import type { LeaderboardEntry } from '../types';
+import { RankList } from './RankList';
+
+interface NearbyRanksProps {
+  entries: LeaderboardEntry[];
+  rank: number; // The current user's rank, highlighted in the list
+}
+
+export function NearbyRanks({ entries, rank }: NearbyRanksProps) {
+  if (entries.length === 0) {
+    return null;
+  }
+
+  return (
+    <motion.div
+      initial={{ opacity: 0, y: 20 }}
+      animate={{ opacity: 1, y: 0 }}
+      className="bg-card border border-border rounded-2xl p-5"
+    >
+      <div className="flex items-center gap-3 mb-4">
+        <div className="inline-flex p-2.5 rounded-xl bg-primary/10">
+          <Users className="w-5 h-5 text-primary" strokeWidth={2.5} />
+        </div>
+        <div className="text-sm text-muted-foreground font-medium">Around You</div>
+      </div>
+
+      <RankList entries={entries} highlightRank={rank} />
+    </motion.div>
+  );
+}
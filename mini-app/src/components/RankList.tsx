@@ -0,0 +1,30 @@
+import type { LeaderboardEntry } from '../types';
+
+interface RankListProps {
+  entries: LeaderboardEntry[];
+  highlightRank: number; // Rank of the current user, highlighted in the list
+}
+
+export function RankList({ entries, highlightRank }: RankListProps) {
+  return (
+    <ul className="space-y-2">
+      {entries.map((entry) => {
+        const isUser = entry.rank === highlightRank;
+        return (
+          <li
+            key={entry.rank}
+            className={`flex items-center justify-between rounded-xl px-3 py-2 tabular-nums ${
+              isUser ? 'bg-primary/15 border border-primary/40 font-semibold' : 'bg-background/40'
+            }`}
+          >
+            <span className="flex items-center gap-3">
+              <span className="text-muted-foreground w-12">#{entry.rank}</span>
+              <span className={isUser ? 'text-primary' : 'text-foreground'}>{entry.username}</span>
+            </span>
+            <span className="text-foreground">{entry.totalClicks.toLocaleString()}</span>
+          </li>
+        );
+      })}
+    </ul>
+  );
+}
@@ -18,6 +18,7 @@ const getTelegramWebApp = () => {
 
 export function useTelegram() {
   const [user, setUser] = useState<TelegramUser | null>(null);
+  const [initData, setInitData] = useState('');
   const [isReady, setIsReady] = useState(false);
 
   useEffect(() => {
@@ -32,6 +33,8 @@ export function useTelegram() {
     WebApp.ready();
     WebApp.expand();
 
+    setInitData(WebApp.initData || '');
+
     const tgUser = WebApp.initDataUnsafe?.user;
     if (tgUser) {
       setUser({
@@ -64,6 +67,7 @@ export function useTelegram() {
 
   return {
     user,
+    initData, // Raw signed initData, verified by the backend on WebSocket init
     isReady,
     hapticFeedback,
     showAlert,
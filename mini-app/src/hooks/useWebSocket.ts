@@ -1,21 +1,23 @@
 
 import { useEffect, useState, useCallback, useRef } from 'react';
-import type { ServerMessage, LeaderboardEntry } from '../types';
+import type { ServerMessage, LeaderboardEntry, WSFriendsLeaderboard } from '../types';
 
 interface UseWebSocketProps {
   url: string;
   telegramId: number;
-  username: string;
+  initData: string; // Raw Telegram WebApp initData, verified server-side
   enabled?: boolean; // Only connect when enabled
 }
 
 
 
-export function useWebSocket({ url, telegramId, username, enabled = true }: UseWebSocketProps) {
+export function useWebSocket({ url, telegramId, initData, enabled = true }: UseWebSocketProps) {
   const [isConnected, setIsConnected] = useState(false);
   const [score, setScore] = useState(0);
   const [rank, setRank] = useState<number>(0);
   const [leaderboard, setLeaderboard] = useState<LeaderboardEntry[]>([]);
+  const [nearbyRanks, setNearbyRanks] = useState<LeaderboardEntry[]>([]);
+  const [friends, setFriends] = useState<WSFriendsLeaderboard | null>(null);
   const [error, setError] = useState<string | null>(null);
   const [isRateLimitError, setIsRateLimitError] = useState(false);
   const [userId, setUserId] = useState<string | null>(null); // Store UUID from backend
@@ -30,11 +32,30 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
   const sessionIdRef = useRef<string | null>(null);
 
   const pendingClicksRef = useRef<number>(0); // Accumulated clicks
+  const inFlightClicksRef = useRef<number>(0); // Clicks sent but not yet acknowledged
+  const pendingStartedAtRef = useRef<number>(0); // Client time of the oldest pending click
+  const lastClickAtRef = useRef<number>(0); // Client time of the latest click
+  const inFlightStartedAtRef = useRef<number>(0); // Client time of the in-flight batch's first click
   const batchIntervalRef = useRef<ReturnType<typeof setInterval> | null>(null);
   const lastBatchSentRef = useRef<number>(0); // Timestamp of last batch sent
-  const BATCH_INTERVAL_MS = 2000; // Send batch every 2 seconds
+  const retryAfterRef = useRef<number>(0); // Don't send batches before this timestamp
+  const BATCH_INTERVAL_MS = 1000; // Send batch every second
+  // Largest batch sent at once: the server's default bucket, CLICK_RATE_LIMIT (10) x CLICK_BURST_SECS (2).
+  // A larger backlog drains over several batches.
+  const MAX_BATCH_CLICKS = 20;
   const MIN_BATCH_INTERVAL_MS = 500; // Minimum time between batches (rate limiter)
 
+  // Puts an unacknowledged batch back in front of the pending clicks, to be sent again
+  const requeueInFlightClicks = useCallback(() => {
+    if (inFlightClicksRef.current === 0) {
+      return;
+    }
+    // The in-flight clicks came before anything still pending
+    pendingStartedAtRef.current = inFlightStartedAtRef.current;
+    pendingClicksRef.current += inFlightClicksRef.current;
+    inFlightClicksRef.current = 0;
+  }, []);
+
   const connect = useCallback(() => {
     try {
       const ws = new WebSocket(url);
@@ -46,9 +67,7 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
 
         ws.send(JSON.stringify({
           type: 'init',
-          user_id: '', // Backend will ignore this and return actual UUID
-          telegram_id: telegramId,
-          username: username,
+          init_data: initData,
         }));
       };
 
@@ -73,12 +92,15 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
 
             case 'score_update':
               console.log('Score update received - score:', message.score, 'rank:', message.rank);
+              // Click acks carry rank 0; the batch they answer is now counted
+              if (message.rank === 0) {
+                inFlightClicksRef.current = 0;
+              }
               setScore(message.score ?? 0);
+              // Click acks carry rank 0; rank changes are pushed separately
               if (typeof message.rank === 'number' && message.rank > 0) {
                 console.log('Updating rank to:', message.rank);
                 setRank(message.rank);
-              } else {
-                console.warn('Received invalid rank:', message.rank, '- keeping current rank');
               }
               if (message.user_id) {
                 console.log('Received user_id from backend:', message.user_id);
@@ -98,14 +120,25 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
               setLeaderboard(message.entries);
               break;
 
+            case 'nearby_ranks':
+              setNearbyRanks(message.entries);
+              break;
+
+            case 'friends_leaderboard':
+              setFriends(message);
+              break;
+
             case 'error':
               console.error('Server error:', message.message);
+              requeueInFlightClicks();
               setError(message.message);
               setIsRateLimitError(false);
               break;
 
             case 'rate_limited':
-              console.warn('Rate limited:', message.message);
+              console.warn('Rate limited:', message.message, `retry after ${message.retry_after_ms}ms`);
+              retryAfterRef.current = Date.now() + (message.retry_after_ms ?? 0);
+              requeueInFlightClicks();
               setError(message.message);
               setIsRateLimitError(true);
               break;
@@ -123,6 +156,7 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
       ws.onclose = () => {
         console.log('WebSocket disconnected');
         setIsConnected(false);
+        requeueInFlightClicks();
 
         reconnectTimeoutRef.current = setTimeout(() => {
           console.log('Reconnecting...');
@@ -135,7 +169,7 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
       console.error('Failed to connect WebSocket:', error);
       setError('Failed to connect');
     }
-  }, [url, telegramId, username]);
+  }, [url, initData, requeueInFlightClicks]);
 
   const sendBatch = useCallback(() => {
     const now = Date.now();
@@ -145,7 +179,17 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
       return;
     }
 
-    const clickCount = pendingClicksRef.current;
+    if (now < retryAfterRef.current) {
+      console.debug('Rate limited: waiting for server retry-after');
+      return;
+    }
+
+    if (inFlightClicksRef.current > 0) {
+      console.debug('Waiting for the previous batch to be acknowledged');
+      return;
+    }
+
+    const clickCount = Math.min(pendingClicksRef.current, MAX_BATCH_CLICKS);
 
     if (clickCount === 0) {
       return;
@@ -154,25 +198,39 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
     if (wsRef.current?.readyState === WebSocket.OPEN && userId && sessionId) {
       console.log(`Sending batch of ${clickCount} clicks`);
 
-      pendingClicksRef.current = 0;
+      const batchStartedAt = pendingStartedAtRef.current;
+
+      // Kept in flight until the server acks it; re-queued on rate_limited, error or disconnect
+      pendingClicksRef.current -= clickCount;
+      inFlightClicksRef.current = clickCount;
+      inFlightStartedAtRef.current = batchStartedAt;
+      if (pendingClicksRef.current === 0) {
+        pendingStartedAtRef.current = 0;
+      }
       lastBatchSentRef.current = now;
 
+      // Identity and session are bound to the socket server-side after init.
+      // The batch's click times let the server check them against its click count.
       wsRef.current.send(JSON.stringify({
         type: 'click',
-        user_id: userId,
-        telegram_id: telegramId,
-        session_id: sessionId,
         click_count: clickCount,
+        batch_started_at: batchStartedAt,
+        batch_ended_at: lastClickAtRef.current,
       }));
     } else if (!userId) {
       console.warn('Cannot send batch: user_id not yet received from backend');
     } else if (!sessionId) {
       console.warn('Cannot send batch: session_id not yet received from backend');
     }
-  }, [userId, telegramId, sessionId]);
+  }, [userId, sessionId]);
 
   const sendClick = useCallback(() => {
     if (userId && sessionId) {
+      const now = Date.now();
+      if (pendingClicksRef.current === 0) {
+        pendingStartedAtRef.current = now;
+      }
+      lastClickAtRef.current = now;
       pendingClicksRef.current += 1;
       console.debug(`Accumulated click (total pending: ${pendingClicksRef.current})`);
     } else if (!userId) {
@@ -196,10 +254,9 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
       if (userId_local && sessionId_local) {
         wsRef.current.send(JSON.stringify({
           type: 'click',
-          user_id: userId_local,
-          telegram_id: telegramId,
-          session_id: sessionId_local,
           click_count: clickCount,
+          batch_started_at: pendingStartedAtRef.current,
+          batch_ended_at: lastClickAtRef.current,
         }));
       }
     }
@@ -213,19 +270,23 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
     }
 
     pendingClicksRef.current = 0;
+    inFlightClicksRef.current = 0;
+    pendingStartedAtRef.current = 0;
+    inFlightStartedAtRef.current = 0;
     lastBatchSentRef.current = 0;
-  }, [telegramId]);
+    retryAfterRef.current = 0;
+  }, []);
 
   useEffect(() => {
-    if (enabled && telegramId > 0) {
+    if (enabled && telegramId > 0 && initData) {
       connect();
     }
     return () => disconnect();
-  }, [enabled, telegramId, connect, disconnect]);
+  }, [enabled, telegramId, initData, connect, disconnect]);
 
   useEffect(() => {
     if (userId && sessionId && isConnected) {
-      console.log('Starting click batch interval (every 2 seconds)');
+      console.log('Starting click batch interval (every second)');
 
       if (batchIntervalRef.current) {
         clearInterval(batchIntervalRef.current);
@@ -249,6 +310,8 @@ export function useWebSocket({ url, telegramId, username, enabled = true }: UseW
     score,
     rank,
     leaderboard,
+    nearbyRanks, // Players just above and below the user
+    friends, // The user's rank among who invited them and who they invited
     error,
     sendClick,
     dbUsername, // Database username (priority over Telegram username)
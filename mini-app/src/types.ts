@@ -17,23 +17,19 @@ export interface LeaderboardEntry {
 
 export interface WSInitMessage {
   type: 'init';
-  user_id: string;
-  telegram_id: number;
-  username: string;
+  init_data: string; // Raw Telegram WebApp initData (signed by Telegram)
 }
 
+// User and session are bound to the connection on init, so later messages omit them
 export interface WSClickMessage {
   type: 'click';
-  user_id: string;
-  telegram_id: number;
-  session_id: string;
   click_count?: number; // Number of clicks in this batch (default: 1)
+  batch_started_at?: number; // Client time (ms since epoch) of the batch's first click
+  batch_ended_at?: number; // Client time (ms since epoch) of the batch's last click
 }
 
 export interface WSRefreshMessage {
   type: 'refresh';
-  user_id: string;
-  telegram_id: number;
 }
 
 export interface WSScoreUpdate {
@@ -56,6 +52,20 @@ export interface WSLeaderboardUpdate {
   entries: LeaderboardEntry[];
 }
 
+// The players just above and below the user, the user included
+export interface WSNearbyRanks {
+  type: 'nearby_ranks';
+  entries: LeaderboardEntry[];
+}
+
+// The user ranked against who invited them and who they invited
+export interface WSFriendsLeaderboard {
+  type: 'friends_leaderboard';
+  entries: LeaderboardEntry[];
+  user_rank: number;
+  total_count: number; // Includes the user
+}
+
 export interface WSError {
   type: 'error';
   message: string;
@@ -64,6 +74,14 @@ export interface WSError {
 export interface WSRateLimited {
   type: 'rate_limited';
   message: string;
+  retry_after_ms: number;
 }
 
-export type ServerMessage = WSScoreUpdate | WSSessionInfo | WSLeaderboardUpdate | WSError | WSRateLimited;
+export type ServerMessage =
+  | WSScoreUpdate
+  | WSSessionInfo
+  | WSLeaderboardUpdate
+  | WSNearbyRanks
+  | WSFriendsLeaderboard
+  | WSError
+  | WSRateLimited;
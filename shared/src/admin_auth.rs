@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+/// Rejects calls that don't carry the shared admin token as
+/// `authorization: Bearer <token>`. Guards every admin gRPC service.
+#[derive(Clone)]
+pub struct AdminAuth {
+    token: Arc<str>,
+}
+
+impl AdminAuth {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into().into(),
+        }
+    }
+
+    /// Reads `ADMIN_TOKEN`, shared by the admin servers and their clients. A
+    /// blank token leaves admin access disabled everywhere.
+    pub fn token_from_env() -> Option<String> {
+        non_blank(std::env::var("ADMIN_TOKEN").ok())
+    }
+}
+
+fn non_blank(token: Option<String>) -> Option<String> {
+    token.filter(|token| !token.trim().is_empty())
+}
+
+impl Interceptor for AdminAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let provided = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match provided {
+            Some(token) if constant_time_eq(token.as_bytes(), self.token.as_bytes()) => Ok(request),
+            _ => {
+                crate::record_counter("admin.unauthenticated", 1);
+                Err(Status::unauthenticated("Invalid admin token"))
+            }
+        }
+    }
+}
+
+/// Compares without returning early, so response times don't leak how much of
+/// a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(value) = authorization {
+            request.metadata_mut().insert("authorization", value.parse().unwrap());
+        }
+        request
+    }
+
+    #[test]
+    fn test_admin_auth_accepts_matching_token() {
+        let mut auth = AdminAuth::new("s3cret");
+        assert!(auth.call(request_with(Some("Bearer s3cret"))).is_ok());
+    }
+
+    #[test]
+    fn test_admin_auth_rejects_missing_or_wrong_token() {
+        let mut auth = AdminAuth::new("s3cret");
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cret"), Some("Bearer s3cret2")] {
+            let status = auth.call(request_with(authorization)).unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        }
+    }
+
+    #[test]
+    fn test_blank_admin_token_is_disabled() {
+        assert_eq!(non_blank(None), None);
+        assert_eq!(non_blank(Some(String::new())), None);
+        assert_eq!(non_blank(Some("  \n".to_string())), None);
+        assert_eq!(non_blank(Some("s3cret".to_string())), Some("s3cret".to_string()));
+    }
+}
@@ -60,9 +60,85 @@ impl ServiceConfig {
     }
 }
 
+/// Suspicion scores (0.0-1.0) at which the anti-cheat escalates. A threshold
+/// above 1.0 disables that action.
+#[derive(Debug, Clone)]
+pub struct AntiCheatConfig {
+    pub flag_score: f64,
+    pub shadow_ban_score: f64,
+    pub freeze_score: f64,
+    /// Fastest sustained click rate a person can plausibly reach.
+    pub max_human_clicks_per_second: u32,
+}
+
+impl AntiCheatConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            flag_score: parse_env("ANTI_CHEAT_FLAG_SCORE", "0.3")?,
+            shadow_ban_score: parse_env("ANTI_CHEAT_SHADOW_BAN_SCORE", "0.6")?,
+            freeze_score: parse_env("ANTI_CHEAT_FREEZE_SCORE", "0.9")?,
+            max_human_clicks_per_second: parse_env("ANTI_CHEAT_MAX_HUMAN_CPS", "20")?,
+        })
+    }
+}
+
+impl Default for AntiCheatConfig {
+    fn default() -> Self {
+        Self {
+            flag_score: 0.3,
+            shadow_ban_score: 0.6,
+            freeze_score: 0.9,
+            max_human_clicks_per_second: 20,
+        }
+    }
+}
+
+/// How the leaderboard's `clicks:stream` consumers recover and trim the stream.
+#[derive(Debug, Clone)]
+pub struct StreamConsumerConfig {
+    /// Pending entries idle this long are claimed from whichever consumer had them.
+    pub claim_idle_ms: u64,
+    /// Deliveries after which an entry goes to the dead-letter stream instead.
+    pub max_deliveries: u64,
+    /// How often pending entries are reclaimed and the stream trimmed.
+    pub maintenance_interval_ms: u64,
+}
+
+impl StreamConsumerConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            claim_idle_ms: parse_env("CLICK_STREAM_CLAIM_IDLE_MS", "30000")?,
+            max_deliveries: parse_env("CLICK_STREAM_MAX_DELIVERIES", "5")?,
+            maintenance_interval_ms: parse_env("CLICK_STREAM_MAINTENANCE_INTERVAL_MS", "10000")?,
+        })
+    }
+}
+
+impl Default for StreamConsumerConfig {
+    fn default() -> Self {
+        Self {
+            claim_idle_ms: 30_000,
+            max_deliveries: 5,
+            maintenance_interval_ms: 10_000,
+        }
+    }
+}
+
+fn parse_env<T>(name: &str, default: &str) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .map_err(|e| ServiceError::Internal(format!("Invalid {}: {}", name, e)))
+}
+
 #[derive(Debug, Clone)]
 pub struct BatchConfig {
     pub click_flush_interval_ms: u64,
+    pub click_flush_chunk_size: usize,
     pub leaderboard_broadcast_interval_ms: u64,
 }
 
@@ -75,6 +151,16 @@ impl BatchConfig {
                 .map_err(|e| {
                     ServiceError::Internal(format!("Invalid CLICK_BATCH_FLUSH_INTERVAL_MS: {}", e))
                 })?,
+            click_flush_chunk_size: env::var("CLICK_FLUSH_CHUNK_SIZE")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| {
+                    ServiceError::Internal(
+                        "Invalid CLICK_FLUSH_CHUNK_SIZE: must be a positive integer".to_string(),
+                    )
+                })?,
             leaderboard_broadcast_interval_ms: env::var("LEADERBOARD_BROADCAST_INTERVAL_MS")
                 .unwrap_or_else(|_| "1000".to_string())
                 .parse()
@@ -11,8 +11,8 @@ pub enum ServiceError {
     #[error("Invalid username: {0}")]
     InvalidUsername(String),
 
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    #[error("Rate limit exceeded, retry after {retry_after_ms}ms")]
+    RateLimitExceeded { retry_after_ms: u64 },
 
     #[error("Session not found: {0}")]
     SessionNotFound(String),
@@ -37,6 +37,12 @@ pub enum ServiceError {
 
     #[error("Telegram API error: {0}")]
     Telegram(String),
+
+    #[error("Unauthenticated: {0}")]
+    Unauthenticated(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
 }
 
 impl From<sqlx::Error> for ServiceError {
@@ -63,8 +69,8 @@ impl From<ServiceError> for tonic::Status {
             ServiceError::UserNotFound(msg) => tonic::Status::not_found(msg),
             ServiceError::UserAlreadyExists(msg) => tonic::Status::already_exists(msg),
             ServiceError::InvalidUsername(msg) => tonic::Status::invalid_argument(msg),
-            ServiceError::RateLimitExceeded => {
-                tonic::Status::resource_exhausted("Rate limit exceeded")
+            err @ ServiceError::RateLimitExceeded { .. } => {
+                tonic::Status::resource_exhausted(err.to_string())
             }
             ServiceError::SessionNotFound(msg) => tonic::Status::not_found(msg),
             ServiceError::SessionExpired(msg) => tonic::Status::deadline_exceeded(msg),
@@ -74,6 +80,8 @@ impl From<ServiceError> for tonic::Status {
             ServiceError::Validation(msg) => tonic::Status::invalid_argument(msg),
             ServiceError::Internal(msg) => tonic::Status::internal(msg),
             ServiceError::Telegram(msg) => tonic::Status::internal(format!("Telegram error: {}", msg)),
+            ServiceError::Unauthenticated(msg) => tonic::Status::unauthenticated(msg),
+            ServiceError::Forbidden(msg) => tonic::Status::permission_denied(msg),
         }
     }
 }
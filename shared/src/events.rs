@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::errors::{Result, ServiceError};
+
+/// Redis stream game-service publishes flushed click batches to.
+pub const CLICK_STREAM_KEY: &str = "clicks:stream";
+
+/// Stream of `clicks:stream` entries the leaderboard gave up on after repeated
+/// failures, with the original fields plus where and how often they failed.
+pub const CLICK_DEAD_LETTER_STREAM_KEY: &str = "clicks:stream:dead";
+
+/// Hash of the last sequence number issued per user for `clicks:stream` events.
+pub const CLICK_SEQ_KEY: &str = "clicks:seq";
+
+/// Set of user ids kept off the leaderboard. Their clicks still count, but the
+/// stream consumer removes them from the boards instead of ranking them.
+pub const HIDDEN_USERS_KEY: &str = "leaderboard:hidden_users";
+
+/// One entry on `clicks:stream`: a flushed batch of clicks for a single user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClickStreamEvent {
+    pub user_id: String,
+    pub username: String,
+    /// User's total after the batch was applied.
+    pub total_clicks: i64,
+    /// Clicks in this batch, or the change an admin made to the total, which can
+    /// be negative. `None` on events published before deltas were added.
+    pub delta: Option<i64>,
+    pub session_id: Option<String>,
+    /// Strictly increasing per user in publish order. `None` on events
+    /// published before sequencing was added.
+    pub seq: Option<u64>,
+    pub timestamp: i64,
+    /// When Postgres recorded the user reaching `total_clicks`, in unix seconds.
+    /// `None` on events published before it was carried.
+    pub reached_at: Option<i64>,
+    /// Postgres `score_version` of `total_clicks`. It rises with every change to
+    /// the total in commit order, so consumers can tell a late total from a
+    /// newer one whatever order they were published in. `None` on events
+    /// published before it was carried.
+    pub score_version: Option<i64>,
+}
+
+impl ClickStreamEvent {
+    pub fn from_fields(fields: &HashMap<String, String>) -> Result<Self> {
+        let user_id = fields
+            .get("user_id")
+            .ok_or_else(|| ServiceError::Validation("Missing user_id field".to_string()))?
+            .clone();
+
+        let username = fields
+            .get("username")
+            .ok_or_else(|| ServiceError::Validation("Missing username field".to_string()))?
+            .clone();
+
+        let total_clicks = fields
+            .get("total_clicks")
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| ServiceError::Validation("Invalid total_clicks field".to_string()))?;
+
+        let delta = optional_number(fields, "delta")?;
+        let seq = optional_number(fields, "seq")?;
+        let reached_at = optional_number(fields, "reached_at")?;
+        let score_version = optional_number(fields, "score_version")?;
+
+        let session_id = fields
+            .get("session_id")
+            .filter(|s| !s.is_empty())
+            .cloned();
+
+        let timestamp = fields
+            .get("timestamp")
+            .and_then(|s| s.parse::<i64>().ok())
+            .unwrap_or(0);
+
+        Ok(Self {
+            user_id,
+            username,
+            total_clicks,
+            delta,
+            session_id,
+            seq,
+            timestamp,
+            reached_at,
+            score_version,
+        })
+    }
+}
+
+fn optional_number<T: std::str::FromStr>(fields: &HashMap<String, String>, name: &str) -> Result<Option<T>> {
+    fields
+        .get(name)
+        .map(|s| {
+            s.parse::<T>()
+                .map_err(|_| ServiceError::Validation(format!("Invalid {} field", name)))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_full_event() {
+        let event = ClickStreamEvent::from_fields(&fields(&[
+            ("user_id", "user-a"),
+            ("username", "alice"),
+            ("total_clicks", "120"),
+            ("delta", "20"),
+            ("session_id", "session-a"),
+            ("seq", "7"),
+            ("timestamp", "1700000000"),
+            ("reached_at", "1699999990"),
+            ("score_version", "12"),
+        ]))
+        .unwrap();
+
+        assert_eq!(event.total_clicks, 120);
+        assert_eq!(event.delta, Some(20));
+        assert_eq!(event.session_id.as_deref(), Some("session-a"));
+        assert_eq!(event.seq, Some(7));
+        assert_eq!(event.reached_at, Some(1_699_999_990));
+        assert_eq!(event.score_version, Some(12));
+    }
+
+    #[test]
+    fn test_parse_legacy_event() {
+        let event = ClickStreamEvent::from_fields(&fields(&[
+            ("user_id", "user-a"),
+            ("username", "alice"),
+            ("total_clicks", "120"),
+            ("timestamp", "1700000000"),
+        ]))
+        .unwrap();
+
+        assert_eq!(event.delta, None);
+        assert_eq!(event.session_id, None);
+        assert_eq!(event.seq, None);
+        assert_eq!(event.reached_at, None);
+        assert_eq!(event.score_version, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_numbers() {
+        let result = ClickStreamEvent::from_fields(&fields(&[
+            ("user_id", "user-a"),
+            ("username", "alice"),
+            ("total_clicks", "120"),
+            ("delta", "lots"),
+        ]));
+
+        assert!(matches!(result, Err(ServiceError::Validation(_))));
+    }
+}
@@ -1,10 +1,16 @@
+pub mod admin_auth;
 pub mod config;
 pub mod errors;
+pub mod events;
+pub mod sharding;
 pub mod telemetry;
 pub mod types;
 
-pub use config::{DatabaseConfig, RedisConfig, ServiceConfig};
+pub use admin_auth::AdminAuth;
+pub use config::{AntiCheatConfig, DatabaseConfig, RedisConfig, ServiceConfig, StreamConsumerConfig};
 pub use errors::{Result, ServiceError};
+pub use events::ClickStreamEvent;
+pub use sharding::{Shard, ShardMap};
 pub use telemetry::{init_metrics, init_tracing, record_counter, record_gauge, record_timing, shutdown};
 pub use types::{
     ClickEvent, GlobalStats, LeaderboardEntry, Session, SessionId, SessionStats, User, UserId,
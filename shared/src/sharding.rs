@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+use std::env;
+
+use crate::errors::{Result, ServiceError};
+
+/// Points each shard gets on the ring. More points spread users more evenly.
+const VIRTUAL_NODES: u32 = 128;
+
+/// One game-service instance. `id` is the instance's `INSTANCE_ID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shard {
+    pub id: String,
+    pub url: String,
+}
+
+/// Consistent-hash ring assigning users to game-service shards. Bot-service and
+/// game-service build it from the same `SHARD_MAP`, so they agree on which
+/// instance owns a user, and adding a shard only moves the users it takes over.
+#[derive(Debug, Clone)]
+pub struct ShardMap {
+    shards: Vec<Shard>,
+    ring: Vec<(u64, usize)>,
+}
+
+impl ShardMap {
+    pub fn new(shards: Vec<Shard>) -> Result<Self> {
+        if shards.is_empty() {
+            return Err(ServiceError::Internal("Shard map has no shards".to_string()));
+        }
+
+        let mut seen = HashSet::new();
+        for shard in &shards {
+            if !seen.insert(shard.id.as_str()) {
+                return Err(ServiceError::Internal(format!("Duplicate shard id: {}", shard.id)));
+            }
+        }
+
+        let mut ring: Vec<(u64, usize)> = shards
+            .iter()
+            .enumerate()
+            .flat_map(|(index, shard)| {
+                (0..VIRTUAL_NODES).map(move |node| (stable_hash(&format!("{}#{}", shard.id, node)), index))
+            })
+            .collect();
+        ring.sort_unstable();
+
+        Ok(Self { shards, ring })
+    }
+
+    /// Parses `id=url` pairs separated by commas, e.g.
+    /// `game-1=http://game-service-1:50051,game-2=http://game-service-2:50051`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let shards = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (id, url) = entry.split_once('=').ok_or_else(|| {
+                    ServiceError::Internal(format!("Invalid SHARD_MAP entry: {}", entry))
+                })?;
+
+                Ok(Shard {
+                    id: id.trim().to_string(),
+                    url: url.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::new(shards)
+    }
+
+    /// Reads `SHARD_MAP`, falling back to a single shard that owns every user.
+    pub fn from_env(default_shard: Shard) -> Result<Self> {
+        match env::var("SHARD_MAP") {
+            Ok(spec) if !spec.trim().is_empty() => Self::parse(&spec),
+            _ => Self::new(vec![default_shard]),
+        }
+    }
+
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    pub fn index_of(&self, shard_id: &str) -> Option<usize> {
+        self.shards.iter().position(|shard| shard.id == shard_id)
+    }
+
+    pub fn shard_index_for(&self, user_id: &str) -> usize {
+        let hash = stable_hash(user_id);
+        let point = self.ring.partition_point(|(node, _)| *node < hash);
+        self.ring[point % self.ring.len()].1
+    }
+
+    pub fn shard_for(&self, user_id: &str) -> &Shard {
+        &self.shards[self.shard_index_for(user_id)]
+    }
+}
+
+/// FNV-1a with a MurmurHash3 finalizer. Unlike `DefaultHasher` it is fixed across
+/// services and Rust releases, which the ring depends on.
+pub fn stable_hash(key: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+
+    // FNV alone clusters keys that share a long prefix, like UUIDs
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard_map(count: usize) -> ShardMap {
+        ShardMap::new(
+            (1..=count)
+                .map(|n| Shard {
+                    id: format!("game-{}", n),
+                    url: format!("http://game-service-{}:50051", n),
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    fn user_ids() -> Vec<String> {
+        (0..10_000).map(|n| format!("00000000-0000-4000-8000-{:012}", n)).collect()
+    }
+
+    #[test]
+    fn test_parse_shard_map() {
+        let map = ShardMap::parse("game-1=http://a:50051, game-2=http://b:50051").unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.shards()[1].id, "game-2");
+        assert_eq!(map.shards()[1].url, "http://b:50051");
+        assert_eq!(map.index_of("game-2"), Some(1));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_maps() {
+        assert!(ShardMap::parse("").is_err());
+        assert!(ShardMap::parse("game-1").is_err());
+        assert!(ShardMap::parse("game-1=http://a,game-1=http://b").is_err());
+    }
+
+    #[test]
+    fn test_single_shard_owns_everyone() {
+        let map = shard_map(1);
+        assert!(user_ids().iter().all(|user_id| map.shard_index_for(user_id) == 0));
+    }
+
+    #[test]
+    fn test_users_spread_across_shards() {
+        let map = shard_map(3);
+        let mut counts = [0usize; 3];
+        for user_id in user_ids() {
+            counts[map.shard_index_for(&user_id)] += 1;
+        }
+
+        for count in counts {
+            assert!(count > 2_000, "Uneven distribution: {:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_adding_shard_only_moves_users_to_it() {
+        let before = shard_map(3);
+        let after = shard_map(4);
+
+        let mut moved = 0;
+        for user_id in user_ids() {
+            let old = before.shard_for(&user_id);
+            let new = after.shard_for(&user_id);
+            if old != new {
+                assert_eq!(new.id, "game-4");
+                moved += 1;
+            }
+        }
+
+        assert!(moved < 4_000, "Too many users moved: {}", moved);
+    }
+}
@@ -17,10 +17,6 @@ impl UserId {
             .map(UserId)
             .map_err(|e| ServiceError::Validation(format!("Invalid user ID: {}", e)))
     }
-
-    pub fn to_string(&self) -> String {
-        self.0.to_string()
-    }
 }
 
 impl Default for UserId {
@@ -48,10 +44,6 @@ impl SessionId {
             .map(SessionId)
             .map_err(|e| ServiceError::Validation(format!("Invalid session ID: {}", e)))
     }
-
-    pub fn to_string(&self) -> String {
-        self.0.to_string()
-    }
 }
 
 impl Default for SessionId {